@@ -0,0 +1,99 @@
+//! Command line arguments of the server.
+
+use std::path::{Path, PathBuf};
+use getopts::Options;
+use wrust_types::{Error, Result};
+use constants::{CONFIG_DIRECTORY, SERVER_CONFIG_NAME};
+
+
+/// Parsed command line arguments
+pub struct Args {
+	/// Path to the server configuration file
+	pub config: PathBuf,
+	/// Only parse and validate the configuration
+	pub test_config: bool,
+	/// Write the process identifier to the file
+	pub pid_file: Option<PathBuf>,
+	/// Print the version and registered modules
+	pub version: bool,
+	/// Print usage
+	pub help: bool,
+	/// Configuration settings overridden as pairs of paths and values
+	pub overrides: Vec<(String, String)>,
+}
+
+
+impl Args {
+	/// Parse `args` given without the program name.
+	pub fn parse(args: &[String]) -> Result<Args> {
+		let matches = match options().parse(args) {
+			Ok(matches) => matches,
+			Err(msg) => return Error::new("Invalid command line arguments").because(msg).result(),
+		};
+
+		let mut overrides = Vec::new();
+		for item in matches.opt_strs("set") {
+			match item.find('=') {
+				Some(pos) => overrides.push((item[..pos].trim().to_string(), item[pos + 1..].trim().to_string())),
+				None => return Error::new(format!("Override '{}' is not in the form path=value", item)).result(),
+			};
+		}
+
+		// The log level is the shortcut for overriding the setting
+		if let Some(level) = matches.opt_str("log-level") {
+			overrides.push(("log.level".to_string(), format!("\"{}\"", level)));
+		}
+
+		Ok(Args {
+			config: match matches.opt_str("config") {
+				Some(path) => PathBuf::from(path),
+				None => Path::new(CONFIG_DIRECTORY).join(SERVER_CONFIG_NAME),
+			},
+			test_config: matches.opt_present("test-config"),
+			pid_file: matches.opt_str("pid-file").map(PathBuf::from),
+			version: matches.opt_present("version"),
+			help: matches.opt_present("help"),
+			overrides: overrides,
+		})
+	}
+
+	/// Get the usage text for the `program`.
+	pub fn usage(program: &str) -> String {
+		options().usage(&format!("Usage: {} [options]", program))
+	}
+}
+
+
+fn options() -> Options {
+	let mut options = Options::new();
+	options.optopt("c", "config", &format!("path to the configuration file, {}/{} by default", CONFIG_DIRECTORY, SERVER_CONFIG_NAME), "PATH");
+	options.optflag("t", "test-config", "parse and validate the configuration then exit");
+	options.optopt("l", "log-level", "override the logging level", "LEVEL");
+	options.optopt("p", "pid-file", "write the process identifier to the file", "PATH");
+	options.optmulti("s", "set", "override the configuration setting, e.g. core.worker_count=8 or servers.[0].listen.port=8080", "PATH=VALUE");
+	options.optflag("v", "version", "print the version and registered modules then exit");
+	options.optflag("h", "help", "print this help then exit");
+	options
+}
+
+
+#[test]
+fn test_args_parsing() {
+	let args: Vec<String> = vec!["-c", "/etc/wrustlr.conf", "--set", "core.worker_count=8", "-s", "log.device = stdout", "--log-level", "trace", "-t"]
+		.into_iter()
+		.map(|arg| arg.to_string())
+		.collect();
+
+	let parsed = Args::parse(&args).unwrap();
+	assert_eq!(parsed.config, PathBuf::from("/etc/wrustlr.conf"));
+	assert!(parsed.test_config);
+	assert!(!parsed.version);
+	assert_eq!(parsed.overrides, vec![
+		("core.worker_count".to_string(), "8".to_string()),
+		("log.device".to_string(), "stdout".to_string()),
+		("log.level".to_string(), "\"trace\"".to_string()),
+	]);
+
+	assert!(Args::parse(&vec!["--set".to_string(), "core.worker_count".to_string()]).is_err());
+	assert!(Args::parse(&vec!["--unknown".to_string()]).is_err());
+}
@@ -1,23 +1,45 @@
 #[macro_use] extern crate log;
+#[macro_use] extern crate chan;
 extern crate chan_signal;
+extern crate getopts;
+extern crate libc;
 extern crate wrust_types;
 extern crate wrust_conf;
 extern crate wrust_module;
 extern crate wrust_log;
 extern crate wrust_core;
 extern crate wrust_mod_echo;
+extern crate wrust_mod_http;
+extern crate wrust_mod_static;
+extern crate wrust_mod_proxy;
 
 mod constants;
+mod cli;
+mod pid;
+mod upgrade;
+mod systemd;
 
-use std::path::Path;
+use std::env;
+use std::thread;
+use std::process;
+use std::io::{self, Write};
 use chan_signal::Signal;
-use wrust_conf::{Conf, FromConf};
-use wrust_module::Facility;
+use wrust_types::{Error, Result};
+use wrust_conf::{Conf, FromConf, SharedConf, Validation};
+use wrust_module::{Facility, Instance};
+use wrust_log::conf::LogConf;
+use wrust_core::conf::AdminConf;
 use wrust_core::net::core::{CoreConf, Core};
-use wrust_core::net::server::ServerConf;
+use wrust_core::net::control::{Admin, Command, Controller, Response};
+use wrust_core::net::server;
+use wrust_core::net::server::{ServerConf, Reload, Inherited};
 use wrust_core::module::Factory;
 use wrust_mod_echo as wmod_echo;
-use constants::{CONFIG_DIRECTORY, SERVER_CONFIG_NAME};
+use wrust_mod_http as wmod_http;
+use wrust_mod_static as wmod_static;
+use wrust_mod_proxy as wmod_proxy;
+use cli::Args;
+use pid::PidFile;
 
 macro_rules! config_failed {
 	($msg:expr, $details:expr) => ({
@@ -27,9 +49,44 @@ macro_rules! config_failed {
 }
 
 fn main() {
+	// Parse command line arguments
+	let program = env::args().next().unwrap_or("wrust-server".to_string());
+	let args = match Args::parse(&env::args().skip(1).collect::<Vec<String>>()) {
+		Ok(args) => args,
+		Err(msg) => {
+			let _ = writeln!(io::stderr(), "{}\n\n{}", msg, Args::usage(&program));
+			process::exit(2);
+		}
+	};
+
+	if args.help {
+		println!("{}", Args::usage(&program));
+		return;
+	}
+
+	if args.version {
+		println!("Wrustlr v{}", env!("CARGO_PKG_VERSION"));
+		for &(category, ref name, ref version) in modules(&Conf::empty()).modules() {
+			println!("    {:?}:{} v{}", category, name, version);
+		}
+		return;
+	}
+
+	if args.test_config {
+		match load(&args).and_then(|config| validate(&config, &modules(&config))) {
+			Ok(_) => {
+				println!("Configuration {} is valid", args.config.display());
+				return;
+			},
+			Err(msg) => {
+				let _ = writeln!(io::stderr(), "Configuration {} is invalid\n{}", args.config.display(), msg);
+				process::exit(1);
+			}
+		}
+	}
+
 	// Load configuration
-	let server_config_path = Path::new(CONFIG_DIRECTORY).join(SERVER_CONFIG_NAME);
-	let server_config = match Conf::from_file(server_config_path.as_path()) {
+	let server_config = match load(&args) {
 		Ok(config) => config,
 		Err(msg) => {
 			config_failed!("Server configuration load failed with message '{:?}'", msg)
@@ -37,13 +94,31 @@ fn main() {
 	};
 
 	// Initialize logger
-	if let Err(msg) = wrust_log::init_from_conf(&server_config, "log") {
-		config_failed!("Server configuration load failed with message '{:?}'", msg)
-	}
+	let log_level = match wrust_log::init_from_conf(&server_config, "log") {
+		Ok(level) => level,
+		Err(msg) => config_failed!("Server configuration load failed with message '{:?}'", msg)
+	};
 
 	// Print welcome message
 	info!("Wrustlr v{} ", env!("CARGO_PKG_VERSION"));
+
+	// The file is removed when the server exits
+	let _pid_file = match args.pid_file {
+		Some(ref path) => match PidFile::create(path) {
+			Ok(pid_file) => Some(pid_file),
+			Err(msg) => config_failed!("PID file creation failed with message '{}'", msg)
+		},
+		None => None
+	};
 	
+	// Load and register modules
+	let module_factory = modules(&server_config);
+
+	// Check the whole configuration and report all problems found at once
+	if let Err(msg) = validate(&server_config, &module_factory) {
+		config_failed!("Server configuration validation failed with message '{}'", msg)
+	}
+
 	// Parse configuration
 	let core_settings = match CoreConf::from_conf(&server_config, "core") {
 		Ok(settings) => settings,
@@ -55,46 +130,235 @@ fn main() {
 		Err(msg) => config_failed!("Servers parse failed with message '{}'", msg)
 	};
 
-	// Load and register modules
-	let mut module_factory = Factory::new(&server_config);
-	// + echo
-	module_factory.register(
-		wmod_echo::Module::category(),
-		wmod_echo::Module::name(),
-		wmod_echo::Module::version(),
-		|c: &Conf, xp: &String| { Box::new(wmod_echo::Module::new(c, xp)) });
+	let admin_settings = if server_config.lookup("admin").is_some() {
+		match AdminConf::from_conf(&server_config, "admin") {
+			Ok(settings) => Some(settings),
+			Err(msg) => config_failed!("Admin settings parse failed with message '{}'", msg)
+		}
+	}
+	else {
+		None
+	};
 
 	// Subscribe to signals we'd like to catch
-	let signal_listener = chan_signal::notify(&[Signal::INT, Signal::TERM]);
+	let signal_listener = chan_signal::notify(&[Signal::INT, Signal::TERM, Signal::QUIT, Signal::HUP, Signal::USR2]);
+
+	// Listener sockets passed by the process which is upgraded to this one
+	let mut inherited = Inherited::from_env();
 
 	// Startup the server
-    info!("Normal server startup");
-
-	match Core::start(core_settings, &module_factory, servers) {
-		Ok(channel) => {
-		    info!("Server ready and listening. Send INT or TERM signal to terminate.");
-		    loop {
-		    	match signal_listener.recv().unwrap() {
-		    		Signal::INT | Signal::TERM => {
-					    if let Err(msg) = channel.send("shutdown") {
-					    	error!("Send SHUTDOWN request failed with message {}", msg);
-					    }
-					    else {
-							if let Err(msg) = channel.recv() {
-								error!("Read SHUTDOWN response failed with message {}", msg);
-							}
-							else {
-							}
-					    }
-
-					    break;
-		    		},
-		    		_ => unreachable!()
-		    	}
-		    }
+	if inherited.is_empty() {
+	    info!("Normal server startup");
+	}
+	else {
+	    info!("Server startup with inherited sockets");
+	}
+
+	// Modules read the configuration published last
+	let shared_config = SharedConf::new(server_config);
+
+	match Core::start(core_settings, shared_config.clone(), &module_factory, servers.clone(), &mut inherited) {
+		Ok((controller, core_thread)) => {
+			inherited.close_unused();
+			inherited.notify_ready();
+
+			let hook_level = log_level.clone();
+			let controller = controller.with_log_level(move |level| hook_level.set(level));
+
+			// Serve admin commands if the admin socket is configured
+			let _admin = match admin_settings {
+				Some(ref settings) => match Admin::start(settings, controller.clone()) {
+					Ok(admin) => Some(admin),
+					Err(msg) => {
+						error!("{}", msg);
+						None
+					}
+				},
+				None => None
+			};
+
+			// The core thread finishes when the server shuts down
+			let (done_sender, done_receiver) = chan::sync(0);
+			thread::spawn(move || {
+				if let Err(_) = core_thread.join() {
+					error!("Server core terminated abnormally");
+				}
+
+				done_sender.send(());
+			});
+
+			systemd::notify("READY=1");
+			systemd::watchdog(controller.clone());
+
+			info!("Server ready and listening. Send INT or TERM signal to shutdown gracefully, QUIT to terminate, HUP to reload the configuration or USR2 to upgrade the binary.");
+
+			// Signals shutdown the server the same way the admin commands do. The first INT or TERM
+			// signal drains connections and the second one or QUIT drops them immediately.
+			let mut servers = servers;
+			let mut draining = false;
+			let mut done = false;
+
+			while !done {
+				chan_select! {
+					signal_listener.recv() -> signal => {
+						match signal {
+							Some(Signal::HUP) => {
+								info!("Received HUP signal");
+								systemd::notify("RELOADING=1");
+								match reload(&args, &controller, &log_level, &shared_config, &servers) {
+									Ok(new_servers) => servers = new_servers,
+									Err(msg) => error!("Configuration reload failed with message '{}'", msg),
+								};
+								systemd::notify("READY=1");
+							},
+							Some(Signal::USR2) if !draining => {
+								info!("Received USR2 signal");
+								match upgrade::start(&controller) {
+									Ok(pid) => {
+										info!("New process {} accepts connections", pid);
+										systemd::notify(&format!("MAINPID={}", pid));
+										execute(&controller, Command::Shutdown);
+										draining = true;
+									},
+									Err(msg) => error!("Binary upgrade failed with message '{}'", msg),
+								};
+							},
+							Some(Signal::INT) | Some(Signal::TERM) if !draining => {
+								systemd::notify("STOPPING=1");
+								execute(&controller, Command::Shutdown);
+								draining = true;
+							},
+							Some(Signal::INT) | Some(Signal::TERM) | Some(Signal::QUIT) => {
+								if !draining {
+									systemd::notify("STOPPING=1");
+								}
+								execute(&controller, Command::Terminate);
+								draining = true;
+							},
+							_ => ()
+						}
+					},
+					done_receiver.recv() => {
+						// The server is shut down with the admin command
+						if !draining {
+							systemd::notify("STOPPING=1");
+						}
+						done = true;
+					},
+				}
+			}
 		},
 		Err(e) => {
 			error!("{}", e);
 		}
 	}
 }
+
+
+/// Create the module factory with all modules registered.
+fn modules(config: &Conf) -> Factory {
+	let mut module_factory = Factory::new(config);
+	// + echo
+	module_factory.register(
+		wmod_echo::Module::category(),
+		wmod_echo::Module::name(),
+		wmod_echo::Module::version(),
+		|c: &Conf, xp: &String| { Instance::Stream(Box::new(wmod_echo::Module::new(c, xp))) });
+	// + echo_datagram
+	module_factory.register(
+		wmod_echo::DatagramModule::category(),
+		wmod_echo::DatagramModule::name(),
+		wmod_echo::DatagramModule::version(),
+		|c: &Conf, xp: &String| { Instance::Datagram(Box::new(wmod_echo::DatagramModule::new(c, xp))) });
+	// + http
+	module_factory.register(
+		wmod_http::Module::category(),
+		wmod_http::Module::name(),
+		wmod_http::Module::version(),
+		|c: &Conf, xp: &String| { Instance::Stream(Box::new(wmod_http::Module::new(c, xp))) });
+	// + static
+	module_factory.register(
+		wmod_static::Module::category(),
+		wmod_static::Module::name(),
+		wmod_static::Module::version(),
+		|c: &Conf, xp: &String| { Instance::Stream(Box::new(wmod_static::Module::new(c, xp))) });
+	// + proxy
+	module_factory.register(
+		wmod_proxy::Module::category(),
+		wmod_proxy::Module::name(),
+		wmod_proxy::Module::version(),
+		|c: &Conf, xp: &String| { Instance::Stream(Box::new(wmod_proxy::Module::new(c, xp))) });
+
+	module_factory
+}
+
+
+/// Execute the `command` and log the failure.
+fn execute(controller: &Controller, command: Command) {
+	match controller.execute(command.clone()) {
+		Ok(Response::Done) => (),
+		Ok(response) => error!("{:?} request failed with response {:?}", command, response),
+		Err(msg) => error!("{:?} request failed with message {}", command, msg),
+	};
+}
+
+
+/// Load the configuration file with settings overridden from the command line.
+fn load(args: &Args) -> Result<Conf> {
+	let config = try!(Conf::from_file(args.config.as_path()));
+	config.with_overrides(&args.overrides)
+}
+
+
+/// Check all settings the server uses and fail with every problem found.
+fn validate(config: &Conf, module_factory: &Factory) -> Result<(LogConf, Vec<ServerConf>)> {
+	let mut validation = Validation::new();
+
+	let log_settings = validation.check(LogConf::from_conf(config, "log"));
+	validation.unknown_keys(config, "log", LogConf::keys());
+
+	validation.check(CoreConf::from_conf(config, "core"));
+	validation.unknown_keys(config, "core", CoreConf::keys());
+
+	let servers = server::validate(config, "servers", module_factory, &mut validation);
+
+	if config.lookup("admin").is_some() {
+		validation.check(AdminConf::from_conf(config, "admin"));
+		validation.unknown_keys(config, "admin", AdminConf::keys());
+	}
+
+	try!(validation.result());
+
+	match log_settings {
+		Some(log_settings) => Ok((log_settings, servers)),
+		None => Error::new("Logging settings are invalid").result(),
+	}
+}
+
+
+/// Read the configuration again, validate it, apply changes to the running server
+/// and publish the new configuration. Core settings and the logging device take
+/// effect after restart only.
+fn reload(args: &Args, controller: &Controller, log_level: &wrust_log::Level, shared_config: &SharedConf, previous_servers: &Vec<ServerConf>) -> Result<Vec<ServerConf>> {
+	let config = try!(load(args));
+	let previous_config = shared_config.snapshot();
+
+	// Validate the configuration completely before anything is applied
+	let module_factory = modules(&config);
+	let (log_settings, servers) = try!(validate(&config, &module_factory));
+
+	let reload = try!(Reload::prepare(&module_factory, &previous_config, previous_servers, &config, &servers));
+
+	match try!(controller.reload(reload)) {
+		Response::Done => (),
+		Response::Failed(msg) => return Error::new(msg).result(),
+		response => return Error::new(format!("Unexpected response {:?}", response)).result(),
+	};
+
+	log_level.set(log_settings.level);
+	info!("Logging level is {}", log_settings.level);
+
+	shared_config.publish(config);
+
+	Ok(servers)
+}
@@ -0,0 +1,46 @@
+//! File with the identifier of the server process.
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use libc;
+use wrust_types::{Error, Result};
+
+
+/// The file is removed when the process which wrote it exits. The process the binary
+/// is upgraded to writes its own identifier so the file is kept on upgrade.
+pub struct PidFile {
+	path: PathBuf,
+	pid: String,
+}
+
+
+impl PidFile {
+	/// Write the identifier of the current process to the file at `path`.
+	pub fn create(path: &Path) -> Result<PidFile> {
+		let pid = format!("{}", unsafe { libc::getpid() });
+
+		let result = File::create(path).and_then(|mut file| file.write_all(format!("{}\n", pid).as_bytes()));
+		if let Err(msg) = result {
+			return Error::new(format!("Cannot write the PID file {}", path.display())).because(msg).result();
+		}
+
+		Ok(PidFile {
+			path: path.to_path_buf(),
+			pid: pid,
+		})
+	}
+}
+
+
+impl Drop for PidFile {
+	fn drop(&mut self) {
+		let mut content = String::new();
+
+		if let Ok(_) = File::open(&self.path).and_then(|mut file| file.read_to_string(&mut content)) {
+			if content.trim() == self.pid {
+				let _ = fs::remove_file(&self.path);
+			}
+		}
+	}
+}
@@ -0,0 +1,59 @@
+//! Service state notifications sent to systemd.
+
+use std::env;
+use std::thread;
+use std::time::Duration;
+use std::os::unix::net::UnixDatagram;
+use libc;
+use wrust_core::net::control::{Command, Controller, Response};
+
+
+/// Send the `state` to the service manager if the server runs under systemd.
+/// The upgraded process notifies through the same socket so the service should
+/// have `NotifyAccess=all`.
+pub fn notify(state: &str) {
+	let path = match env::var("NOTIFY_SOCKET") {
+		Ok(path) => path,
+		Err(_) => return,
+	};
+
+	// Abstract socket addresses cannot be used with the standard library
+	if path.starts_with('@') {
+		warn!("systemd notification socket {} is not supported", path);
+		return;
+	}
+
+	let result = UnixDatagram::unbound().and_then(|socket| socket.send_to(state.as_bytes(), &path));
+	if let Err(msg) = result {
+		warn!("Cannot notify systemd with '{}' because {}", state, msg);
+	}
+}
+
+
+/// Start pinging the systemd watchdog if it is enabled for the server. The ping
+/// is sent only while the core event loop responds to commands.
+pub fn watchdog(controller: Controller) {
+	let interval = match env::var("WATCHDOG_USEC").ok().and_then(|usec| usec.parse::<u64>().ok()) {
+		Some(usec) if usec > 0 => Duration::from_millis(usec / 2000),
+		_ => return,
+	};
+
+	// Without the process identifier the watchdog is set for whoever reads it
+	if let Some(pid) = env::var("WATCHDOG_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) {
+		if pid != unsafe { libc::getpid() } as u32 {
+			return;
+		}
+	}
+
+	thread::spawn(move || {
+		loop {
+			thread::sleep(interval);
+
+			match controller.execute(Command::Status) {
+				Ok(Response::Status(_)) => notify("WATCHDOG=1"),
+				// The core has stopped
+				_ => break,
+			};
+		}
+	});
+}
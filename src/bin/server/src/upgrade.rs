@@ -0,0 +1,147 @@
+//! Binary upgrade which passes listener sockets to the new process.
+
+use std::env;
+use std::thread;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::io::{RawFd, FromRawFd};
+use std::process;
+use std::sync::mpsc;
+use std::time::Duration;
+use libc;
+use wrust_types::{Error, Result};
+use wrust_core::net::control::{Command, Controller, Response};
+use wrust_core::net::server::{Inherited, INHERITED_ENV, READY_ENV};
+
+/// Time in milliseconds the new process has to take over the sockets in.
+const READY_TIMEOUT: u64 = 10000;
+
+
+/// Start the new process of the current binary and pass listener sockets to it.
+/// The function returns the identifier of the new process when it accepts connections.
+pub fn start(controller: &Controller) -> Result<u32> {
+	let sockets = match try!(controller.execute(Command::Handoff)) {
+		Response::Sockets(sockets) => sockets,
+		Response::Failed(msg) => return Error::new(msg).result(),
+		response => return Error::new(format!("Unexpected response {:?}", response)).result(),
+	};
+
+	let sockets: Vec<(String, RawFd, bool)> = sockets
+		.into_iter()
+		.map(|socket| (socket.address, socket.fd, socket.activated))
+		.collect();
+
+	let result = spawn(&sockets);
+
+	// The old process keeps serving so it owns socket files again
+	if result.is_err() {
+		if let Err(msg) = controller.execute(Command::Reclaim) {
+			error!("{}", msg);
+		}
+	}
+
+	result
+}
+
+
+fn spawn(sockets: &[(String, RawFd, bool)]) -> Result<u32> {
+	let binary = match env::current_exe() {
+		Ok(binary) => binary,
+		Err(msg) => return Error::new("Cannot find the server binary").because(msg).result(),
+	};
+
+	let (ready_read, ready_write) = try!(pipe());
+	let mut ready = unsafe { File::from_raw_fd(ready_read) };
+
+	// Sockets are inherited by the new process only
+	let mut inheritable = vec![ready_write];
+	inheritable.extend(sockets.iter().map(|&(_, fd, _)| fd));
+
+	let mut result = Ok(());
+	for fd in &inheritable {
+		if result.is_ok() {
+			result = set_inheritable(*fd, true);
+		}
+	}
+
+	let child = match result {
+		Ok(_) => {
+			info!("Starting {} with sockets {}", binary.display(), Inherited::encode(sockets));
+
+			process::Command::new(&binary)
+				.args(&env::args().skip(1).collect::<Vec<String>>())
+				.env(INHERITED_ENV, Inherited::encode(sockets))
+				.env(READY_ENV, ready_write.to_string())
+				// The watchdog is pinged by the new process when it becomes the main one
+				.env_remove("WATCHDOG_PID")
+				.spawn()
+				.map_err(|msg| Error::new(format!("Cannot start {}", binary.display())).because(msg))
+		},
+		Err(msg) => Err(msg),
+	};
+
+	for fd in &inheritable {
+		let _ = set_inheritable(*fd, false);
+	}
+
+	// The pipe is closed without notification when the new process exits
+	drop(unsafe { File::from_raw_fd(ready_write) });
+
+	let mut child = try!(child);
+
+	let (tx, rx) = mpsc::channel();
+	thread::spawn(move || {
+		let mut buf = [0u8; 1];
+		let _ = tx.send(ready.read(&mut buf).unwrap_or(0) > 0);
+	});
+
+	match rx.recv_timeout(Duration::from_millis(READY_TIMEOUT)) {
+		Ok(true) => Ok(child.id()),
+		_ => {
+			let _ = child.kill();
+			let _ = child.wait();
+			Error::new(format!("The new process {} did not take over sockets", child.id())).result()
+		},
+	}
+}
+
+
+/// Create the pipe and return its read and write ends.
+fn pipe() -> Result<(RawFd, RawFd)> {
+	let mut fds = [0 as libc::c_int; 2];
+
+	if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+		return Error::new("Cannot create the pipe").because(::std::io::Error::last_os_error()).result();
+	}
+
+	// Neither end is inherited unless asked explicitly
+	for fd in &fds {
+		try!(set_inheritable(*fd, false));
+	}
+
+	Ok((fds[0], fds[1]))
+}
+
+
+/// Clear or set the close-on-exec flag of `fd`.
+fn set_inheritable(fd: RawFd, inheritable: bool) -> Result<()> {
+	unsafe {
+		let flags = libc::fcntl(fd, libc::F_GETFD);
+		if flags < 0 {
+			return Error::new(format!("Cannot read flags of the descriptor {}", fd)).because(::std::io::Error::last_os_error()).result();
+		}
+
+		let flags = if inheritable {
+			flags & !libc::FD_CLOEXEC
+		}
+		else {
+			flags | libc::FD_CLOEXEC
+		};
+
+		if libc::fcntl(fd, libc::F_SETFD, flags) < 0 {
+			return Error::new(format!("Cannot set flags of the descriptor {}", fd)).because(::std::io::Error::last_os_error()).result();
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,45 @@
+//! JSON file format.
+
+use rustc_serialize::json::Json;
+use super::super::Value;
+
+
+/// Parse `text` into the group of settings.
+pub fn parse(text: &str) -> Result<Value, String> {
+	match Json::from_str(text) {
+		Ok(value @ Json::Object(_)) => Ok(convert(value).unwrap_or(Value::group())),
+		Ok(_) => Err("The top level value must be an object".to_string()),
+		Err(error) => Err(format!("{:?}", error)),
+	}
+}
+
+
+/// Convert the JSON `value` into the setting value. Null settings are the same
+/// as missing ones so they are left out of groups and lists.
+fn convert(value: Json) -> Option<Value> {
+	let value = match value {
+		Json::Boolean(value) => Value::Boolean(value),
+		Json::I64(value) => Value::Integer(value),
+		Json::U64(value) if value <= i64::max_value() as u64 => Value::Integer(value as i64),
+		Json::U64(value) => Value::Float(value as f64),
+		Json::F64(value) => Value::Float(value),
+		Json::String(value) => Value::Str(value),
+		Json::Array(items) => Value::List(items.into_iter().filter_map(convert).collect()),
+		Json::Object(settings) => Value::Group(settings.into_iter().filter_map(|(name, value)| convert(value).map(|value| (name, value))).collect()),
+		Json::Null => return None,
+	};
+
+	Some(value)
+}
+
+
+#[test]
+fn test_json_parsing() {
+	let root = parse("{\"core\": {\"worker_count\": 4, \"tls\": null}, \"servers\": [{\"port\": 80}, null]}").unwrap();
+	assert_eq!(root.lookup("core.worker_count"), Some(&Value::Integer(4)));
+	assert_eq!(root.lookup("core.tls"), None);
+	assert_eq!(root.lookup("servers.[0].port"), Some(&Value::Integer(80)));
+	assert_eq!(root.lookup("servers.[1]"), None);
+
+	assert!(parse("[]").is_err());
+}
@@ -0,0 +1,339 @@
+//! libconfig file format.
+
+use std::collections::BTreeMap;
+use super::super::Value;
+
+
+/// Parse error with the line number it occurred on.
+pub type ParseError = (usize, String);
+
+
+/// Parse `text` into the group of settings.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+	let mut parser = Parser {
+		chars: text.chars().collect(),
+		pos: 0,
+		line: 1,
+	};
+
+	let settings = try!(parser.settings(None));
+
+	Ok(Value::Group(settings))
+}
+
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+	line: usize,
+}
+
+
+impl Parser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn peek_at(&self, offset: usize) -> Option<char> {
+		self.chars.get(self.pos + offset).cloned()
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.peek();
+
+		if let Some(c) = c {
+			self.pos += 1;
+			if c == '\n' {
+				self.line += 1;
+			}
+		}
+
+		c
+	}
+
+	fn error<T, S: Into<String>>(&self, what: S) -> Result<T, ParseError> {
+		Err((self.line, what.into()))
+	}
+
+	fn found(&self) -> String {
+		match self.peek() {
+			Some(c) => format!("'{}'", c),
+			None => "end of file".to_string(),
+		}
+	}
+
+	/// Skip whitespaces and comments.
+	fn skip(&mut self) -> Result<(), ParseError> {
+		loop {
+			match (self.peek(), self.peek_at(1)) {
+				(Some(c), _) if c.is_whitespace() => {
+					self.next();
+				},
+				(Some('#'), _) | (Some('/'), Some('/')) => {
+					while let Some(c) = self.next() {
+						if c == '\n' {
+							break;
+						}
+					}
+				},
+				(Some('/'), Some('*')) => {
+					let line = self.line;
+					self.next();
+					self.next();
+
+					loop {
+						match self.next() {
+							Some('*') if self.peek() == Some('/') => {
+								self.next();
+								break;
+							},
+							Some(_) => (),
+							None => return Err((line, "Unterminated comment".to_string())),
+						};
+					}
+				},
+				_ => return Ok(()),
+			};
+		}
+	}
+
+	/// Parse settings until the `end` character or the end of text.
+	fn settings(&mut self, end: Option<char>) -> Result<BTreeMap<String, Value>, ParseError> {
+		let mut settings = BTreeMap::new();
+
+		loop {
+			try!(self.skip());
+
+			if self.peek() == end {
+				return Ok(settings);
+			}
+
+			let line = self.line;
+			let name = try!(self.name());
+
+			try!(self.skip());
+			match self.next() {
+				Some(':') | Some('=') => (),
+				_ => return self.error(format!("Expected ':' or '=' after '{}'", name)),
+			};
+
+			let value = try!(self.value());
+
+			try!(self.skip());
+			match self.peek() {
+				Some(';') | Some(',') => {
+					self.next();
+				},
+				_ => (),
+			};
+
+			if settings.insert(name.clone(), value).is_some() {
+				return Err((line, format!("Duplicate setting '{}'", name)));
+			}
+		}
+	}
+
+	fn name(&mut self) -> Result<String, ParseError> {
+		let mut name = String::new();
+
+		while let Some(c) = self.peek() {
+			let valid = if name.is_empty() {
+				c.is_alphabetic() || c == '*'
+			}
+			else {
+				c.is_alphanumeric() || c == '-' || c == '_' || c == '*'
+			};
+
+			if !valid {
+				break;
+			}
+
+			name.push(c);
+			self.next();
+		}
+
+		if name.is_empty() {
+			return self.error(format!("Expected setting name but found {}", self.found()));
+		}
+
+		Ok(name)
+	}
+
+	fn value(&mut self) -> Result<Value, ParseError> {
+		try!(self.skip());
+
+		match self.peek() {
+			Some('{') => {
+				self.next();
+				let settings = try!(self.settings(Some('}')));
+				self.next();
+				Ok(Value::Group(settings))
+			},
+			Some('(') => {
+				self.next();
+				Ok(Value::List(try!(self.values(')'))))
+			},
+			Some('[') => {
+				self.next();
+				Ok(Value::List(try!(self.values(']'))))
+			},
+			Some('"') => self.string(),
+			Some(_) => self.scalar(),
+			None => self.error("Expected value but found end of file"),
+		}
+	}
+
+	/// Parse values separated with commas until the `end` character.
+	fn values(&mut self, end: char) -> Result<Vec<Value>, ParseError> {
+		let mut values = Vec::new();
+
+		loop {
+			try!(self.skip());
+
+			if self.peek() == Some(end) {
+				self.next();
+				return Ok(values);
+			}
+
+			values.push(try!(self.value()));
+
+			try!(self.skip());
+			match self.peek() {
+				Some(',') => {
+					self.next();
+				},
+				Some(c) if c == end => (),
+				_ => return self.error(format!("Expected ',' or '{}' but found {}", end, self.found())),
+			};
+		}
+	}
+
+	/// Parse the string. Adjacent strings are concatenated.
+	fn string(&mut self) -> Result<Value, ParseError> {
+		let mut value = String::new();
+
+		while self.peek() == Some('"') {
+			self.next();
+
+			loop {
+				match self.next() {
+					Some('"') => break,
+					Some('\\') => match self.next() {
+						Some('n') => value.push('\n'),
+						Some('r') => value.push('\r'),
+						Some('t') => value.push('\t'),
+						Some('f') => value.push('\u{c}'),
+						Some('\\') => value.push('\\'),
+						Some('"') => value.push('"'),
+						Some('x') => {
+							let code: String = (0..2).filter_map(|_| self.next()).collect();
+							match u8::from_str_radix(&code, 16) {
+								Ok(code) => value.push(code as char),
+								Err(_) => return self.error(format!("Invalid escape sequence '\\x{}'", code)),
+							};
+						},
+						Some(c) => return self.error(format!("Invalid escape sequence '\\{}'", c)),
+						None => return self.error("Unterminated string"),
+					},
+					Some('\n') | None => return self.error("Unterminated string"),
+					Some(c) => value.push(c),
+				};
+			}
+
+			try!(self.skip());
+		}
+
+		Ok(Value::Str(value))
+	}
+
+	/// Parse the boolean or the number.
+	fn scalar(&mut self) -> Result<Value, ParseError> {
+		let mut token = String::new();
+
+		while let Some(c) = self.peek() {
+			if c.is_alphanumeric() || c == '-' || c == '+' || c == '.' {
+				token.push(c);
+				self.next();
+			}
+			else {
+				break;
+			}
+		}
+
+		if token.is_empty() {
+			return self.error(format!("Expected value but found {}", self.found()));
+		}
+
+		match scalar(&token) {
+			Some(value) => Ok(value),
+			None => self.error(format!("Invalid value '{}'", token)),
+		}
+	}
+}
+
+
+/// Parse the boolean or the number written as `token`.
+fn scalar(token: &str) -> Option<Value> {
+	match token.to_lowercase().as_str() {
+		"true" => return Some(Value::Boolean(true)),
+		"false" => return Some(Value::Boolean(false)),
+		_ => (),
+	};
+
+	// Integers can have L or LL suffix
+	let number = token.trim_right_matches(|c| c == 'L' || c == 'l');
+
+	let (negative, digits) = if number.starts_with('-') {
+		(true, &number[1..])
+	}
+	else if number.starts_with('+') {
+		(false, &number[1..])
+	}
+	else {
+		(false, number)
+	};
+
+	let integer = if digits.starts_with("0x") || digits.starts_with("0X") {
+		i64::from_str_radix(&digits[2..], 16).ok()
+	}
+	else {
+		digits.parse::<i64>().ok()
+	};
+
+	if let Some(integer) = integer {
+		return Some(Value::Integer(if negative { -integer } else { integer }));
+	}
+
+	match token.parse::<f64>() {
+		Ok(float) => Some(Value::Float(float)),
+		Err(_) => None,
+	}
+}
+
+
+#[test]
+fn test_libconfig_parsing() {
+	let text = "# Comment\n\
+		core : { worker_count = 4; enabled = TRUE; };\n\
+		/* block\n comment */ log = { level = \"de\" \"bug\"; ratio = 0.5; mask = 0xFF; big = 10L; };\n\
+		servers = ({ port = -1; }, { name = \"a\\\"b\"; },);\n\
+		empty = ();\n";
+
+	let root = parse(text).unwrap();
+	assert_eq!(root.lookup("core.worker_count"), Some(&Value::Integer(4)));
+	assert_eq!(root.lookup("core.enabled"), Some(&Value::Boolean(true)));
+	assert_eq!(root.lookup("log.level"), Some(&Value::Str("debug".to_string())));
+	assert_eq!(root.lookup("log.ratio"), Some(&Value::Float(0.5)));
+	assert_eq!(root.lookup("log.mask"), Some(&Value::Integer(255)));
+	assert_eq!(root.lookup("log.big"), Some(&Value::Integer(10)));
+	assert_eq!(root.lookup("servers.[0].port"), Some(&Value::Integer(-1)));
+	assert_eq!(root.lookup("servers.[1].name"), Some(&Value::Str("a\"b".to_string())));
+	assert_eq!(root.lookup("empty"), Some(&Value::List(vec![])));
+
+	assert_eq!(parse("a = 1;\nb = ;").unwrap_err().0, 2);
+	assert_eq!(parse("a = 1;\n\na = 2;").unwrap_err().0, 3);
+	assert!(parse("a = { b = 1;").is_err());
+	assert!(parse("a = \"b").is_err());
+	assert!(parse("a = yes;").is_err());
+	assert!(parse("a = off;").is_err());
+}
@@ -0,0 +1,50 @@
+//! Configuration file formats.
+
+use std::path::Path;
+use wrust_types::{Error, Result};
+use super::Value;
+use super::preprocess;
+
+mod libconfig;
+mod toml;
+mod json;
+
+pub use self::libconfig::parse as parse_libconfig;
+
+
+/// Load the file at `path` in the format its extension tells, `.toml` for TOML,
+/// `.json` for JSON and libconfig for others. Includes and environment variables
+/// are supported in libconfig files only.
+pub fn load(path: &Path) -> Result<Value> {
+	let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_lowercase());
+
+	match extension.as_ref().map(|extension| extension.as_str()) {
+		Some("toml") => load_with(path, toml::parse),
+		Some("json") => load_with(path, json::parse),
+		_ => load_libconfig(path),
+	}
+}
+
+
+fn load_with<F>(path: &Path, parse: F) -> Result<Value>
+	where F: Fn(&str) -> ::std::result::Result<Value, String> {
+	let text = try!(preprocess::read(path));
+
+	match parse(&text) {
+		Ok(value) => Ok(value),
+		Err(msg) => Error::new(format!("Error reading config file {}: {}", path.display(), msg)).result(),
+	}
+}
+
+
+fn load_libconfig(path: &Path) -> Result<Value> {
+	let source = try!(preprocess::preprocess(path));
+
+	match libconfig::parse(&source.text) {
+		Ok(value) => Ok(value),
+		Err((line, msg)) => match source.origins.get(line - 1) {
+			Some(&(ref file, line)) => Error::new(format!("Error reading config file {}: {} at {}:{}", path.display(), msg, file.display(), line)).result(),
+			None => Error::new(format!("Error reading config file {}: {} at the end", path.display(), msg)).result(),
+		},
+	}
+}
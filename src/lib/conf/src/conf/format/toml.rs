@@ -0,0 +1,26 @@
+//! TOML file format.
+
+use toml;
+use super::super::Value;
+
+
+/// Parse `text` into the group of settings.
+pub fn parse(text: &str) -> Result<Value, String> {
+	match text.parse::<toml::Value>() {
+		Ok(value) => Ok(convert(value)),
+		Err(errors) => Err(format!("{:?}", errors)),
+	}
+}
+
+
+fn convert(value: toml::Value) -> Value {
+	match value {
+		toml::Value::Boolean(value) => Value::Boolean(value),
+		toml::Value::Integer(value) => Value::Integer(value),
+		toml::Value::Float(value) => Value::Float(value),
+		toml::Value::String(value) => Value::Str(value),
+		toml::Value::Datetime(value) => Value::Str(value.to_string()),
+		toml::Value::Array(items) => Value::List(items.into_iter().map(convert).collect()),
+		toml::Value::Table(settings) => Value::Group(settings.into_iter().map(|(name, value)| (name, convert(value))).collect()),
+	}
+}
@@ -1,53 +1,293 @@
 //! Trait for loadable from configuration file objects.
 
-use std::ops::Deref;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::path::Path;
-use config::reader;
-use config::types::{Config, Value};
 use wrust_types::{Error, Result};
 
-/// Shared configuration
+mod value;
+mod format;
+mod preprocess;
+mod shared;
+mod validation;
+
+pub use self::value::Value;
+pub use self::shared::SharedConf;
+pub use self::validation::Validation;
+
+/// Immutable configuration snapshot which can be shared between threads
 #[derive(Debug, Clone)]
 pub struct Conf {
-	instance: Rc<Config>,
+	instance: Arc<Value>,
+	overrides: Option<Arc<Value>>,
 }
 
 
 impl Conf {
-	/// `from_file` loads configuration from file.
+	/// `from_file` loads configuration from file. The format is chosen by the file
+	/// extension, `.toml` for TOML, `.json` for JSON and libconfig for others.
+	/// libconfig files can include other files and refer to environment variables.
 	pub fn from_file(path: &Path) -> Result<Conf> {
-		match reader::from_file(path) {
-			Ok(config) => Ok(Conf {
-				instance: Rc::new(config),
-			}),
-			Err(err) => Error::new("Error reading config file").because(err).result()
+		Ok(Conf {
+			instance: Arc::new(try!(format::load(path))),
+			overrides: None,
+		})
+	}
+
+	/// `empty` creates configuration without settings.
+	pub fn empty() -> Conf {
+		Conf {
+			instance: Arc::new(Value::group()),
+			overrides: None,
+		}
+	}
+
+	/// Override settings with values given as pairs of paths and values. Paths are
+	/// names separated with dots, e.g. `core.worker_count`, and values are written
+	/// in the libconfig syntax. Values which are not valid in that syntax are taken
+	/// as strings. Items of lists in the config file are addressed with indices in
+	/// square brackets, e.g. `servers.[0].listen.port`. Overridden settings are
+	/// visible through `lookup*` functions of `Conf`.
+	pub fn with_overrides(mut self, overrides: &[(String, String)]) -> Result<Conf> {
+		if overrides.is_empty() {
+			return Ok(self);
+		}
+
+		let mut root = Value::group();
+		for &(ref path, ref value) in overrides {
+			let names: Vec<&str> = path.split('.').collect();
+			if names.iter().any(|name| !is_setting_name(name)) {
+				return Error::new(format!("Invalid setting path '{}'", path)).result();
+			}
+
+			// Indices must address items which exist in the config file
+			for (i, name) in names.iter().enumerate() {
+				if let Some(index) = list_index(name) {
+					let list_xpath = names[..i].join(".");
+					match self.instance.lookup(&list_xpath) {
+						Some(&Value::List(ref items)) if index < items.len() => (),
+						Some(&Value::List(ref items)) => {
+							return Error::new(format!("Index {} is out of range of {} items at '{}' in '{}'", index, items.len(), list_xpath, path)).result();
+						},
+						_ => return Error::new(format!("List is not found at '{}' in '{}'", list_xpath, path)).result(),
+					};
+				}
+			}
+
+			if !insert_override(&mut root, &names, setting_value(value), Some(&*self.instance)) {
+				return Error::new(format!("Setting '{}' conflicts with another override", path)).result();
+			}
 		}
+
+		self.overrides = Some(Arc::new(root));
+
+		Ok(self)
 	}
 
 	/// Resolve the `xpath` provided. Resolution rules are:  
-	/// * If the element at `xpath` is the scalar value then it's the reference, return the value of that element
+	/// * If the element at `xpath` is the string value then it's the reference, return the value of that element
 	/// * If the element at `xpath` is the group then return `xpath`
 	/// * Otherwise `xpath` is invalid
 	pub fn resolve_reference(&self, xpath: &str) -> Option<String> {
-		match self.instance.lookup(xpath) {
-			Some(&Value::Svalue(ref target)) => Some(target.to_string()),
+		match self.lookup(xpath) {
+			Some(&Value::Str(ref target)) => Some(target.to_string()),
 			Some(&Value::Group(_)) => Some(xpath.to_string()),
 			_ => None,
 		}
 	}
+
+	/// Get the tree the setting at `path` is read from. Groups are read from
+	/// the config file so overrides do not hide settings they do not mention.
+	fn source(&self, path: &str) -> &Value {
+		if let Some(ref overrides) = self.overrides {
+			match overrides.lookup(path) {
+				Some(&Value::Group(_)) | None => (),
+				Some(_) => return overrides,
+			};
+		}
+
+		&self.instance
+	}
+
+	/// Get the number of elements in the list at `xpath`.
+	pub fn count(&self, xpath: &str) -> Option<usize> {
+		match self.lookup(xpath) {
+			Some(&Value::List(ref collection)) => Some(collection.len()),
+			_ => None,
+		}
+	}
+
+	/// Get names of settings in the group at `xpath` sorted. The list is empty
+	/// if there is no group at `xpath`.
+	pub fn keys(&self, xpath: &str) -> Vec<String> {
+		let mut keys: Vec<String> = Vec::new();
+
+		for tree in Some(&self.instance).into_iter().chain(self.overrides.as_ref()) {
+			if let Some(&Value::Group(ref settings)) = tree.lookup(xpath) {
+				for key in settings.keys() {
+					if !keys.contains(key) {
+						keys.push(key.clone());
+					}
+				}
+			}
+		}
+
+		keys.sort();
+		keys
+	}
+
+	pub fn lookup(&self, path: &str) -> Option<&Value> {
+		match self.source(path).lookup(path) {
+			Some(value) => Some(value),
+			None => match self.overrides {
+				Some(ref overrides) => overrides.lookup(path),
+				None => None,
+			},
+		}
+	}
+
+	pub fn lookup_str(&self, path: &str) -> Option<&str> {
+		self.lookup(path).and_then(|value| value.as_str())
+	}
+
+	pub fn lookup_str_or<'a>(&'a self, path: &str, default: &'a str) -> &'a str {
+		self.lookup_str(path).unwrap_or(default)
+	}
+
+	pub fn lookup_boolean(&self, path: &str) -> Option<bool> {
+		self.lookup(path).and_then(|value| value.as_bool())
+	}
+
+	pub fn lookup_boolean_or(&self, path: &str, default: bool) -> bool {
+		self.lookup_boolean(path).unwrap_or(default)
+	}
+
+	pub fn lookup_integer32(&self, path: &str) -> Option<i32> {
+		match self.lookup_integer64(path) {
+			Some(value) if value as i32 as i64 == value => Some(value as i32),
+			_ => None,
+		}
+	}
+
+	pub fn lookup_integer32_or(&self, path: &str, default: i32) -> i32 {
+		self.lookup_integer32(path).unwrap_or(default)
+	}
+
+	pub fn lookup_integer64(&self, path: &str) -> Option<i64> {
+		self.lookup(path).and_then(|value| value.as_integer())
+	}
+
+	pub fn lookup_integer64_or(&self, path: &str, default: i64) -> i64 {
+		self.lookup_integer64(path).unwrap_or(default)
+	}
+
+	pub fn lookup_floating64(&self, path: &str) -> Option<f64> {
+		self.lookup(path).and_then(|value| value.as_float())
+	}
+
+	pub fn lookup_floating64_or(&self, path: &str, default: f64) -> f64 {
+		self.lookup_floating64(path).unwrap_or(default)
+	}
 }
 
 
-impl Deref for Conf {
-	type Target = Config;
+/// Test if `name` is valid setting name or the index of the list item.
+fn is_setting_name(name: &str) -> bool {
+	if list_index(name).is_some() {
+		return true;
+	}
 
-	fn deref(&self) -> &Config {
-		&self.instance
+	let is_letter = |c: char| (c >= 'a' && c <= 'z') || (c >= 'A' && c <= 'Z') || c == '*';
+	let mut chars = name.chars();
+
+	match chars.next() {
+		Some(c) if is_letter(c) => chars.all(|c| is_letter(c) || (c >= '0' && c <= '9') || c == '-' || c == '_'),
+		_ => false,
+	}
+}
+
+
+/// Parse `value` written in the libconfig syntax or take it as the string.
+fn setting_value(value: &str) -> Value {
+	match format::parse_libconfig(&format!("value = {};", value)) {
+		Ok(Value::Group(mut settings)) => settings.remove("value").unwrap_or(Value::Str(value.to_string())),
+		_ => Value::Str(value.to_string()),
 	}
 }
 
 
+/// Get the index of the list item written in square brackets, e.g. `[0]`.
+fn list_index(name: &str) -> Option<usize> {
+	if name.len() < 3 || !name.starts_with('[') || !name.ends_with(']') {
+		return None;
+	}
+
+	let digits = &name[1..name.len() - 1];
+	if !digits.chars().all(|c| c >= '0' && c <= '9') {
+		return None;
+	}
+
+	digits.parse().ok()
+}
+
+
+/// Insert the `value` at the path of `names` into `tree`. Lists the path goes through
+/// are copied from `existing`, the same place of the config file, so items which
+/// are not overridden stay as they are.
+/// Returns `false` if the path goes through an overridden value.
+fn insert_override(tree: &mut Value, names: &[&str], value: Value, existing: Option<&Value>) -> bool {
+	let (name, rest) = match names.split_first() {
+		Some((name, rest)) => (*name, rest),
+		None => return false,
+	};
+
+	let existing = existing.and_then(|existing| existing.lookup(name));
+
+	let child = match *tree {
+		Value::Group(ref mut settings) => {
+			if rest.is_empty() {
+				// Groups and lists which are copied from the config file can be replaced
+				let conflict = match settings.get(name) {
+					Some(current) => match *current {
+						Value::Group(_) | Value::List(_) => Some(current) != existing,
+						_ => false,
+					},
+					None => false,
+				};
+
+				if !conflict {
+					settings.insert(name.to_string(), value);
+				}
+
+				return !conflict;
+			}
+
+			let copied = match (existing, list_index(rest[0])) {
+				(Some(&Value::List(ref items)), Some(_)) => Value::List(items.clone()),
+				_ => Value::group(),
+			};
+
+			settings.entry(name.to_string()).or_insert(copied)
+		},
+		Value::List(ref mut items) => {
+			let item = match list_index(name).and_then(move |index| items.get_mut(index)) {
+				Some(item) => item,
+				None => return false,
+			};
+
+			if rest.is_empty() {
+				*item = value;
+				return true;
+			}
+
+			item
+		},
+		_ => return false,
+	};
+
+	insert_override(child, rest, value, existing)
+}
+
+
 /// Loadable from configuration file.  
 /// The trait is implement for Vec so it's very simple to load the list of similar settings into the vector.
 pub trait FromConf: Sized {
@@ -59,9 +299,9 @@ pub trait FromConf: Sized {
 impl<T: FromConf> FromConf for Vec<T> {
 	fn from_conf(config: &Conf, xpath: &str) -> Result<Vec<T>> {
 		// Test if at the xpath is the array and get the numer of elements in it
-		let count = match config.lookup(xpath) {
-			Some(&Value::List(ref collection)) => collection.len(),
-			_ => return Error::new(format!("Expected array at '{}' but found nothing", xpath)).result(),
+		let count = match config.count(xpath) {
+			Some(count) => count,
+			None => return Error::new(format!("Expected array at '{}' but found nothing", xpath)).result(),
 		};
 
 		// Read array items
@@ -77,3 +317,29 @@ impl<T: FromConf> FromConf for Vec<T> {
 		Ok(items)
 	}
 }
+
+
+#[test]
+fn test_overrides() {
+	let config = Conf {
+		instance: Arc::new(format::parse_libconfig("servers = ({ listen = { port = 80; }; }, { listen = { port = 81; }; });").unwrap()),
+		overrides: None,
+	};
+
+	let overrides = |values: &[(&str, &str)]| {
+		let values: Vec<(String, String)> = values.iter().map(|&(path, value)| (path.to_string(), value.to_string())).collect();
+		config.clone().with_overrides(&values)
+	};
+
+	let overridden = overrides(&[("servers.[1].listen.port", "8081"), ("servers.[1].listen.address", "\"*\"")]).unwrap();
+	assert_eq!(overridden.count("servers"), Some(2));
+	assert_eq!(overridden.lookup_integer64("servers.[0].listen.port"), Some(80));
+	assert_eq!(overridden.lookup_integer64("servers.[1].listen.port"), Some(8081));
+	assert_eq!(overridden.lookup_str("servers.[1].listen.address"), Some("*"));
+	assert_eq!(overridden.keys("servers.[1].listen"), vec!["address".to_string(), "port".to_string()]);
+
+	assert!(overrides(&[("servers.[2].listen.port", "8082")]).is_err());
+	assert!(overrides(&[("servers.[0].listen.port.[0]", "1")]).is_err());
+	assert!(overrides(&[("servers.[x].listen.port", "1")]).is_err());
+	assert!(overrides(&[("servers.[0].listen", "1"), ("servers.[0].listen.port", "1")]).is_err());
+}
@@ -0,0 +1,303 @@
+//! Include files and environment variable interpolation.
+//!
+//! The line `@include "pattern"` is replaced with contents of files matching
+//! the glob pattern which is relative to the directory of the including file.
+//! Files are included in alphabetical order and those being included already
+//! are skipped, so `@include "*.conf"` does not include the including file.
+//! Inside a list files are separated with commas so each file can hold
+//! list elements, e.g. listeners of a team. The pattern without wildcards
+//! must match the file.
+//!
+//! `${NAME}` and `${NAME:-default}` in string values are replaced with the value
+//! of the environment variable `NAME` or with `default` if it is not set.
+//! `$${` is written to get the literal `${`.
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use glob::glob;
+use wrust_types::{Error, Result};
+
+
+/// Configuration text composed of the file and files it includes.
+pub struct Source {
+	/// Configuration text
+	pub text: String,
+	/// Files and line numbers lines of the text come from
+	pub origins: Vec<(PathBuf, usize)>,
+}
+
+
+/// Read the file at `path` as it is.
+pub fn read(path: &Path) -> Result<String> {
+	let mut text = String::new();
+
+	match File::open(path).and_then(|mut file| file.read_to_string(&mut text)) {
+		Ok(_) => Ok(text),
+		Err(msg) => Error::new(format!("Cannot read config file {}", path.display())).because(msg).result(),
+	}
+}
+
+
+/// Read the file at `path` with files it includes and interpolate environment variables.
+pub fn preprocess(path: &Path) -> Result<Source> {
+	let mut source = Source {
+		text: String::new(),
+		origins: Vec::new(),
+	};
+
+	try!(include(path, &mut Vec::new(), &mut source));
+
+	Ok(source)
+}
+
+
+/// Syntax state of the text which is carried from line to line.
+#[derive(Default)]
+struct Scanner {
+	in_comment: bool,
+	brackets: Vec<char>,
+}
+
+
+fn include(path: &Path, stack: &mut Vec<PathBuf>, source: &mut Source) -> Result<()> {
+	let text = try!(read(path));
+
+	stack.push(path.canonicalize().unwrap_or(path.to_path_buf()));
+
+	let mut scanner = Scanner::default();
+
+	for (index, line) in text.lines().enumerate() {
+		let location = format!("{}:{}", path.display(), index + 1);
+
+		if !scanner.in_comment && line.trim().starts_with("@include") {
+			let pattern = try!(directive(line.trim(), &location));
+			let pattern = try!(interpolate(&pattern, &location));
+			let in_list = match scanner.brackets.last() {
+				Some(&'(') | Some(&'[') => true,
+				_ => false,
+			};
+
+			// Relative patterns start from the directory of the including file
+			let pattern = match path.parent() {
+				Some(directory) if !Path::new(&pattern).is_absolute() => directory.join(&pattern),
+				_ => PathBuf::from(&pattern),
+			};
+
+			let files = match glob(&pattern.to_string_lossy()) {
+				Ok(files) => files,
+				Err(msg) => return Error::new(format!("Invalid include pattern at {}", location)).because(msg).result(),
+			};
+
+			// The literal path which matches nothing is most likely a typo
+			let literal = !pattern.to_string_lossy().contains(|c| c == '*' || c == '?' || c == '[');
+			let mut matched = false;
+
+			let mut first = true;
+			for file in files {
+				let file = match file {
+					Ok(file) => file,
+					Err(msg) => return Error::new(format!("Cannot include files at {}", location)).because(msg).result(),
+				};
+
+				matched = true;
+
+				if stack.contains(&file.canonicalize().unwrap_or(file.clone())) {
+					continue;
+				}
+
+				if in_list && !first {
+					push(source, ",", path, index + 1);
+				}
+
+				try!(include(&file, stack, source));
+				first = false;
+			}
+
+			if literal && !matched {
+				return Error::new(format!("Included file {} is not found at {}", pattern.display(), location)).result();
+			}
+		}
+		else {
+			let line = try!(scanner.scan(line, &location));
+			push(source, &line, path, index + 1);
+		}
+	}
+
+	stack.pop();
+
+	Ok(())
+}
+
+
+fn push(source: &mut Source, line: &str, path: &Path, number: usize) {
+	source.text.push_str(line);
+	source.text.push('\n');
+	source.origins.push((path.to_path_buf(), number));
+}
+
+
+/// Get the pattern of the `@include` directive.
+fn directive(line: &str, location: &str) -> Result<String> {
+	let rest = line["@include".len()..].trim();
+
+	if rest.starts_with('"') {
+		if let Some(end) = rest[1..].find('"') {
+			let tail = rest[end + 2..].trim();
+			if tail.is_empty() || tail.starts_with('#') || tail.starts_with("//") {
+				return Ok(rest[1..end + 1].to_string());
+			}
+		}
+	}
+
+	Error::new(format!("Expected @include \"pattern\" at {}", location)).result()
+}
+
+
+impl Scanner {
+	/// Track comments and brackets of the `line` and interpolate variables in strings of it.
+	fn scan(&mut self, line: &str, location: &str) -> Result<String> {
+		let mut result = String::with_capacity(line.len());
+		let mut string: Option<String> = None;
+		let mut chars = line.chars().peekable();
+
+		while let Some(c) = chars.next() {
+			if self.in_comment {
+				result.push(c);
+				if c == '*' && chars.peek() == Some(&'/') {
+					result.push(chars.next().unwrap());
+					self.in_comment = false;
+				}
+			}
+			else if let Some(mut value) = string.take() {
+				if c == '\\' {
+					value.push(c);
+					if let Some(escaped) = chars.next() {
+						value.push(escaped);
+					}
+					string = Some(value);
+				}
+				else if c == '"' {
+					result.push_str(&try!(interpolate(&value, location)));
+					result.push(c);
+				}
+				else {
+					value.push(c);
+					string = Some(value);
+				}
+			}
+			else {
+				match c {
+					'"' => string = Some(String::new()),
+					'#' => {
+						result.push(c);
+						result.extend(chars.by_ref());
+						break;
+					},
+					'/' if chars.peek() == Some(&'/') => {
+						result.push(c);
+						result.extend(chars.by_ref());
+						break;
+					},
+					'/' if chars.peek() == Some(&'*') => {
+						result.push(c);
+						result.push(chars.next().unwrap());
+						self.in_comment = true;
+						continue;
+					},
+					'{' | '(' | '[' => self.brackets.push(c),
+					'}' | ')' | ']' => {
+						self.brackets.pop();
+					},
+					_ => (),
+				};
+
+				result.push(c);
+			}
+		}
+
+		// Strings are written on one line
+		if let Some(value) = string {
+			result.push_str(&value);
+		}
+
+		Ok(result)
+	}
+}
+
+
+/// Replace `${NAME}` and `${NAME:-default}` in the string `value` written with escapes.
+/// The literal `${` is written as `$${`.
+fn interpolate(value: &str, location: &str) -> Result<String> {
+	let mut result = String::with_capacity(value.len());
+	let mut rest = value;
+
+	while let Some(start) = rest.find('$') {
+		result.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		if rest.starts_with("$${") {
+			result.push_str("${");
+			rest = &rest[3..];
+			continue;
+		}
+
+		if !rest.starts_with("${") {
+			result.push('$');
+			rest = &rest[1..];
+			continue;
+		}
+
+		let end = match rest.find('}') {
+			Some(end) => end,
+			None => return Error::new(format!("Unterminated variable reference at {}", location)).result(),
+		};
+
+		let reference = &rest[2..end];
+		let (name, default) = match reference.find(":-") {
+			Some(pos) => (&reference[..pos], Some(&reference[pos + 2..])),
+			None => (reference, None),
+		};
+
+		match (env::var(name), default) {
+			(Ok(value), _) => result.push_str(&escape(&value)),
+			(Err(_), Some(default)) => result.push_str(default),
+			(Err(_), None) => return Error::new(format!("Environment variable {} is not set at {}", name, location)).result(),
+		};
+
+		rest = &rest[end + 1..];
+	}
+
+	result.push_str(rest);
+
+	Ok(result)
+}
+
+
+/// Escape the `value` so it can be written inside the string.
+fn escape(value: &str) -> String {
+	value.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+
+#[test]
+fn test_interpolation() {
+	env::set_var("WRUST_TEST_HOST", "example.com");
+	env::remove_var("WRUST_TEST_MISSING");
+
+	assert_eq!(interpolate("${WRUST_TEST_HOST}:80", "a:1").unwrap(), "example.com:80");
+	assert_eq!(interpolate("${WRUST_TEST_MISSING:-localhost}", "a:1").unwrap(), "localhost");
+	assert_eq!(interpolate("plain", "a:1").unwrap(), "plain");
+	assert_eq!(interpolate("$${WRUST_TEST_MISSING} costs $5", "a:1").unwrap(), "${WRUST_TEST_MISSING} costs $5");
+	assert_eq!(interpolate("^a$$${WRUST_TEST_HOST}", "a:1").unwrap(), "^a$${WRUST_TEST_HOST}");
+	assert!(interpolate("${WRUST_TEST_MISSING}", "a:1").is_err());
+	assert!(interpolate("${WRUST_TEST_HOST", "a:1").is_err());
+
+	let mut scanner = Scanner::default();
+	assert_eq!(scanner.scan("host = \"${WRUST_TEST_HOST}\"; # ${WRUST_TEST_MISSING}", "a:1").unwrap(), "host = \"example.com\"; # ${WRUST_TEST_MISSING}");
+	assert_eq!(scanner.scan("servers : ({", "a:2").unwrap(), "servers : ({");
+	assert_eq!(scanner.brackets, vec!['(', '{']);
+	assert_eq!(scanner.scan("}, /* ) */", "a:3").unwrap(), "}, /* ) */");
+	assert_eq!(scanner.brackets, vec!['(']);
+}
@@ -0,0 +1,58 @@
+//! Configuration shared between threads.
+
+use std::sync::{Arc, RwLock};
+use super::Conf;
+
+
+/// The current configuration snapshot which can be replaced while threads read it.
+/// Snapshots are immutable, readers keep the one they got until they ask again.
+#[derive(Clone)]
+pub struct SharedConf {
+	current: Arc<RwLock<Conf>>,
+}
+
+
+impl SharedConf {
+	pub fn new(config: Conf) -> SharedConf {
+		SharedConf {
+			current: Arc::new(RwLock::new(config)),
+		}
+	}
+
+	/// Get the current configuration snapshot.
+	pub fn snapshot(&self) -> Conf {
+		match self.current.read() {
+			Ok(config) => config.clone(),
+			Err(poisoned) => poisoned.into_inner().clone(),
+		}
+	}
+
+	/// Replace the current snapshot with `config` and return the previous one.
+	pub fn publish(&self, config: Conf) -> Conf {
+		let mut current = match self.current.write() {
+			Ok(current) => current,
+			Err(poisoned) => poisoned.into_inner(),
+		};
+
+		::std::mem::replace(&mut *current, config)
+	}
+}
+
+
+#[test]
+fn test_shared_conf() {
+	use std::thread;
+
+	let first = Conf::empty().with_overrides(&[("core.worker_count".to_string(), "1".to_string())]).unwrap();
+	let second = Conf::empty().with_overrides(&[("core.worker_count".to_string(), "2".to_string())]).unwrap();
+
+	let shared = SharedConf::new(first);
+	let snapshot = shared.snapshot();
+
+	let reader = shared.clone();
+	let published = thread::spawn(move || reader.publish(second)).join().unwrap();
+
+	assert_eq!(published.lookup_integer32("core.worker_count"), Some(1));
+	assert_eq!(snapshot.lookup_integer32("core.worker_count"), Some(1));
+	assert_eq!(shared.snapshot().lookup_integer32("core.worker_count"), Some(2));
+}
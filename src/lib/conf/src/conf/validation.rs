@@ -0,0 +1,100 @@
+//! Collector of configuration problems.
+
+use std::fmt;
+use wrust_types::{Error, Result};
+use super::Conf;
+
+
+/// Problems found in the configuration. They are collected instead of stopping
+/// at the first one so all of them can be reported at once.
+pub struct Validation {
+	errors: Vec<Error>,
+}
+
+
+impl Validation {
+	pub fn new() -> Validation {
+		Validation {
+			errors: Vec::new(),
+		}
+	}
+
+	/// Record the error of `result` if there is one and return the value otherwise.
+	pub fn check<T>(&mut self, result: Result<T>) -> Option<T> {
+		match result {
+			Ok(value) => Some(value),
+			Err(error) => {
+				self.errors.push(error);
+				None
+			},
+		}
+	}
+
+	/// Record the error with the message given.
+	pub fn error<S>(&mut self, what: S)
+		where S: Into<String> {
+		self.errors.push(Error::new(what));
+	}
+
+	/// Record settings of the group at `xpath` which are not in the `known` list.
+	pub fn unknown_keys(&mut self, config: &Conf, xpath: &str, known: &[&str]) {
+		for key in config.keys(xpath) {
+			if !known.contains(&key.as_str()) {
+				self.error(format!("Unknown setting '{}' at '{}'", key, xpath));
+			}
+		}
+	}
+
+	/// The number of problems found.
+	pub fn len(&self) -> usize {
+		self.errors.len()
+	}
+
+	pub fn is_ok(&self) -> bool {
+		self.errors.is_empty()
+	}
+
+	pub fn errors(&self) -> &[Error] {
+		&self.errors
+	}
+
+	/// Consume the validation and turn problems found into the single error.
+	pub fn result(self) -> Result<()> {
+		if self.is_ok() {
+			Ok(())
+		}
+		else {
+			Error::new(format!("{}", self)).result()
+		}
+	}
+}
+
+
+/// Problems are written one per line.
+impl fmt::Display for Validation {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		try!(write!(f, "{} configuration error(s) found", self.errors.len()));
+
+		for error in &self.errors {
+			try!(write!(f, "\n    {}", error));
+		}
+
+		Ok(())
+	}
+}
+
+
+#[test]
+fn test_validation_collects_errors() {
+	let mut validation = Validation::new();
+	assert_eq!(validation.check::<u32>(Ok(1)), Some(1));
+	assert!(validation.is_ok());
+
+	assert_eq!(validation.check::<u32>(Error::new("Port is required at 'a'").result()), None);
+	validation.error("Path is required at 'b'");
+	validation.unknown_keys(&Conf::empty(), "c", &[]);
+	assert_eq!(validation.len(), 2);
+
+	let error = validation.result().unwrap_err();
+	assert_eq!(format!("{}", error), "2 configuration error(s) found\n    Port is required at 'a'\n    Path is required at 'b'");
+}
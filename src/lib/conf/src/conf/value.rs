@@ -0,0 +1,100 @@
+//! Configuration tree independent of the file format.
+
+use std::collections::BTreeMap;
+
+
+/// Value of the setting
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+	Boolean(bool),
+	Integer(i64),
+	Float(f64),
+	Str(String),
+	/// Ordered collection of values
+	List(Vec<Value>),
+	/// Named settings
+	Group(BTreeMap<String, Value>),
+}
+
+
+impl Value {
+	/// Create the group without settings.
+	pub fn group() -> Value {
+		Value::Group(BTreeMap::new())
+	}
+
+	/// Find the value at `path`. The path consists of setting names and list
+	/// indices in square brackets separated with dots, e.g. `servers.[0].listen`.
+	pub fn lookup(&self, path: &str) -> Option<&Value> {
+		let mut value = self;
+
+		for segment in path.split('.') {
+			value = match *value {
+				Value::List(ref items) if segment.starts_with('[') && segment.ends_with(']') => {
+					match segment[1..segment.len() - 1].trim().parse::<usize>() {
+						Ok(index) => match items.get(index) {
+							Some(item) => item,
+							None => return None,
+						},
+						Err(_) => return None,
+					}
+				},
+				Value::Group(ref settings) => match settings.get(segment) {
+					Some(setting) => setting,
+					None => return None,
+				},
+				_ => return None,
+			};
+		}
+
+		Some(value)
+	}
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			Value::Boolean(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_integer(&self) -> Option<i64> {
+		match *self {
+			Value::Integer(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_float(&self) -> Option<f64> {
+		match *self {
+			Value::Float(value) => Some(value),
+			Value::Integer(value) => Some(value as f64),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			Value::Str(ref value) => Some(value),
+			_ => None,
+		}
+	}
+}
+
+
+#[test]
+fn test_value_lookup() {
+	let mut listen = BTreeMap::new();
+	listen.insert("port".to_string(), Value::Integer(8080));
+
+	let mut server = BTreeMap::new();
+	server.insert("listen".to_string(), Value::Group(listen));
+
+	let mut root = BTreeMap::new();
+	root.insert("servers".to_string(), Value::List(vec![Value::Group(server)]));
+	let root = Value::Group(root);
+
+	assert_eq!(root.lookup("servers.[0].listen.port"), Some(&Value::Integer(8080)));
+	assert_eq!(root.lookup("servers.[1].listen.port"), None);
+	assert_eq!(root.lookup("servers.listen"), None);
+	assert_eq!(root.lookup("servers.[0].listen.port.x"), None);
+}
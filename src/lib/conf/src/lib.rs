@@ -1,8 +1,12 @@
 //! Wrust Configuration
 
-extern crate config;
+#[macro_use] extern crate log;
+extern crate glob;
+extern crate toml;
+extern crate rustc_serialize;
 extern crate wrust_types;
 
 pub mod conf;
+#[macro_use] pub mod schema;
 
-pub use conf::{Conf, FromConf};
\ No newline at end of file
+pub use conf::{Conf, FromConf, SharedConf, Validation, Value};
\ No newline at end of file
@@ -0,0 +1,433 @@
+//! Declarative configuration schema.
+//!
+//! `conf_schema!` declares the settings structure with types, defaults and ranges
+//! of its fields and generates `FromConf` for it. Settings which the structure does
+//! not declare are reported with warnings.
+//!
+//! ```ignore
+//! conf_schema! {
+//! 	/// Core settings
+//! 	pub struct CoreConf {
+//! 		/// Worker count
+//! 		worker_count: u16 = Field::required().range(1, 256),
+//! 		/// Drain timeout in milliseconds
+//! 		drain_timeout: u64 = Field::default(30000),
+//! 		/// Path to the PID file
+//! 		pid_file: Option<String> = Field::optional(),
+//! 		/// Group of TLS settings
+//! 		tls: Option<TlsConf> = Section::optional(),
+//! 	}
+//! 	finish = CoreConf::finish;
+//! }
+//! ```
+//!
+//! The `finish` function checks the structure as whole or completes it once its
+//! fields are read. Groups which have settings the structure does not declare,
+//! e.g. settings of modules, are found with `group = open_group;` before `finish`.
+
+use std::fmt::Debug;
+use conf::{Conf, FromConf};
+use wrust_types::Error;
+use wrust_types::net::Cidr;
+
+pub use wrust_types::Result;
+
+
+/// Declare the settings structure and generate `FromConf` for it.
+#[macro_export]
+macro_rules! conf_schema {
+	(
+		$(#[$attr:meta])*
+		pub struct $name:ident {
+			$(
+				$(#[$field_attr:meta])*
+				$field:ident : $ty:ty = $rule:expr
+			),* $(,)*
+		}
+	) => {
+		conf_schema! {
+			$(#[$attr])*
+			pub struct $name {
+				$(
+					$(#[$field_attr])*
+					$field : $ty = $rule
+				),*
+			}
+			group = $crate::schema::group;
+			finish = $crate::schema::finish;
+		}
+	};
+	(
+		$(#[$attr:meta])*
+		pub struct $name:ident {
+			$(
+				$(#[$field_attr:meta])*
+				$field:ident : $ty:ty = $rule:expr
+			),* $(,)*
+		}
+		finish = $finish:path;
+	) => {
+		conf_schema! {
+			$(#[$attr])*
+			pub struct $name {
+				$(
+					$(#[$field_attr])*
+					$field : $ty = $rule
+				),*
+			}
+			group = $crate::schema::group;
+			finish = $finish;
+		}
+	};
+	(
+		$(#[$attr:meta])*
+		pub struct $name:ident {
+			$(
+				$(#[$field_attr:meta])*
+				$field:ident : $ty:ty = $rule:expr
+			),* $(,)*
+		}
+		group = $group:path;
+		finish = $finish:path;
+	) => {
+		$(#[$attr])*
+		pub struct $name {
+			$(
+				$(#[$field_attr])*
+				pub $field: $ty,
+			)*
+		}
+
+		impl $name {
+			/// Names of settings the structure declares.
+			pub fn keys() -> &'static [&'static str] {
+				const KEYS: &'static [&'static str] = &[$(stringify!($field)),*];
+				KEYS
+			}
+		}
+
+		impl $crate::FromConf for $name {
+			fn from_conf(config: &$crate::Conf, xpath: &str) -> $crate::schema::Result<Self> {
+				let xpath = try!($group(config, xpath, $name::keys()));
+
+				let settings = $name {
+					$(
+						$field: try!($rule.read(config, &xpath, stringify!($field))),
+					)*
+				};
+
+				$finish(settings, config, &xpath)
+			}
+		}
+	};
+}
+
+
+/// Find the group at `xpath` following the reference if there is one and
+/// warn about settings in it which are not `known`.
+pub fn group(config: &Conf, xpath: &str, known: &[&str]) -> Result<String> {
+	let xpath = match config.resolve_reference(xpath) {
+		Some(path) => path,
+		None => return Error::new(format!("Group does not exist at path '{}'", xpath)).result(),
+	};
+
+	for key in config.keys(&xpath) {
+		if !known.contains(&key.as_str()) {
+			warn!("Unknown setting '{}' at '{}' is ignored", key, xpath);
+		}
+	}
+
+	Ok(xpath)
+}
+
+
+/// Find the group at `xpath` following the reference if there is one. Settings
+/// in it which are not `known` belong to someone else, e.g. to the module.
+pub fn open_group(config: &Conf, xpath: &str, _known: &[&str]) -> Result<String> {
+	match config.resolve_reference(xpath) {
+		Some(path) => Ok(path),
+		None => Error::new(format!("Group does not exist at path '{}'", xpath)).result(),
+	}
+}
+
+
+/// The structure is complete once its fields are read.
+pub fn finish<T>(settings: T, _config: &Conf, _xpath: &str) -> Result<T> {
+	Ok(settings)
+}
+
+
+/// Value of the setting which can be read from the config.
+pub trait Setting: Sized {
+	/// Read the setting at `xpath`. `None` means the setting is missing.
+	fn read(config: &Conf, xpath: &str) -> Result<Option<Self>>;
+}
+
+
+impl Setting for bool {
+	fn read(config: &Conf, xpath: &str) -> Result<Option<bool>> {
+		match config.lookup(xpath) {
+			Some(_) => match config.lookup_boolean(xpath) {
+				Some(value) => Ok(Some(value)),
+				None => Error::new(format!("Boolean is expected at '{}'", xpath)).result(),
+			},
+			None => Ok(None),
+		}
+	}
+}
+
+
+impl Setting for String {
+	fn read(config: &Conf, xpath: &str) -> Result<Option<String>> {
+		match config.lookup(xpath) {
+			Some(_) => match config.lookup_str(xpath) {
+				Some(value) => Ok(Some(value.to_string())),
+				None => Error::new(format!("String is expected at '{}'", xpath)).result(),
+			},
+			None => Ok(None),
+		}
+	}
+}
+
+
+/// Read integer of any width.
+fn integer(config: &Conf, xpath: &str) -> Result<Option<i64>> {
+	match config.lookup(xpath) {
+		Some(_) => match config.lookup_integer64(xpath) {
+			Some(value) => Ok(Some(value)),
+			None => Error::new(format!("Integer is expected at '{}'", xpath)).result(),
+		},
+		None => Ok(None),
+	}
+}
+
+
+macro_rules! integer_setting {
+	($ty:ty, $signed:expr) => {
+		impl Setting for $ty {
+			fn read(config: &Conf, xpath: &str) -> Result<Option<$ty>> {
+				match try!(integer(config, xpath)) {
+					Some(value) if (value as $ty) as i64 == value && (value >= 0 || $signed) => Ok(Some(value as $ty)),
+					Some(value) => Error::new(format!("Value {} does not fit {} at '{}'", value, stringify!($ty), xpath)).result(),
+					None => Ok(None),
+				}
+			}
+		}
+	};
+}
+
+integer_setting!(i32, true);
+integer_setting!(i64, true);
+integer_setting!(u16, false);
+integer_setting!(u32, false);
+integer_setting!(u64, false);
+integer_setting!(usize, false);
+
+
+/// Address ranges are written as strings, e.g. `10.0.0.0/8`.
+impl Setting for Cidr {
+	fn read(config: &Conf, xpath: &str) -> Result<Option<Cidr>> {
+		match try!(String::read(config, xpath)) {
+			Some(value) => match value.parse() {
+				Ok(range) => Ok(Some(range)),
+				Err(msg) => Error::new(format!("{} at '{}'", msg, xpath)).result(),
+			},
+			None => Ok(None),
+		}
+	}
+}
+
+
+/// Optional settings are always read, missing ones are `None`.
+impl<T: Setting> Setting for Option<T> {
+	fn read(config: &Conf, xpath: &str) -> Result<Option<Option<T>>> {
+		Ok(try!(T::read(config, xpath)).map(Some))
+	}
+}
+
+
+/// Lists are read item by item and every item must be valid.
+impl<T: Setting> Setting for Vec<T> {
+	fn read(config: &Conf, xpath: &str) -> Result<Option<Vec<T>>> {
+		let count = match config.lookup(xpath) {
+			Some(_) => match config.count(xpath) {
+				Some(count) => count,
+				None => return Error::new(format!("List is expected at '{}'", xpath)).result(),
+			},
+			None => return Ok(None),
+		};
+
+		let mut items = Vec::new();
+
+		for i in 0..count {
+			let item_xpath = format!("{}.[{}]", xpath, i);
+			match try!(T::read(config, &item_xpath)) {
+				Some(item) => items.push(item),
+				None => return Error::new(format!("Setting is required at '{}'", item_xpath)).result(),
+			};
+		}
+
+		Ok(Some(items))
+	}
+}
+
+
+/// Rule of reading the field of the settings structure.
+pub struct Field<T> {
+	default: Option<T>,
+	range: Option<(T, T)>,
+}
+
+
+impl<T: Setting + Clone + PartialOrd + Debug> Field<T> {
+	/// The setting must be present.
+	pub fn required() -> Field<T> {
+		Field {
+			default: None,
+			range: None,
+		}
+	}
+
+	/// The `value` is used when the setting is missing.
+	pub fn default(value: T) -> Field<T> {
+		Field {
+			default: Some(value),
+			range: None,
+		}
+	}
+
+	/// The setting must be between `min` and `max` inclusive.
+	pub fn range(mut self, min: T, max: T) -> Field<T> {
+		self.range = Some((min, max));
+		self
+	}
+
+	/// Read the value of the field from the setting `name` of the group at `xpath`.
+	pub fn read(&self, config: &Conf, xpath: &str, name: &str) -> Result<T> {
+		let xpath = format!("{}.{}", xpath, name);
+		let value = match try!(T::read(config, &xpath)) {
+			Some(value) => value,
+			None => return match self.default {
+				Some(ref value) => Ok(value.clone()),
+				None => Error::new(format!("Setting is required at '{}'", xpath)).result(),
+			},
+		};
+
+		if let Some((ref min, ref max)) = self.range {
+			if value < *min || value > *max {
+				return Error::new(format!("Value {:?} is out of range {:?}-{:?} at '{}'", value, min, max, xpath)).result();
+			}
+		}
+
+		Ok(value)
+	}
+}
+
+
+impl<T: Setting + Clone + PartialOrd + Debug> Field<Option<T>> {
+	/// The setting can be missing.
+	pub fn optional() -> Field<Option<T>> {
+		Field::default(None)
+	}
+}
+
+
+/// Rule of reading the field from the group of settings with its own `FromConf`.
+pub struct Section<T> {
+	default: Option<T>,
+}
+
+
+impl<T: FromConf + Clone> Section<T> {
+	/// The group must be present.
+	pub fn required() -> Section<T> {
+		Section {
+			default: None,
+		}
+	}
+
+	/// The `value` is used when the group is missing.
+	pub fn default(value: T) -> Section<T> {
+		Section {
+			default: Some(value),
+		}
+	}
+
+	/// Read the value of the field from the group `name` of the group at `xpath`.
+	pub fn read(&self, config: &Conf, xpath: &str, name: &str) -> Result<T> {
+		let xpath = format!("{}.{}", xpath, name);
+
+		match config.lookup(&xpath) {
+			Some(_) => T::from_conf(config, &xpath),
+			None => match self.default {
+				Some(ref value) => Ok(value.clone()),
+				None => Error::new(format!("Group is required at '{}'", xpath)).result(),
+			},
+		}
+	}
+}
+
+
+impl<T: FromConf + Clone> Section<Option<T>> {
+	/// The group can be missing.
+	pub fn optional() -> Section<Option<T>> {
+		Section::default(None)
+	}
+}
+
+
+/// Optional groups which are present are read as they are.
+impl<T: FromConf> FromConf for Option<T> {
+	fn from_conf(config: &Conf, xpath: &str) -> Result<Option<T>> {
+		Ok(Some(try!(T::from_conf(config, xpath))))
+	}
+}
+
+
+/// Rule of the field which keeps the path of the group the structure is read from,
+/// e.g. to read settings of the module later.
+pub struct Location;
+
+
+impl Location {
+	pub fn read(&self, _config: &Conf, xpath: &str, _name: &str) -> Result<String> {
+		Ok(xpath.to_string())
+	}
+}
+
+
+#[cfg(test)]
+conf_schema! {
+	/// Settings used in tests
+	pub struct TestConf {
+		count: u16 = Field::required().range(1, 16),
+		timeout: u64 = Field::default(100),
+		name: Option<String> = Field::optional(),
+	}
+}
+
+
+#[test]
+fn test_schema() {
+	use FromConf;
+
+	let overrides = |values: &[(&str, &str)]| {
+		let values: Vec<(String, String)> = values.iter().map(|&(path, value)| (path.to_string(), value.to_string())).collect();
+		Conf::empty().with_overrides(&values).unwrap()
+	};
+
+	let config = overrides(&[("test.count", "4"), ("test.name", "first"), ("test.cuont", "1")]);
+	let settings = TestConf::from_conf(&config, "test").unwrap();
+	assert_eq!(settings.count, 4);
+	assert_eq!(settings.timeout, 100);
+	assert_eq!(settings.name, Some("first".to_string()));
+	assert_eq!(TestConf::keys(), &["count", "timeout", "name"]);
+
+	assert!(TestConf::from_conf(&overrides(&[("test.count", "32")]), "test").is_err());
+	assert!(TestConf::from_conf(&overrides(&[("test.count", "-1")]), "test").is_err());
+	assert!(TestConf::from_conf(&overrides(&[("test.count", "4"), ("test.timeout", "-1")]), "test").is_err());
+	assert!(TestConf::from_conf(&overrides(&[("test.count", "4"), ("test.timeout", "\"1\"")]), "test").is_err());
+	assert_eq!(TestConf::from_conf(&overrides(&[("test.count", "4"), ("test.timeout", "1")]), "test").unwrap().timeout, 1);
+	assert!(TestConf::from_conf(&overrides(&[("test.count", "\"4\"")]), "test").is_err());
+	assert!(TestConf::from_conf(&Conf::empty(), "test").is_err());
+}
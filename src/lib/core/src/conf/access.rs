@@ -0,0 +1,115 @@
+//! Client address access lists configuration
+
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use wrust_types::{Error, Result};
+use wrust_types::net::Cidr;
+use wrust_conf::Conf;
+use wrust_conf::schema::Field;
+
+
+conf_schema! {
+	/// Access lists of client addresses. Addresses in the deny list are blocked and
+	/// if the allow list is not empty addresses which are not in it are blocked too.
+	#[derive(Clone, Default, PartialEq)]
+	pub struct AccessConf {
+		/// Ranges of addresses which are allowed
+		allow: Vec<Cidr> = Field::default(Vec::new()),
+		/// Ranges of addresses which are blocked
+		deny: Vec<Cidr> = Field::default(Vec::new()),
+		/// The file rules are read from in addition to lists
+		file: Option<String> = Field::optional(),
+	}
+	finish = AccessConf::finish;
+}
+
+
+impl AccessConf {
+	/// Test if the client address `ip` can connect.
+	pub fn allows(&self, ip: &IpAddr) -> bool {
+		if self.deny.iter().any(|range| range.contains(ip)) {
+			return false;
+		}
+
+		self.allow.is_empty() || self.allow.iter().any(|range| range.contains(ip))
+	}
+
+	/// Test if any list has ranges.
+	pub fn is_enabled(&self) -> bool {
+		!self.allow.is_empty() || !self.deny.is_empty()
+	}
+
+	/// Add rules written one per line as `allow <range>` or `deny <range>`.
+	/// Lines starting with `#` are comments.
+	fn add_rules(&mut self, text: &str, origin: &str) -> Result<()> {
+		for (number, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let mut words = line.split_whitespace();
+			let (action, range) = match (words.next(), words.next(), words.next()) {
+				(Some(action), Some(range), None) => (action, range),
+				_ => return Error::new(format!("Rule '{}' is not in the form of 'allow|deny <range>' at {}:{}", line, origin, number + 1)).result(),
+			};
+
+			let range = match range.parse() {
+				Ok(range) => range,
+				Err(msg) => return Error::new(format!("{} at {}:{}", msg, origin, number + 1)).result(),
+			};
+
+			match action {
+				"allow" => self.allow.push(range),
+				"deny" => self.deny.push(range),
+				_ => return Error::new(format!("Unknown action '{}' at {}:{}", action, origin, number + 1)).result(),
+			};
+		}
+
+		Ok(())
+	}
+
+	/// Rules of the file are read every time the configuration is loaded so
+	/// the file changed is applied on reload.
+	fn finish(mut self, _config: &Conf, xpath: &str) -> Result<AccessConf> {
+		if let Some(path) = self.file.clone() {
+			let mut text = String::new();
+			if let Err(msg) = File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+				return Error::new(format!("Cannot read access rules from {} at '{}.file'", path, xpath)).because(msg).result();
+			}
+
+			try!(self.add_rules(&text, &path));
+		}
+
+		Ok(self)
+	}
+}
+
+
+#[test]
+fn test_access_conf() {
+	let ip = |s: &str| -> IpAddr { s.parse().unwrap() };
+
+	let mut access = AccessConf::default();
+	assert!(access.allows(&ip("192.0.2.1")));
+	assert!(!access.is_enabled());
+
+	access.add_rules("# Office\nallow 10.0.0.0/8\n\n  deny 10.1.0.0/16  \nallow 2001:db8::/32\n", "access.list").unwrap();
+	assert_eq!(access.allow.len(), 2);
+	assert_eq!(access.deny.len(), 1);
+	assert!(access.allows(&ip("10.2.0.1")));
+	assert!(access.allows(&ip("::ffff:10.2.0.1")));
+	assert!(!access.allows(&ip("10.1.0.1")));
+	assert!(!access.allows(&ip("192.0.2.1")));
+	assert!(access.allows(&ip("2001:db8::1")));
+
+	let mut access = AccessConf::default();
+	access.add_rules("deny 192.0.2.0/24", "access.list").unwrap();
+	assert!(!access.allows(&ip("192.0.2.1")));
+	assert!(access.allows(&ip("198.51.100.1")));
+
+	assert!(AccessConf::default().add_rules("permit 10.0.0.0/8", "access.list").is_err());
+	assert!(AccessConf::default().add_rules("allow 10.0.0.0/8 now", "access.list").is_err());
+	assert!(AccessConf::default().add_rules("deny 10.0.0.0/40", "access.list").is_err());
+}
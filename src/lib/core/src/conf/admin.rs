@@ -0,0 +1,13 @@
+//! Admin endpoint configuration
+
+use wrust_conf::schema::Field;
+
+
+conf_schema! {
+	/// Admin endpoint settings
+	#[derive(Clone)]
+	pub struct AdminConf {
+		/// Filesystem path of the UNIX socket the admin endpoint listens on
+		path: String = Field::required(),
+	}
+}
@@ -0,0 +1,27 @@
+//! Client address limits configuration
+
+use wrust_conf::schema::Field;
+
+
+conf_schema! {
+	/// Limits applied to each client IP address separately. Zero disables the limit.
+	#[derive(Clone, Default, PartialEq)]
+	pub struct LimitConf {
+		/// The maximum number of connections open from the address at once
+		connections: u32 = Field::default(0),
+		/// New connections per second the address can open
+		rate: u32 = Field::default(0),
+		/// New connections the address can open at once, the rate if missing
+		burst: u32 = Field::default(0),
+		/// Bytes per second the address can read and write in total
+		bandwidth: u64 = Field::default(0),
+	}
+}
+
+
+impl LimitConf {
+	/// Test if any limit is enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.connections > 0 || self.rate > 0 || self.bandwidth > 0
+	}
+}
@@ -1,5 +1,15 @@
+mod access;
+mod admin;
+mod limit;
 mod module;
 mod network;
+mod tls;
+mod timeout;
 
+pub use self::access::AccessConf;
+pub use self::admin::AdminConf;
+pub use self::limit::LimitConf;
 pub use self::module::ModuleConf;
 pub use self::network::{SocketConf, NetSocketConf, UnixSocketConf};
+pub use self::tls::TlsConf;
+pub use self::timeout::TimeoutConf;
@@ -1,108 +1,107 @@
 //! Network configuration
 
+use std::fmt;
 use std::str::FromStr;
 use wrust_types::{Error, Result};
 use wrust_types::net::Protocol;
 use wrust_conf::{Conf, FromConf};
+use wrust_conf::schema::{Field, Setting, open_group, finish};
 
 
-/// Socket configuration
-#[derive(Clone)]
-pub struct SocketConf {
-	/// Network protocol with address associated
-	pub protocol: Protocol<NetSocketConf, NetSocketConf, UnixSocketConf>,
+conf_schema! {
+	/// Socket configuration
+	#[derive(Clone, PartialEq)]
+	pub struct SocketConf {
+		/// Network protocol with address associated
+		protocol: Protocol<NetSocketConf, NetSocketConf, UnixSocketConf> = Endpoint,
+	}
+	group = open_group;
+	finish = finish;
 }
 
 
-impl FromConf for SocketConf {
-	// Load settings from the config
-	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		// Determine where xpath is reference to
-		let xpath = match config.resolve_reference(xpath) {
-			Some(path) => path,
-			None => return Error::new(format!("Reference or group is not found at '{}'", xpath)).result(),
-		};
+/// Rule of reading the protocol which selects settings of the address next to it.
+struct Endpoint;
+
 
+impl Endpoint {
+	fn read(&self, config: &Conf, xpath: &str, name: &str) -> Result<Protocol<NetSocketConf, NetSocketConf, UnixSocketConf>> {
 		// Read protocol name
-		let protocol = match config.lookup_str(&format!("{}.protocol", &xpath)) {
+		let protocol = match try!(String::read(config, &format!("{}.{}", xpath, name))) {
 			Some(name) => name.to_uppercase(),
 			None => return Error::new(format!("Protocol is undefined at '{}'", xpath)).result(),
 		};
 
 		// Read other settings based on protocol type
-		let protocol = match Protocol::from_str(&protocol) {
-			Ok(Protocol::Tcp(_)) => match NetSocketConf::from_conf(config, &xpath) {
-				Ok(addr) => Protocol::Tcp(addr),
-				Err(msg) => return Err(msg),
-			},
-			Ok(Protocol::Udp(_)) => match NetSocketConf::from_conf(config, &xpath) {
-				Ok(addr) => Protocol::Udp(addr),
-				Err(msg) => return Err(msg),
-			},
-			Ok(Protocol::Unix(_)) => match UnixSocketConf::from_conf(config, &xpath) {
-				Ok(addr) => Protocol::Unix(addr),
-				Err(msg) => return Err(msg),
-			},
-			Err(error) => return Error::new(format!("Invalid protocol at '{}': {}", xpath, error)).result(),
-		};
+		match Protocol::from_str(&protocol) {
+			Ok(Protocol::Tcp(_)) => Ok(Protocol::Tcp(try!(NetSocketConf::from_conf(config, xpath)))),
+			Ok(Protocol::Udp(_)) => Ok(Protocol::Udp(try!(NetSocketConf::from_conf(config, xpath)))),
+			Ok(Protocol::Unix(_)) => Ok(Protocol::Unix(try!(UnixSocketConf::from_conf(config, xpath)))),
+			Err(error) => Error::new(format!("Invalid protocol at '{}': {}", xpath, error)).result(),
+		}
+	}
+}
 
-		Ok(SocketConf {
-			protocol: protocol,
-		})
+
+/// Sockets are written as the protocol name followed by the address,
+/// e.g. `tcp 127.0.0.1:8080` or `unix /tmp/wrustlr.sock`.
+impl fmt::Display for SocketConf {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.protocol {
+			Protocol::Tcp(ref details) => write!(f, "tcp {}:{}", details.address, details.port),
+			Protocol::Udp(ref details) => write!(f, "udp {}:{}", details.address, details.port),
+			Protocol::Unix(ref details) => write!(f, "unix {}", details.path),
+		}
 	}
 }
 
 
-/// Network socket configuration
-#[derive(Clone)]
-pub struct NetSocketConf {
-	/// IP address
-	pub address: String,
-	/// Port
-	pub port: u16,
+conf_schema! {
+	/// Network socket configuration
+	#[derive(Clone, PartialEq)]
+	pub struct NetSocketConf {
+		/// Protocol name the address is read for
+		protocol: String = Field::required(),
+		/// IP address, `*` is any address
+		address: String = Field::required(),
+		/// Port
+		port: u16 = Field::required().range(1, 65535),
+	}
+	finish = NetSocketConf::finish;
 }
 
 
-impl FromConf for NetSocketConf {
-	// Load settings from the config
-	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		Ok(NetSocketConf {
-			address: match config.lookup_str(&format!("{}.address", xpath)) {
-				Some(ip) => {
-					if ip == "*" {
-						"0.0.0.0".to_string()
-					}
-					else {
-						ip.to_string()
-					}
-				},
-				None => return Error::new(format!("Address is required at '{}'", xpath)).result(),
-			},
-			port: match config.lookup_integer32(&format!("{}.port", xpath)) {
-				Some(port) => port as u16,
-				None => return Error::new(format!("Port is required at '{}'", xpath)).result(),
-			}
-		})
+impl NetSocketConf {
+	fn finish(mut self, _config: &Conf, _xpath: &str) -> Result<NetSocketConf> {
+		// Settings are compared to find changes so the same ones are written the same way
+		self.protocol = self.protocol.to_lowercase();
+
+		if self.address == "*" {
+			self.address = "0.0.0.0".to_string();
+		}
+
+		Ok(self)
 	}
 }
 
 
-/// UNIX socket configuration
-#[derive(Clone)]
-pub struct UnixSocketConf {
-	/// Filesystem path
-	pub path: String,
+conf_schema! {
+	/// UNIX socket configuration
+	#[derive(Clone, PartialEq)]
+	pub struct UnixSocketConf {
+		/// Protocol name the path is read for
+		protocol: String = Field::required(),
+		/// Filesystem path
+		path: String = Field::required(),
+	}
+	finish = UnixSocketConf::finish;
 }
 
 
-impl FromConf for UnixSocketConf {
-	// Load settings from the config
-	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		Ok(UnixSocketConf {
-			path: match config.lookup_str(&format!("{}.path", xpath)) {
-				Some(path) => path.to_string(),
-				None => return Error::new(format!("Path is required at '{}'", xpath)).result(),
-			}
-		})
+impl UnixSocketConf {
+	fn finish(mut self, _config: &Conf, _xpath: &str) -> Result<UnixSocketConf> {
+		self.protocol = self.protocol.to_lowercase();
+
+		Ok(self)
 	}
 }
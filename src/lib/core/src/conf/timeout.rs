@@ -0,0 +1,27 @@
+//! Client connection timeout configuration
+
+use wrust_conf::schema::Field;
+
+
+conf_schema! {
+	/// Client connection timeouts in milliseconds. Zero disables the timeout.
+	#[derive(Clone, Default, PartialEq)]
+	pub struct TimeoutConf {
+		/// The longest time the connection can stay without I/O events
+		idle: u64 = Field::default(0),
+		/// The longest time the connection can wait to become readable
+		read: u64 = Field::default(0),
+		/// The longest time the connection can wait to become writable
+		write: u64 = Field::default(0),
+		/// The longest time the connection can stay open
+		lifetime: u64 = Field::default(0),
+	}
+}
+
+
+impl TimeoutConf {
+	/// Test if any timeout is enabled.
+	pub fn is_enabled(&self) -> bool {
+		self.idle > 0 || self.read > 0 || self.write > 0 || self.lifetime > 0
+	}
+}
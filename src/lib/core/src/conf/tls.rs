@@ -0,0 +1,17 @@
+//! TLS configuration
+
+use wrust_conf::schema::Field;
+
+
+conf_schema! {
+	/// TLS configuration
+	#[derive(Clone, PartialEq)]
+	pub struct TlsConf {
+		/// Path to the PEM file with the certificate chain
+		certificate: String = Field::required(),
+		/// Path to the PEM file with the private key
+		private_key: String = Field::required(),
+		/// Path to the PEM file with CA certificates used to verify clients
+		client_ca: Option<String> = Field::optional(),
+	}
+}
@@ -1,9 +1,10 @@
 #[macro_use] extern crate log;
 extern crate wrust_types;
 extern crate wrust_io;
-extern crate wrust_conf;
+#[macro_use] extern crate wrust_conf;
 extern crate wrust_module;
 extern crate wrust_async;
+extern crate libc;
 
 pub mod conf;
 pub mod net;
@@ -1,12 +1,13 @@
 use std::collections::HashMap;
 use wrust_types::{Error, Result};
 use wrust_conf::Conf;
-use wrust_module::Category;
-use wrust_module::stream;
+use wrust_module::{Category, Instance};
 
 pub struct Factory {
 	config: Conf,
-	streams: HashMap<String, Box<Fn(&Conf, &String) -> Box<stream::Behavior>>>,
+	streams: HashMap<String, Box<Fn(&Conf, &String) -> Instance>>,
+	datagrams: HashMap<String, Box<Fn(&Conf, &String) -> Instance>>,
+	registered: Vec<(Category, String, String)>,
 }
 
 impl Factory {
@@ -14,24 +15,52 @@ impl Factory {
 		Factory {
 			config: config.clone(),
 			streams: HashMap::new(),
+			datagrams: HashMap::new(),
+			registered: Vec::new(),
 		}
 	}
 
 	pub fn register<F: 'static>(&mut self, category: Category, name: String, version: String, producer: F)
-		where F: Fn(&Conf, &String) -> Box<stream::Behavior> {
+		where F: Fn(&Conf, &String) -> Instance {
 		info!("Registered module {:?}:{} v{}", category, name, version);
+		self.registered.push((category, name.clone(), version));
 		match category {
 			Category::Stream => self.streams.insert(name, Box::new(producer)),
+			Category::Datagram => self.datagrams.insert(name, Box::new(producer)),
 		};
 	}
 
-	pub fn produce(&self, category: Category, name: &String, xpath_base: &String) -> Result<Box<stream::Behavior>> {
-		debug!("Instantiate module {:?}:{} using XPath base '{}'", category, name, xpath_base);
+	/// Get categories, names and versions of modules in the order they are registered.
+	pub fn modules(&self) -> &[(Category, String, String)] {
+		&self.registered
+	}
+
+	/// Test if the module `name` of the `category` is registered.
+	pub fn is_registered(&self, category: Category, name: &str) -> bool {
 		match category {
-			Category::Stream => match self.streams.get(name) {
-				Some(new) => Ok(new(&self.config, xpath_base)),
-				None => Error::new(format!("Module {:?}:{} is not registered", category, name)).result(),
+			Category::Stream => self.streams.contains_key(name),
+			Category::Datagram => self.datagrams.contains_key(name),
+		}
+	}
+
+	pub fn produce(&self, category: Category, name: &String, xpath_base: &String) -> Result<Instance> {
+		debug!("Instantiate module {:?}:{} using XPath base '{}'", category, name, xpath_base);
+		let producers = match category {
+			Category::Stream => &self.streams,
+			Category::Datagram => &self.datagrams,
+		};
+
+		match producers.get(name) {
+			Some(new) => {
+				let instance = new(&self.config, xpath_base);
+				if instance.category() == category {
+					Ok(instance)
+				}
+				else {
+					Error::new(format!("Module {:?}:{} produced instance of {:?} category", category, name, instance.category())).result()
+				}
 			},
+			None => Error::new(format!("Module {:?}:{} is not registered", category, name)).result(),
 		}
 	}
 }
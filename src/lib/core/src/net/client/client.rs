@@ -1,14 +1,19 @@
 use std::fmt;
+use std::net::SocketAddr;
 use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use std::cell::{RefCell, UnsafeCell};
 use std::ptr;
 use wrust_io::mio;
 use wrust_io::mio::tcp::*;
 use wrust_io::mio::unix::*;
-use wrust_types::Result;
+use wrust_io::tls::{Session as TlsSession, ServerSession};
+use wrust_types::{Error, Result};
 use wrust_types::net::Protocol;
 use wrust_types::net::connection::{State, Descriptor};
-use wrust_module::stream::{Intention, Flush};
+use wrust_module::stream::{Intention, Flush, Session};
+use ::conf::TimeoutConf;
 
 
 pub type ClientProtocol = Protocol<TcpStream, (), UnixStream>;
@@ -52,18 +57,35 @@ impl LeftData {
 }
 
 
+/// Moments the client connection timeouts are measured from.
+struct Timing {
+	created: Instant,
+	active: Instant,
+	waiting: Instant,
+	timer: Option<mio::Timeout>,
+}
+
+
 pub struct Client {
 	server_token: mio::Token,
 	token: mio::Token,
+	serial: usize,
 	socket: Mutex<RefCell<ClientProtocol>>,
 	state: Mutex<RefCell<State>>,
 	descriptor: Descriptor,
 	left_data: Mutex<UnsafeCell<Option<LeftData>>>,
+	tls: Option<Mutex<RefCell<ServerSession>>>,
+	busy: AtomicBool,
+	due: AtomicBool,
+	wake: Mutex<RefCell<Option<State>>>,
+	outbound: Mutex<RefCell<Vec<mio::Token>>>,
+	session: Mutex<RefCell<Option<Session>>>,
+	timing: Mutex<RefCell<Timing>>,
 }
 
 
 impl Client {
-	pub fn new(server_token: mio::Token, token: mio::Token, socket: ClientProtocol) -> Client {
+	pub fn new(server_token: mio::Token, token: mio::Token, serial: usize, socket: ClientProtocol, tls: Option<ServerSession>) -> Client {
 		let descriptor = Descriptor::new(
 			token.as_usize() as u32,
 			match socket {
@@ -71,15 +93,45 @@ impl Client {
 				Protocol::Udp(_) => None,
 				Protocol::Unix(_) => None,
 			}
-		);
+		).with_serial(serial);
+
+		Client::with_descriptor(server_token, token, serial, socket, tls, descriptor, State::Opened)
+	}
 
+	/// Create the outbound connection to `addr` opened optionally on behalf of the connection
+	/// with the token and serial in `owner`. The peer address of the connecting socket is not
+	/// known yet so it is given explicitly.
+	pub fn outbound(server_token: mio::Token, token: mio::Token, serial: usize, owner: Option<(mio::Token, usize)>, socket: ClientProtocol, addr: Option<SocketAddr>) -> Client {
+		let descriptor = Descriptor::outbound(token.as_usize() as u32, addr, owner.map(|(owner, serial)| (owner.as_usize() as u32, serial)))
+			.with_serial(serial);
+
+		Client::with_descriptor(server_token, token, serial, socket, None, descriptor, State::Connecting)
+	}
+
+	fn with_descriptor(server_token: mio::Token, token: mio::Token, serial: usize, socket: ClientProtocol, tls: Option<ServerSession>, descriptor: Descriptor, state: State) -> Client {
 		Client {
 			server_token: server_token,
 			token: token,
+			serial: serial,
 			socket: Mutex::new(RefCell::new(socket)),
-			state: Mutex::new(RefCell::new(State::Opened)),
+			state: Mutex::new(RefCell::new(state)),
 			descriptor: descriptor,
 			left_data: Mutex::new(UnsafeCell::new(None)),
+			tls: match tls {
+				Some(session) => Some(Mutex::new(RefCell::new(session))),
+				None => None,
+			},
+			busy: AtomicBool::new(false),
+			due: AtomicBool::new(false),
+			wake: Mutex::new(RefCell::new(None)),
+			outbound: Mutex::new(RefCell::new(Vec::new())),
+			session: Mutex::new(RefCell::new(None)),
+			timing: Mutex::new(RefCell::new(Timing {
+				created: Instant::now(),
+				active: Instant::now(),
+				waiting: Instant::now(),
+				timer: None,
+			})),
 		}
 	}
 
@@ -91,6 +143,68 @@ impl Client {
 		&self.token
 	}
 
+	/// The number which is unique for each client connection added to the registry
+	/// while tokens are reused.
+	pub fn serial(&self) -> usize {
+		self.serial
+	}
+
+	/// Get the token and serial of the connection which opened this outbound connection.
+	pub fn owner(&self) -> Option<(mio::Token, usize)> {
+		self.descriptor.owner().map(|(id, serial)| (mio::Token(id as usize), serial))
+	}
+
+	/// Test if the client connection is passed to a worker and is not waiting for I/O events.
+	pub fn is_busy(&self) -> bool {
+		self.busy.load(Ordering::SeqCst)
+	}
+
+	pub fn set_busy(&self, busy: bool) {
+		self.busy.store(busy, Ordering::SeqCst);
+	}
+
+	/// Take the flag of the stream processing module timer which expired while
+	/// the client connection was busy.
+	pub fn take_due(&self) -> bool {
+		self.due.swap(false, Ordering::SeqCst)
+	}
+
+	pub fn set_due(&self) {
+		self.due.store(true, Ordering::SeqCst);
+	}
+
+	/// Keep the token of the outbound connection opened on behalf of the client connection.
+	pub fn add_outbound(&self, token: mio::Token) {
+		let guard = self.outbound.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.push(token);
+	}
+
+	/// Take tokens of outbound connections opened on behalf of the client connection.
+	/// Tokens can be reused by other connections already.
+	pub fn take_outbound(&self) -> Vec<mio::Token> {
+		let guard = self.outbound.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.split_off(0)
+	}
+
+	/// Take the state the client connection was asked to continue in while it was busy.
+	pub fn take_wake(&self) -> Option<State> {
+		let guard = self.wake.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.take()
+	}
+
+	/// Keep the `state` the client connection must continue in when it is not busy anymore.
+	/// Closing takes precedence over writing.
+	pub fn set_wake(&self, state: State) {
+		let guard = self.wake.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		if *cell != Some(State::Closed) {
+			*cell = Some(state);
+		}
+	}
+
 	pub fn state(&self) -> State {
 		let guard = self.state.lock().unwrap();
 		let cell = guard.borrow();
@@ -114,6 +228,123 @@ impl Client {
 		func(&mut *cell)
 	}
 
+	/// Test if the client connection is secured with TLS.
+	pub fn is_tls(&self) -> bool {
+		self.tls.is_some()
+	}
+
+	/// Test if the TLS session has encrypted data which is not written to the socket yet.
+	pub fn tls_wants_write(&self) -> bool {
+		match self.tls {
+			Some(ref tls) => {
+				let guard = tls.lock().unwrap();
+				let cell = guard.borrow();
+				cell.wants_write()
+			},
+			None => false,
+		}
+	}
+
+	/// Execute `func` on the socket and the TLS session of the client connection.
+	/// The socket is always locked before the session.
+	pub fn then_on_tls<F, T>(&self, mut func: F) -> Result<T>
+		where F: FnMut(&mut ClientProtocol, &mut ServerSession) -> Result<T> {
+		match self.tls {
+			Some(ref tls) => {
+				let socket_guard = self.socket.lock().unwrap();
+				let mut socket_cell = socket_guard.borrow_mut();
+				let tls_guard = tls.lock().unwrap();
+				let mut tls_cell = tls_guard.borrow_mut();
+				func(&mut *socket_cell, &mut *tls_cell)
+			},
+			None => Error::new("The client connection is not secured with TLS").result(),
+		}
+	}
+
+	/// Keep the session the stream processing module created for the client connection.
+	pub fn set_session(&self, session: Session) {
+		let guard = self.session.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		*cell = Some(session);
+	}
+
+	/// Take the session out of the client connection which is going to be closed.
+	pub fn take_session(&self) -> Option<Session> {
+		let guard = self.session.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.take()
+	}
+
+	/// Execute `func` on the session of the client connection. Only one worker
+	/// processes the client connection at a time so the lock is never contended.
+	pub fn then_on_session<F, T>(&self, mut func: F) -> Result<T>
+		where F: FnMut(&mut Session) -> T {
+		let guard = self.session.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		match *cell {
+			Some(ref mut session) => Ok(func(session)),
+			None => Error::new(format!("{:?} has no session", self)).result(),
+		}
+	}
+
+	/// Get time since the client connection is opened.
+	pub fn age(&self) -> Duration {
+		let guard = self.timing.lock().unwrap();
+		let cell = guard.borrow();
+		cell.created.elapsed()
+	}
+
+	/// The client connection has got I/O events.
+	pub fn touch(&self) {
+		let guard = self.timing.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.active = Instant::now();
+	}
+
+	/// The client connection starts waiting for I/O events.
+	pub fn set_waiting(&self) {
+		let guard = self.timing.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		cell.waiting = Instant::now();
+	}
+
+	/// Replace the timer which expires the client connection and return the previous one.
+	pub fn replace_timer(&self, timer: Option<mio::Timeout>) -> Option<mio::Timeout> {
+		let guard = self.timing.lock().unwrap();
+		let mut cell = guard.borrow_mut();
+		let previous = cell.timer.take();
+		cell.timer = timer;
+		previous
+	}
+
+	/// Get deadlines of timeouts enabled in `conf` which apply to the current state
+	/// of the client connection along with timeout names.
+	pub fn deadlines(&self, conf: &TimeoutConf) -> Vec<(&'static str, Instant)> {
+		let guard = self.timing.lock().unwrap();
+		let cell = guard.borrow();
+		let mut deadlines = Vec::new();
+
+		if conf.lifetime > 0 {
+			deadlines.push(("lifetime", cell.created + Duration::from_millis(conf.lifetime)));
+		}
+
+		if conf.idle > 0 {
+			deadlines.push(("idle", cell.active + Duration::from_millis(conf.idle)));
+		}
+
+		match self.state() {
+			State::Reading | State::Handshaking if conf.read > 0 => {
+				deadlines.push(("read", cell.waiting + Duration::from_millis(conf.read)));
+			},
+			State::Writing | State::Flushing if conf.write > 0 => {
+				deadlines.push(("write", cell.waiting + Duration::from_millis(conf.write)));
+			},
+			_ => (),
+		};
+
+		deadlines
+	}
+
 	pub fn left_data(&self) -> Option<LeftData> {
 		let cell = self.left_data.lock().unwrap();
 		let mut left_data: Option<LeftData> = None;
@@ -122,6 +353,12 @@ impl Client {
 		left_data
 	}
 
+	/// Test if data is left unwritten since the last write operation.
+	pub fn has_left_data(&self) -> bool {
+		let cell = self.left_data.lock().unwrap();
+		unsafe { (*cell.get()).is_some() }
+	}
+
 	pub fn set_left_data(&self, data: Option<LeftData>) {
 		let cell = self.left_data.lock().unwrap();
 		let mut left_data = data;
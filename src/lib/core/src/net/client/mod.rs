@@ -2,5 +2,5 @@ mod client;
 mod registry;
 
 pub use self::client::LeftData;
-pub use self::client::Client;
+pub use self::client::{Client, ClientProtocol};
 pub use self::registry::Registry;
@@ -1,30 +1,41 @@
+use std::net::SocketAddr;
 use std::ops::{Index, IndexMut};
 use std::sync::Arc;
 use wrust_io::mio;
 use wrust_io::mio::util::Slab;
+use wrust_io::tls::ServerSession;
 use wrust_types::{Result, Error};
 use super::Client;
 use super::client::ClientProtocol;
 
 pub struct Registry {
 	items: Slab<Arc<Client>>,
+	capacity: usize,
+	serial: usize,
 }
 
 
 impl Registry {
 	pub fn new(start_from: usize, capacity: usize) -> Registry {
 		Registry {
-			items: Slab::new_starting_at(mio::Token(start_from), capacity)
+			items: Slab::new_starting_at(mio::Token(start_from), capacity),
+			capacity: capacity,
+			serial: 0,
 		}
 	}
 
-	pub fn add(&mut self, server_token: mio::Token, socket: ClientProtocol) -> Result<mio::Token> {
+	pub fn add(&mut self, server_token: mio::Token, socket: ClientProtocol, tls: Option<ServerSession>) -> Result<mio::Token> {
+		self.serial = self.serial.wrapping_add(1);
+		let serial = self.serial;
+
 		let token = self.items
 			.insert_with(|token| {
 					Arc::new(Client::new(
 						server_token,
 						token,
-						socket))
+						serial,
+						socket,
+						tls))
 				});
 
 		match token {
@@ -33,9 +44,58 @@ impl Registry {
 		}
 	}
 
+	/// Add the outbound connection to `addr` opened optionally on behalf of the connection
+	/// with the token and serial in `owner`.
+	pub fn add_outbound(&mut self, server_token: mio::Token, owner: Option<(mio::Token, usize)>, socket: ClientProtocol, addr: Option<SocketAddr>) -> Result<mio::Token> {
+		self.serial = self.serial.wrapping_add(1);
+		let serial = self.serial;
+
+		let token = self.items
+			.insert_with(|token| {
+					Arc::new(Client::outbound(
+						server_token,
+						token,
+						serial,
+						owner,
+						socket,
+						addr))
+				});
+
+		match token {
+			Some(token) => Ok(token),
+			None => Error::new("Cannot add the outbound Client to the Registry").result()
+		}
+	}
+
+	/// Get the client connection if it is still in the registry.
+	pub fn get(&self, index: mio::Token) -> Option<&Arc<Client>> {
+		self.items.get(index)
+	}
+
 	pub fn remove(&mut self, index: mio::Token) {
 		self.items.remove(index);
 	}
+
+	pub fn len(&self) -> usize {
+		self.items.count()
+	}
+
+	/// Test if there is no room for another connection.
+	pub fn is_full(&self) -> bool {
+		self.len() >= self.capacity
+	}
+
+	pub fn each<Func, R>(&self, mut func: Func) -> Option<R>
+		where Func: FnMut(&Arc<Client>) -> Option<R> {
+		for item in self.items.iter() {
+			let result = func(item);
+			if result.is_some() {
+				return result;
+			}
+		}
+
+		None
+	}
 }
 
 
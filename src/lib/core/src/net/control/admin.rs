@@ -0,0 +1,140 @@
+//! Admin endpoint listening on the UNIX socket.
+
+use std::fs;
+use std::thread;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use libc;
+use wrust_types::{Error, Result};
+use ::conf::AdminConf;
+use super::{Command, Response, Controller};
+
+
+/// Permissions of the socket file so only the user the server runs as sends commands.
+const SOCKET_MODE: u32 = 0o600;
+
+/// Admin endpoint which reads commands line by line from local clients
+/// and writes responses back. The socket file is removed when the `Admin` is dropped
+/// unless the process the binary is upgraded to has bound its own socket at the path.
+pub struct Admin {
+	path: PathBuf,
+	/// Device and inode of the socket file bound
+	inode: (u64, u64),
+}
+
+
+impl Admin {
+	/// Bind the admin socket and start serving commands with the `controller`.
+	pub fn start(conf: &AdminConf, controller: Controller) -> Result<Admin> {
+		let path = PathBuf::from(&conf.path);
+
+		// The socket file can be left by the server which did not shutdown properly
+		// but other files at the path are never removed
+		if let Ok(meta) = fs::symlink_metadata(&path) {
+			if !meta.file_type().is_socket() {
+				return Error::new(format!("Admin socket path {} exists and is not a socket", conf.path)).result();
+			}
+
+			let _ = fs::remove_file(&path);
+		}
+
+		// The socket file is created with the restrictive mode so it is never
+		// accessible by other users, even for a moment
+		let umask = unsafe { libc::umask((0o777 & !SOCKET_MODE) as libc::mode_t) };
+		let listener = UnixListener::bind(&path);
+		unsafe { libc::umask(umask) };
+
+		let listener = match listener {
+			Ok(listener) => listener,
+			Err(msg) => return Error::new(format!("Admin socket binding to {} failed", conf.path)).because(msg).result(),
+		};
+
+		let inode = match fs::metadata(&path) {
+			Ok(meta) => (meta.dev(), meta.ino()),
+			Err(msg) => {
+				let _ = fs::remove_file(&path);
+				return Error::new(format!("Cannot get metadata of the admin socket {}", conf.path)).because(msg).result();
+			},
+		};
+
+		info!("Admin commands are accepted on {}", conf.path);
+
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				match stream {
+					Ok(stream) => {
+						// The client which keeps the connection idle does not block others
+						let controller = controller.clone();
+						thread::spawn(move || {
+							if let Err(msg) = serve(stream, &controller) {
+								warn!("Admin connection failed because {}", msg);
+							}
+						});
+					},
+					Err(msg) => {
+						error!("Admin socket accept failed with error: {}", msg);
+						break;
+					},
+				};
+			}
+		});
+
+		Ok(Admin {
+			path: path,
+			inode: inode,
+		})
+	}
+
+	/// Get the path of the admin socket.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+}
+
+
+impl Drop for Admin {
+	fn drop(&mut self) {
+		if let Ok(meta) = fs::metadata(&self.path) {
+			if (meta.dev(), meta.ino()) == self.inode {
+				let _ = fs::remove_file(&self.path);
+			}
+		}
+	}
+}
+
+
+/// Execute commands the admin client sends until it closes the connection.
+fn serve(stream: UnixStream, controller: &Controller) -> Result<()> {
+	let mut writer = match stream.try_clone() {
+		Ok(writer) => writer,
+		Err(msg) => return Error::new("Cannot clone the admin connection").because(msg).result(),
+	};
+
+	for line in BufReader::new(stream).lines() {
+		let line = match line {
+			Ok(line) => line,
+			Err(msg) => return Error::new("Cannot read the admin command").because(msg).result(),
+		};
+
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let response = match Command::from_str(&line) {
+			Ok(command) => {
+				info!("Received {:?} command from the admin socket", command);
+				try!(controller.execute(command))
+			},
+			Err(msg) => Response::Failed(format!("{}", msg)),
+		};
+
+		if let Err(msg) = write!(writer, "{}", response) {
+			return Error::new("Cannot write the admin response").because(msg).result();
+		}
+	}
+
+	Ok(())
+}
@@ -0,0 +1,240 @@
+//! Admin commands and responses.
+
+use std::fmt;
+use std::str::FromStr;
+use std::net::SocketAddr;
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+use log::LogLevelFilter;
+use wrust_types::{Error, Result};
+use wrust_types::net::connection::State;
+
+
+/// Command the `Core` executes on behalf of the administrator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+	/// Report the state of the server.
+	Status,
+	/// List client connections.
+	Connections,
+	/// Close the client connection with the identifier given.
+	Drop(u32),
+	/// Stop accepting connections on the listener with the index given.
+	Pause(usize),
+	/// Accept connections on the paused listener with the index given again.
+	Resume(usize),
+	/// Change the logging level.
+	LogLevel(LogLevelFilter),
+	/// Stop accepting connections, let open connections finish and shutdown the server.
+	Shutdown,
+	/// Shutdown the server immediately dropping open connections.
+	Terminate,
+	/// List sockets of listeners to pass them to the new process on upgrade.
+	/// Socket files of UNIX listeners are kept on shutdown from then on.
+	Handoff,
+	/// Own sockets of listeners again after the upgrade failed.
+	Reclaim,
+}
+
+
+impl FromStr for Command {
+	type Err = Error;
+
+	/// Parse the command line `s` in the form of `name [argument]`.
+	fn from_str(s: &str) -> Result<Command> {
+		let mut words = s.split_whitespace();
+
+		let name = match words.next() {
+			Some(name) => name.to_lowercase(),
+			None => return Error::new("Command is empty").result(),
+		};
+
+		let argument = words.next();
+		if words.next().is_some() {
+			return Error::new(format!("Command '{}' has too many arguments", name)).result();
+		}
+
+		let command = match (name.as_str(), argument) {
+			("status", None) => Command::Status,
+			("connections", None) => Command::Connections,
+			("drop", Some(id)) => Command::Drop(try!(parse_argument(&name, id))),
+			("pause", Some(index)) => Command::Pause(try!(parse_argument(&name, index))),
+			("resume", Some(index)) => Command::Resume(try!(parse_argument(&name, index))),
+			("log_level", Some(level)) => Command::LogLevel(try!(parse_argument(&name, level))),
+			("shutdown", None) => Command::Shutdown,
+			("terminate", None) => Command::Terminate,
+			("status", Some(_)) | ("connections", Some(_)) | ("shutdown", Some(_)) | ("terminate", Some(_)) =>
+				return Error::new(format!("Command '{}' takes no arguments", name)).result(),
+			("drop", None) | ("pause", None) | ("resume", None) | ("log_level", None) =>
+				return Error::new(format!("Command '{}' requires an argument", name)).result(),
+			_ => return Error::new(format!("Unknown command '{}'", name)).result(),
+		};
+
+		Ok(command)
+	}
+}
+
+
+/// Response of the `Core` to the `Command`.
+#[derive(Debug, Clone)]
+pub enum Response {
+	/// The command is executed.
+	Done,
+	/// The state of the server.
+	Status(Status),
+	/// Client connections.
+	Connections(Vec<ConnectionStatus>),
+	/// Sockets of listeners.
+	Sockets(Vec<SocketStatus>),
+	/// The command failed with the message given.
+	Failed(String),
+}
+
+
+/// The state of the server.
+#[derive(Debug, Clone)]
+pub struct Status {
+	/// Lifecycle stage of the `Core`
+	pub stage: String,
+	/// Worker thread count
+	pub workers: usize,
+	/// Client connection count
+	pub connections: usize,
+	/// Listeners in the order of configuration
+	pub listeners: Vec<ListenerStatus>,
+}
+
+
+/// The state of the listener.
+#[derive(Debug, Clone)]
+pub struct ListenerStatus {
+	/// Index of the listener which commands refer to
+	pub index: usize,
+	/// Protocol and address the listener is bound to
+	pub address: String,
+	/// Name of the module the traffic is forwarded to
+	pub module: String,
+	/// The listener accepts connections
+	pub accepting: bool,
+	/// Open connections the listener accepted
+	pub connections: usize,
+	/// Connections denied because of limits of client addresses
+	pub denied: usize,
+}
+
+
+/// The socket of the listener.
+#[derive(Debug, Clone)]
+pub struct SocketStatus {
+	/// Protocol and address the listener is bound to
+	pub address: String,
+	/// File descriptor of the socket
+	pub fd: RawFd,
+	/// The socket is passed by systemd socket activation
+	pub activated: bool,
+}
+
+
+/// The state of the client connection.
+#[derive(Debug, Clone)]
+pub struct ConnectionStatus {
+	/// Identifier of the connection
+	pub id: u32,
+	/// Index of the listener the connection belongs to
+	pub listener: usize,
+	/// Peer address if available
+	pub peer: Option<SocketAddr>,
+	/// The connection is opened by the server
+	pub outbound: bool,
+	/// The current state of the connection
+	pub state: State,
+	/// Time since the connection is opened
+	pub age: Duration,
+}
+
+
+/// Responses are written as lines of `key: value` pairs where the first line
+/// is either `ok` or `error: message` and an empty line ends the response.
+impl fmt::Display for Response {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Response::Done => {
+				try!(writeln!(f, "ok"));
+			},
+			Response::Status(ref status) => {
+				try!(writeln!(f, "ok"));
+				try!(writeln!(f, "stage: {}", status.stage));
+				try!(writeln!(f, "workers: {}", status.workers));
+				try!(writeln!(f, "connections: {}", status.connections));
+
+				for listener in &status.listeners {
+					try!(writeln!(f, "listener: {} {} module={} accepting={} connections={} denied={}",
+						listener.index,
+						listener.address,
+						listener.module,
+						listener.accepting,
+						listener.connections,
+						listener.denied));
+				}
+			},
+			Response::Connections(ref connections) => {
+				try!(writeln!(f, "ok"));
+
+				for connection in connections {
+					let peer = match connection.peer {
+						Some(addr) => format!("{}", addr),
+						None => "-".to_string(),
+					};
+
+					try!(writeln!(f, "connection: {} listener={} peer={} outbound={} state={} age={}",
+						connection.id,
+						connection.listener,
+						peer,
+						connection.outbound,
+						connection.state,
+						connection.age.as_secs()));
+				}
+			},
+			Response::Sockets(ref sockets) => {
+				try!(writeln!(f, "ok"));
+
+				for socket in sockets {
+					try!(writeln!(f, "socket: {} {}", socket.fd, socket.address));
+				}
+			},
+			Response::Failed(ref msg) => {
+				try!(writeln!(f, "error: {}", msg));
+			},
+		};
+
+		writeln!(f, "")
+	}
+}
+
+
+fn parse_argument<T: FromStr>(name: &str, value: &str) -> Result<T> {
+	match value.parse() {
+		Ok(value) => Ok(value),
+		Err(_) => Error::new(format!("Invalid argument '{}' of command '{}'", value, name)).result(),
+	}
+}
+
+
+#[test]
+fn test_command_from_str() {
+	assert_eq!(Command::from_str("status").unwrap(), Command::Status);
+	assert_eq!(Command::from_str(" CONNECTIONS ").unwrap(), Command::Connections);
+	assert_eq!(Command::from_str("drop 12").unwrap(), Command::Drop(12));
+	assert_eq!(Command::from_str("pause 0").unwrap(), Command::Pause(0));
+	assert_eq!(Command::from_str("resume 1").unwrap(), Command::Resume(1));
+	assert_eq!(Command::from_str("log_level debug").unwrap(), Command::LogLevel(LogLevelFilter::Debug));
+	assert_eq!(Command::from_str("shutdown").unwrap(), Command::Shutdown);
+	assert_eq!(Command::from_str("terminate").unwrap(), Command::Terminate);
+
+	assert!(Command::from_str("").is_err());
+	assert!(Command::from_str("restart").is_err());
+	assert!(Command::from_str("drop").is_err());
+	assert!(Command::from_str("drop x").is_err());
+	assert!(Command::from_str("status now").is_err());
+	assert!(Command::from_str("pause 1 2").is_err());
+}
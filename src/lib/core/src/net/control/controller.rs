@@ -0,0 +1,85 @@
+//! Handle which passes admin commands to the `Core`.
+
+use std::sync::Arc;
+use std::sync::mpsc::{self, Sender};
+use log::LogLevelFilter;
+use wrust_types::{Error, Result};
+use ::net::server::Reload;
+use super::{Command, Response};
+
+
+/// What the `Core` is asked to do.
+pub enum Control {
+	/// Execute the admin command.
+	Execute(Command),
+	/// Apply changes of listeners after the configuration is reloaded.
+	Reload(Reload),
+}
+
+/// The `Control` with the `Sender` the `Response` is sent back with.
+pub type Envelope = (Control, Sender<Response>);
+
+/// The function which changes the logging level.
+pub type LogLevelHook = Arc<Fn(LogLevelFilter) + Send + Sync>;
+
+
+/// Cloneable handle which executes commands in the `Core` event loop
+/// and waits for responses.
+#[derive(Clone)]
+pub struct Controller {
+	sender: Sender<Envelope>,
+	log_level: Option<LogLevelHook>,
+}
+
+
+impl Controller {
+	/// Create a new `Controller` which sends commands to the `Core` through `sender`.
+	pub fn new(sender: Sender<Envelope>) -> Controller {
+		Controller {
+			sender: sender,
+			log_level: None,
+		}
+	}
+
+	/// Set the function which executes `Command::LogLevel`. The logger is
+	/// initialized outside of the `Core` so without it the command fails.
+	pub fn with_log_level<F>(mut self, func: F) -> Controller
+		where F: Fn(LogLevelFilter) + Send + Sync + 'static {
+		self.log_level = Some(Arc::new(func));
+		self
+	}
+
+	/// Execute the `command` and wait for the `Response`.
+	pub fn execute(&self, command: Command) -> Result<Response> {
+		if let Command::LogLevel(level) = command {
+			return match self.log_level {
+				Some(ref func) => {
+					func(level);
+					info!("Logging level is changed to {}", level);
+					Ok(Response::Done)
+				},
+				None => Ok(Response::Failed("Logging level cannot be changed".to_string())),
+			};
+		}
+
+		self.send(Control::Execute(command))
+	}
+
+	/// Apply the `reload` in the `Core` and wait for the `Response`.
+	pub fn reload(&self, reload: Reload) -> Result<Response> {
+		self.send(Control::Reload(reload))
+	}
+
+	fn send(&self, control: Control) -> Result<Response> {
+		let (tx, rx) = mpsc::channel();
+
+		if let Err(_) = self.sender.send((control, tx)) {
+			return Error::new("Cannot send the command to the core because the core is stopped").result();
+		}
+
+		match rx.recv() {
+			Ok(response) => Ok(response),
+			Err(msg) => Error::new("Cannot receive the response of the core").because(msg).result(),
+		}
+	}
+}
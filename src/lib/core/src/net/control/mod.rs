@@ -0,0 +1,7 @@
+mod admin;
+mod command;
+mod controller;
+
+pub use self::admin::Admin;
+pub use self::command::{Command, Response, Status, ListenerStatus, SocketStatus, ConnectionStatus};
+pub use self::controller::{Controller, Control, Envelope, LogLevelHook};
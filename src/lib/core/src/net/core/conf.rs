@@ -1,32 +1,25 @@
 //! Core configuration
 
-use wrust_types::{Error, Result};
-use wrust_conf::{Conf, FromConf};
+use wrust_conf::schema::Field;
 
 
-/// Core settings
-pub struct CoreConf {
-	/// Worker count
-	pub worker_count: u16,
-}
-
+/// Default time in milliseconds connections are drained for on shutdown.
+const DEFAULT_DRAIN_TIMEOUT: u64 = 30000;
 
-impl FromConf for CoreConf {
-	// Load settings from the config
-	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		// Check if core section exists
-		if None == config.lookup(xpath) {
-			return Error::new(format!("Group does not exist at path '{}'", xpath)).result();
-		}
+/// Default maximum number of client connections.
+const DEFAULT_MAX_CONNECTIONS: usize = 1024;
 
-		// Read work queue settings
-		let worker_count = match config.lookup_integer32(&format!("{}.worker_count", xpath)) {
-			Some(count) => count as u16,
-			None => return Error::new(format!("Worker Count is required at '{}'", xpath)).result(),
-		};
 
-		Ok(CoreConf {
-			worker_count: worker_count,
-		})
+conf_schema! {
+	/// Core settings
+	pub struct CoreConf {
+		/// Worker count
+		worker_count: u16 = Field::required().range(1, 1024),
+		/// Time in milliseconds open connections can finish in on graceful shutdown
+		/// before they are closed. Zero closes them right away.
+		drain_timeout: u64 = Field::default(DEFAULT_DRAIN_TIMEOUT),
+		/// The maximum number of client connections including outbound ones.
+		/// Listeners stop accepting connections while the limit is reached.
+		max_connections: usize = Field::default(DEFAULT_MAX_CONNECTIONS).range(1, 1048576),
 	}
 }
@@ -1,31 +1,100 @@
+use std::fmt;
 use std::thread;
 use std::fs;
 use std::path::Path;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Instant;
 use wrust_io::mio;
 use wrust_io::mio::{EventSet, PollOpt};
 use wrust_types::{Error, Result};
-use wrust_types::channel::DuplexChannel;
 use wrust_types::net::Protocol;
-use ::net::{Request, CommandChannel};
+use wrust_types::net::connection::State;
+use wrust_conf::SharedConf;
+use wrust_module::stream::{Behavior, Dispatcher};
+use ::net::{Request, Timer, EventDispatcher};
+use ::net::control::{Command, Response, Status, ListenerStatus, SocketStatus, ConnectionStatus, Controller, Control, Envelope};
 use ::net::core::{CoreConf};
-use ::net::server::{ServerConf, Registry as ServerRegistry};
-use ::net::client::{Registry as ClientRegistry};
+use ::net::server::{Server, ServerConf, Reload, Change, Inherited, Registry as ServerRegistry};
+use ::net::client::{Client, ClientProtocol, Registry as ClientRegistry};
 use ::net::work::{Queue, Parcel};
 use ::module::Factory;
 
+/// The maximum size of datagram the UDP server can receive.
+const DATAGRAM_SIZE_MAX: usize = 65536;
+
+/// Time in milliseconds connections closed after the drain timeout have to finish.
+const CLOSE_TIMEOUT: u64 = 1000;
+
+/// The maximum number of servers including those which drain connections after reload.
+const SERVERS_MAX: usize = 256;
+
 #[derive(Debug, PartialEq)]
 enum Stage {
 	Init,
 	Listen,
+	Drain,
 	Shutdown,
 }
 
+impl fmt::Display for Stage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			Stage::Init => write!(f, "init"),
+			Stage::Listen => write!(f, "listen"),
+			Stage::Drain => write!(f, "drain"),
+			Stage::Shutdown => write!(f, "shutdown"),
+		}
+	}
+}
+
+/// Why the server is closed after its connections are drained.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Retirement {
+	/// Another server accepts connections on the socket
+	Replaced,
+	/// The server is removed from the configuration
+	Removed,
+}
+
+/// What accepting a connection on the listener results in.
+#[derive(Debug)]
+enum Accept {
+	/// The connection is added to the registry
+	Client(mio::Token),
+	/// The connection is closed at once
+	Denied,
+	/// No connections wait in the backlog
+	Empty,
+}
+
 pub struct Core {
 	stage: Stage,
-	channel: CommandChannel,
+	channel: Receiver<Envelope>,
 	servers: ServerRegistry,
 	clients: ClientRegistry,
 	queue: Queue,
+	workers: usize,
+	/// Listeners which do not accept connections by indices
+	paused: Vec<bool>,
+	/// Listeners which do not accept connections because of connection limits by indices
+	throttled: Vec<bool>,
+	/// Open connections accepted by listeners by indices
+	connections: Vec<usize>,
+	drain_timeout: u64,
+	/// Connections which are left after the drain timeout are being closed
+	drain_expired: bool,
+	/// Servers in the order of the current configuration
+	active: Vec<mio::Token>,
+	/// Servers which are closed when their connections are drained by indices
+	retired: Vec<Option<Retirement>>,
+	/// Drain timeouts of retired servers by indices
+	retire_timers: Vec<Option<mio::Timeout>>,
+	/// Sockets of servers are passed to the new process
+	handed_off: bool,
+	/// The current configuration modules read through dispatchers
+	config: SharedConf,
 }
 
 
@@ -35,24 +104,44 @@ impl Core {
 		&mut self.clients
 	}
 
-	pub fn start(conf: CoreConf, module_factory: &Factory, servers: Vec<ServerConf>) -> Result<CommandChannel> {
+	/// Start the event loop thread which serves `servers`. Servers take over `inherited` sockets
+	/// which listen on the same addresses. The `Controller` returned executes admin commands
+	/// in the loop and the thread finishes after `Command::Shutdown`.
+	pub fn start(conf: CoreConf, config: SharedConf, module_factory: &Factory, servers: Vec<ServerConf>, inherited: &mut Inherited) -> Result<(Controller, thread::JoinHandle<()>)> {
 		// Create TCP listeners from the configuration
-		let mut server_reg = ServerRegistry::new(0);
+		let mut server_reg = ServerRegistry::new(0, SERVERS_MAX);
+		let mut active = Vec::new();
 
 		for config in &servers {
-			try!(server_reg.add(module_factory, config));
+			active.push(try!(server_reg.add(module_factory, config, inherited)));
 		}
 
-		// Create duplex channel to communicate with the server
-		let (request_channel, response_channel) = DuplexChannel::new().split();
+		// Create the channel admin commands are sent through
+		let (command_sender, command_receiver) = mpsc::channel();
 
-		let slab = ClientRegistry::new(server_reg.len(), 1024);
+		let slab = ClientRegistry::new(server_reg.capacity(), conf.max_connections);
+		let paused = vec![false; server_reg.capacity()];
+		let throttled = vec![false; server_reg.capacity()];
+		let connections = vec![0; server_reg.capacity()];
+		let retired = vec![None; server_reg.capacity()];
+		let retire_timers = (0..server_reg.capacity()).map(|_| None).collect();
 		let mut instance = Core {
 			stage: Stage::Init,
-			channel: response_channel,
+			channel: command_receiver,
 			servers: server_reg,
 			clients: slab,
 			queue: Queue::new(conf.worker_count as usize),
+			workers: conf.worker_count as usize,
+			paused: paused,
+			throttled: throttled,
+			connections: connections,
+			drain_timeout: conf.drain_timeout,
+			drain_expired: false,
+			active: active,
+			retired: retired,
+			retire_timers: retire_timers,
+			handed_off: false,
+			config: config,
 		};
 
 		// Create and initialize event loop
@@ -64,41 +153,26 @@ impl Core {
 
 		// .. register servers
 		let err = instance.servers.each(|ref serv| -> Option<Error> {
-			match *serv.socket() {
-				Protocol::Tcp(ref listener) => {
-					match event_loop.register(listener, *serv.token(), EventSet::all(), PollOpt::edge()) {
-						Ok(_) => {
-							if let Protocol::Tcp(ref details) = serv.config().listen.protocol {
-								info!("Listen on {}:{} using TCP", details.address, details.port);
-							}
-
-							None
-						},
-						Err(msg) => Some(Error::new("TCP listener registration failed").because(msg))
-					}
-				},
-				Protocol::Unix(ref listener) => {
-					match event_loop.register(listener, *serv.token(), EventSet::all(), PollOpt::edge()) {
-						Ok(_) => {
-							if let Protocol::Unix(ref details) = serv.config().listen.protocol {
-								info!("Listen on {} using UNIX", details.path);
-							}
-
-							None
-						},
-						Err(msg) => Some(Error::new("UNIX listener registration failed because {}").because(msg))
-					}
-				},
-				Protocol::Udp(_) => Some(Error::new("UDP is not supported"))
+			if let Err(msg) = listen(&mut event_loop, serv) {
+				return Some(msg);
 			}
+
+			None
 		});
 
 		if let Some(msg) = err {
 			return Err(msg);
 		}
 
+		// .. let stream processing modules act on connections through the loop
+		let config = instance.config.clone();
+		instance.servers.each(|ref serv| -> Option<Error> {
+			attach(&mut event_loop, serv, &config);
+			None
+		});
+
 		// .. run the loop
-		thread::spawn(move || {
+		let thread = thread::spawn(move || {
 			instance.stage = Stage::Listen;
 
 			let mut success = true;
@@ -117,16 +191,798 @@ impl Core {
 		});
 
 		// Server are ready and running
-		Ok(request_channel)
+		Ok((Controller::new(command_sender), thread))
+	}
+
+	fn receive(&mut self, token: mio::Token) {
+		let server = self.servers[token].clone();
+
+		// The socket is registered edge-triggered so it must be drained completely
+		loop {
+			let mut buf = vec![0u8; DATAGRAM_SIZE_MAX];
+			let received = match *server.socket() {
+				Protocol::Udp(ref socket) => socket.recv_from(&mut buf),
+				_ => return,
+			};
+
+			match received {
+				Ok(Some((count, peer))) => {
+					buf.truncate(count);
+
+					// Push Datagram event in the queue
+					self.queue.push(Parcel::Datagram {
+						server: server.clone(),
+						peer: peer,
+						data: buf,
+					});
+				},
+				Ok(None) => break,
+				Err(msg) => {
+					error!("Cannot receive datagram on {:?} because {}", *server, msg);
+					break;
+				}
+			};
+		}
+	}
+
+	/// Register the client connection for `events` and mark it as waiting for them.
+	/// The state the client connection was asked to continue in while it was busy
+	/// overrides `events`.
+	fn wait(&mut self, event_loop: &mut mio::EventLoop<Core>, client_token: mio::Token, events: mio::EventSet, registered: bool) {
+		let client = match self.clients.get(client_token) {
+			Some(client) => client.clone(),
+			None => return,
+		};
+
+		client.set_busy(false);
+
+		// Connections which are not established yet keep the wake request until they are
+		let state = client.state();
+		let established = state != State::Connecting && state != State::Handshaking;
+
+		// The stream processing module timer which expired while the connection was busy
+		// goes first and wake requests wait for the next time
+		if established && client.take_due() {
+			if let Err(msg) = register(event_loop, &client, EventSet::none(), registered) {
+				error!("{}", msg);
+			}
+
+			self.timeout(&client);
+			return;
+		}
+
+		let wake = if established {
+			client.take_wake()
+		}
+		else {
+			None
+		};
+
+		let events = match wake {
+			Some(State::Closed) => {
+				if registered {
+					let _ = deregister(event_loop, &client);
+				}
+
+				self.close(&client);
+				return;
+			},
+			Some(State::Writing) => {
+				client.set_state(State::Writing);
+				EventSet::writable()
+			},
+			_ => events,
+		};
+
+		if let Err(msg) = register(event_loop, &client, events, registered) {
+			error!("{}", msg);
+		}
+
+		client.set_waiting();
+		self.schedule(event_loop, &client);
+	}
+
+	/// Set the timer which expires the client connection at the nearest deadline
+	/// of timeouts configured for the server.
+	fn schedule(&mut self, event_loop: &mut mio::EventLoop<Core>, client: &Arc<Client>) {
+		if let Some(timeout) = client.replace_timer(None) {
+			event_loop.clear_timeout(timeout);
+		}
+
+		let deadline = client
+			.deadlines(&self.servers[*client.server_token()].config().timeout)
+			.into_iter()
+			.map(|(_, deadline)| deadline)
+			.min();
+
+		if let Some(deadline) = deadline {
+			let timer = Timer::Expire {
+				client_token: *client.token(),
+				serial: client.serial(),
+			};
+
+			match event_loop.timeout_ms(timer, milliseconds_until(deadline)) {
+				Ok(timeout) => {
+					client.replace_timer(Some(timeout));
+				},
+				Err(msg) => error!("Cannot set the timeout of {:?} because {:?}", client, msg),
+			};
+		}
+	}
+
+	/// Wake the client connection waiting for I/O events so it continues in the `state` given.
+	fn wake(&mut self, event_loop: &mut mio::EventLoop<Core>, client_token: mio::Token, serial: Option<usize>, state: State) {
+		let client = match self.clients.get(client_token) {
+			Some(client) => client.clone(),
+			None => return,
+		};
+
+		// The token can be reused by another connection already
+		if let Some(serial) = serial {
+			if client.serial() != serial {
+				return;
+			}
+		}
+
+		// The connection processed by a worker is woken when it returns to the loop
+		if client.is_busy() {
+			client.set_wake(state);
+			return;
+		}
+
+		let current = client.state();
+		match state {
+			State::Closed => {
+				let _ = deregister(event_loop, &client);
+				self.close(&client);
+			},
+			State::Writing if current == State::Connecting || current == State::Handshaking => {
+				client.set_wake(state);
+			},
+			State::Writing => {
+				client.set_state(State::Writing);
+
+				if let Err(msg) = register(event_loop, &client, EventSet::writable(), true) {
+					error!("{}", msg);
+				}
+
+				client.set_waiting();
+				self.schedule(event_loop, &client);
+			},
+			_ => (),
+		};
+	}
+
+	/// Add the outbound connection to the registry and wait until it is established.
+	fn connect(&mut self, event_loop: &mut mio::EventLoop<Core>, server_token: mio::Token, owner: Option<(mio::Token, usize)>, socket: ClientProtocol, addr: Option<SocketAddr>, timeout: u64) {
+		// The owner must be alive and belong to the same server
+		if let Some((owner_token, owner_serial)) = owner {
+			match self.clients.get(owner_token) {
+				Some(client) if client.serial() != owner_serial => {
+					debug!("Outbound connection is dropped because the owner {:?} is closed", owner_token);
+					return;
+				},
+				Some(client) if *client.server_token() == server_token => (),
+				Some(_) => {
+					error!("Outbound connection is dropped because the owner {:?} belongs to another server", owner_token);
+					return;
+				},
+				None => {
+					debug!("Outbound connection is dropped because the owner {:?} is closed", owner_token);
+					return;
+				},
+			};
+		}
+
+		let client_token = match self.clients.add_outbound(server_token, owner, socket, addr) {
+			Ok(client_token) => client_token,
+			Err(msg) => {
+				error!("{}", msg);
+				return;
+			},
+		};
+
+		let client = self.clients[client_token].clone();
+
+		// The outbound connection is aborted if the owner is closed while it is connecting
+		if let Some((owner_token, _)) = owner {
+			self.clients[owner_token].add_outbound(client_token);
+		}
+
+		// Outbound connections take room of accepted ones
+		self.throttle(event_loop);
+
+		if let Err(msg) = register(event_loop, &client, State::Connecting.as_event_set(), false) {
+			self.abort(&client, format!("{}", msg));
+			return;
+		}
+
+		if timeout > 0 {
+			let timer = Timer::Connect {
+				client_token: client_token,
+				serial: client.serial(),
+			};
+
+			if let Err(msg) = event_loop.timeout_ms(timer, timeout) {
+				error!("Cannot set the connect timeout of {:?} because {:?}", client, msg);
+			}
+		}
+	}
+
+	/// Abort `outbound` connections the closed connection `owner_token` with the `serial` number
+	/// opened and which are still connecting. Connections processed by workers are left
+	/// to the stream processing module.
+	fn abort_outbound(&mut self, event_loop: &mut mio::EventLoop<Core>, owner_token: mio::Token, serial: usize, outbound: Vec<mio::Token>) {
+		for client_token in outbound {
+			let client = match self.clients.get(client_token) {
+				Some(client) => client.clone(),
+				None => continue,
+			};
+
+			// The token can be reused by another connection already
+			if client.owner() != Some((owner_token, serial)) || client.is_busy() || client.state() != State::Connecting {
+				continue;
+			}
+
+			debug!("{:?} is aborted because the owner {:?} is closed", client, owner_token);
+			let _ = deregister(event_loop, &client);
+			self.abort(&client, "The owner connection is closed".to_string());
+		}
+	}
+
+	/// Test if the connection which opened the outbound `client` connection is still open.
+	fn is_owner_alive(&self, client: &Arc<Client>) -> bool {
+		match client.owner() {
+			Some((owner_token, owner_serial)) => match self.clients.get(owner_token) {
+				Some(owner) => owner.serial() == owner_serial,
+				None => false,
+			},
+			None => true,
+		}
+	}
+
+	/// Pass the client connection to a worker to call the stream processing module timer.
+	fn timeout(&mut self, client: &Arc<Client>) {
+		client.set_busy(true);
+
+		self.queue.push(Parcel::Timeout {
+			server: self.servers[*client.server_token()].clone(),
+			client: client.clone(),
+		});
+	}
+
+	/// Pass the client connection to a worker to close it.
+	fn close(&mut self, client: &Arc<Client>) {
+		client.set_busy(true);
+
+		self.queue.push(Parcel::Close {
+			server: self.servers[*client.server_token()].clone(),
+			client: client.clone(),
+		});
+	}
+
+	/// Pass the outbound connection which failed to a worker to drop it.
+	fn abort(&mut self, client: &Arc<Client>, reason: String) {
+		client.set_busy(true);
+
+		self.queue.push(Parcel::Abort {
+			server: self.servers[*client.server_token()].clone(),
+			client: client.clone(),
+			reason: reason,
+		});
+	}
+
+	/// Execute the admin `command` and produce the response.
+	fn execute(&mut self, event_loop: &mut mio::EventLoop<Core>, command: Command) -> Response {
+		match command {
+			Command::Status => {
+				let mut listeners = Vec::new();
+				self.servers.each(|ref serv| -> Option<()> {
+					let index = serv.token().as_usize();
+
+					listeners.push(ListenerStatus {
+						index: index,
+						address: format!("{}", serv.config().listen),
+						module: serv.config().forward.module.clone(),
+						accepting: !self.paused[index] && !self.throttled[index],
+						connections: self.connections[index],
+						denied: serv.limiter().denied(),
+					});
+
+					None
+				});
+
+				Response::Status(Status {
+					stage: format!("{}", self.stage),
+					workers: self.workers,
+					connections: self.clients.len(),
+					listeners: listeners,
+				})
+			},
+			Command::Connections => {
+				let mut connections = Vec::new();
+				self.clients.each(|client| -> Option<()> {
+					let desc = client.descriptor();
+
+					connections.push(ConnectionStatus {
+						id: desc.id(),
+						listener: client.server_token().as_usize(),
+						peer: desc.addr(),
+						outbound: desc.is_outbound(),
+						state: client.state(),
+						age: client.age(),
+					});
+
+					None
+				});
+
+				Response::Connections(connections)
+			},
+			Command::Drop(id) => {
+				// Tokens below the client registry range belong to servers
+				let client_token = mio::Token(id as usize);
+				if client_token.as_usize() < self.servers.capacity() || self.clients.get(client_token).is_none() {
+					return Response::Failed(format!("Connection #{} does not exist", id));
+				}
+
+				info!("Connection #{} is dropped by the admin", id);
+				self.wake(event_loop, client_token, None, State::Closed);
+				Response::Done
+			},
+			Command::Pause(index) | Command::Resume(index) => {
+				if index >= self.servers.capacity() || self.servers.get(mio::Token(index)).is_none() {
+					return Response::Failed(format!("Listener #{} does not exist", index));
+				}
+
+				if self.stage != Stage::Listen {
+					return Response::Failed("The server is shutting down".to_string());
+				}
+
+				if self.retired[index].is_some() {
+					return Response::Failed(format!("Listener #{} is closing", index));
+				}
+
+				let pause = command == Command::Pause(index);
+				if self.paused[index] == pause {
+					return Response::Done;
+				}
+
+				// The throttled listener is registered when connections close
+				let server = self.servers[index].clone();
+				let result = if self.throttled[index] {
+					Ok(())
+				}
+				else if pause {
+					unlisten(event_loop, &server)
+				}
+				else {
+					listen(event_loop, &server)
+				};
+
+				match result {
+					Ok(_) => {
+						self.paused[index] = pause;
+						info!("{:?} is {} by the admin", *server, if pause { "paused" } else { "resumed" });
+						Response::Done
+					},
+					Err(msg) => Response::Failed(format!("{}", msg)),
+				}
+			},
+			Command::Handoff => {
+				let sockets = self.active
+					.iter()
+					.map(|token| {
+						let server = &self.servers[*token];
+
+						SocketStatus {
+							address: format!("{}", server.config().listen),
+							fd: server.raw_fd(),
+							activated: server.is_activated(),
+						}
+					})
+					.collect();
+
+				self.handed_off = true;
+				Response::Sockets(sockets)
+			},
+			Command::Reclaim => {
+				self.handed_off = false;
+				Response::Done
+			},
+			Command::LogLevel(_) => Response::Failed("Logging level cannot be changed by the core".to_string()),
+			Command::Shutdown => {
+				info!("Received SHUTDOWN command");
+				if self.stage == Stage::Listen {
+					self.drain(event_loop);
+				}
+
+				Response::Done
+			},
+			Command::Terminate => {
+				info!("Received TERMINATE command");
+				self.terminate(event_loop, true);
+				Response::Done
+			},
+		}
+	}
+
+	/// Stop accepting connections and let open connections finish until the drain timeout expires.
+	fn drain(&mut self, event_loop: &mut mio::EventLoop<Core>) {
+		self.stage = Stage::Drain;
+
+		for token in self.servers.tokens() {
+			let index = token.as_usize();
+			if !self.paused[index] {
+				if !self.throttled[index] {
+					let server = self.servers[index].clone();
+					if let Err(msg) = unlisten(event_loop, &server) {
+						error!("{}", msg);
+					}
+				}
+
+				self.paused[index] = true;
+			}
+		}
+
+		info!("Draining {} connections for up to {} ms", self.clients.len(), self.drain_timeout);
+
+		if self.drain_timeout > 0 {
+			if let Err(msg) = event_loop.timeout_ms(Timer::Drain, self.drain_timeout) {
+				error!("Cannot set the drain timeout because {:?}", msg);
+			}
+		}
+		else {
+			self.expire_drain(event_loop);
+		}
+
+		self.finish_drain(event_loop);
+	}
+
+	/// Close connections which are left after the drain timeout so modules see their sessions closed.
+	/// Connections which do not close in time are dropped.
+	fn expire_drain(&mut self, event_loop: &mut mio::EventLoop<Core>) {
+		if self.drain_expired {
+			warn!("{} connections are dropped because they are not closed in time", self.clients.len());
+			self.terminate(event_loop, true);
+			return;
+		}
+
+		self.drain_expired = true;
+
+		let mut clients = Vec::new();
+		self.clients.each(|client| -> Option<()> {
+			clients.push(*client.token());
+			None
+		});
+
+		if !clients.is_empty() {
+			info!("Closing {} connections left after the drain timeout", clients.len());
+		}
+
+		for client_token in clients {
+			self.wake(event_loop, client_token, None, State::Closed);
+		}
+
+		if let Err(msg) = event_loop.timeout_ms(Timer::Drain, CLOSE_TIMEOUT) {
+			error!("Cannot set the close timeout because {:?}", msg);
+		}
+	}
+
+	/// Shutdown the server when the last connection is closed while draining.
+	fn finish_drain(&mut self, event_loop: &mut mio::EventLoop<Core>) {
+		if self.stage == Stage::Drain && self.clients.len() == 0 {
+			info!("All connections are drained");
+			self.terminate(event_loop, false);
+		}
+	}
+
+	/// Apply changes of listeners after the configuration is reloaded. Listeners which are replaced
+	/// or removed stop accepting connections and close when their connections are drained.
+	fn reload(&mut self, event_loop: &mut mio::EventLoop<Core>, reload: Reload) -> Response {
+		if self.stage != Stage::Listen {
+			reload.discard();
+			return Response::Failed("The server is shutting down".to_string());
+		}
+
+		// Check everything which can fail before the first change is applied
+		let required = reload.changes().iter().filter(|change| match **change {
+			Change::Keep(_) => false,
+			_ => true,
+		}).count();
+
+		if required > self.servers.available() {
+			reload.discard();
+			return Response::Failed(format!("{} listeners cannot be added while {} listeners are in use", required, self.servers.len()));
+		}
+
+		let sockets: Result<Vec<_>> = reload.changes().iter().map(|change| match *change {
+			Change::Keep(index) | Change::Replace(index, _) if index >= self.active.len() => {
+				Error::new(format!("Listener #{} of the previous configuration does not exist", index)).result()
+			},
+			Change::Replace(index, _) => {
+				let server = &self.servers[self.active[index]];
+				server.clone_socket().map(|socket| Some((socket, server.is_activated())))
+			},
+			_ => Ok(None),
+		}).collect();
+
+		let sockets = match sockets {
+			Ok(sockets) => sockets,
+			Err(msg) => {
+				reload.discard();
+				return Response::Failed(format!("{}", msg));
+			},
+		};
+
+		let changes: Vec<Change> = reload
+			.into_changes()
+			.into_iter()
+			.zip(sockets.into_iter())
+			.map(|(change, socket)| match (change, socket) {
+				(Change::Replace(index, listener), Some((socket, activated))) => {
+					// Connections of the replaced listener which are still open count
+					// against limits of client addresses of the replacement
+					let limiter = self.servers[self.active[index]].limiter().with_conf(&listener.config().limits);
+					Change::Replace(index, listener.with_socket(socket).with_activated(activated).with_limiter(limiter))
+				},
+				(change, _) => change,
+			})
+			.collect();
+
+		// Apply changes
+		let previous = self.active.clone();
+		let mut matched = vec![false; previous.len()];
+		let mut active = Vec::new();
+		let mut failures = Vec::new();
+
+		for change in changes {
+			let (listener, replaced) = match change {
+				Change::Keep(index) => {
+					matched[index] = true;
+					active.push(previous[index]);
+					continue;
+				},
+				Change::Replace(index, listener) => {
+					matched[index] = true;
+					(listener, Some(previous[index]))
+				},
+				Change::Bind(listener) => (listener, None),
+			};
+
+			let token = match self.servers.insert(listener) {
+				Ok(token) => token,
+				Err(msg) => {
+					error!("{}", msg);
+					failures.push(format!("{}", msg));
+
+					// The listener which is not replaced keeps working with previous settings
+					if let Some(replaced) = replaced {
+						active.push(replaced);
+					}
+
+					continue;
+				},
+			};
+
+			let server = self.servers[token].clone();
+			attach(event_loop, &server, &self.config);
+
+			// The replacement of the paused listener stays paused
+			let paused = match replaced {
+				Some(replaced) => self.paused[replaced.as_usize()],
+				None => false,
+			};
+
+			if let Some(replaced) = replaced {
+				info!("{:?} replaces {:?} with new settings", *server, replaced);
+				self.retire(event_loop, replaced, Retirement::Replaced);
+			}
+
+			if paused {
+				self.paused[token.as_usize()] = true;
+			}
+			else if let Err(msg) = listen(event_loop, &server) {
+				error!("{}", msg);
+				self.paused[token.as_usize()] = true;
+			}
+
+			active.push(token);
+		}
+
+		for (index, server_token) in previous.into_iter().enumerate() {
+			if !matched[index] {
+				self.retire(event_loop, server_token, Retirement::Removed);
+			}
+		}
+
+		self.active = active;
+
+		// New listeners can start at connection limits
+		self.throttle(event_loop);
+
+		if !failures.is_empty() {
+			return Response::Failed(format!("Configuration is reloaded partially because {}", failures.join("; ")));
+		}
+
+		info!("Configuration is reloaded");
+
+		Response::Done
+	}
+
+	/// Stop accepting connections on the server and close it when its connections are drained.
+	fn retire(&mut self, event_loop: &mut mio::EventLoop<Core>, server_token: mio::Token, retirement: Retirement) {
+		let index = server_token.as_usize();
+
+		if !self.paused[index] {
+			if !self.throttled[index] {
+				let server = self.servers[index].clone();
+				if let Err(msg) = unlisten(event_loop, &server) {
+					error!("{}", msg);
+				}
+			}
+
+			self.paused[index] = true;
+		}
+
+		self.retired[index] = Some(retirement);
+
+		if self.drain_timeout > 0 {
+			match event_loop.timeout_ms(Timer::Retire { server_token: server_token }, self.drain_timeout) {
+				Ok(timeout) => self.retire_timers[index] = Some(timeout),
+				Err(msg) => error!("Cannot set the drain timeout of {:?} because {:?}", server_token, msg),
+			};
+		}
+		else {
+			self.close_server(event_loop, server_token);
+		}
+
+		self.release(event_loop, server_token);
+	}
+
+	/// Close connections of the server.
+	fn close_server(&mut self, event_loop: &mut mio::EventLoop<Core>, server_token: mio::Token) {
+		let mut clients = Vec::new();
+		self.clients.each(|client| -> Option<()> {
+			if *client.server_token() == server_token {
+				clients.push(*client.token());
+			}
+
+			None
+		});
+
+		for client_token in clients {
+			self.wake(event_loop, client_token, None, State::Closed);
+		}
+	}
+
+	/// Remove the retired server from the registry if it has no connections left.
+	fn release(&mut self, event_loop: &mut mio::EventLoop<Core>, server_token: mio::Token) {
+		let index = server_token.as_usize();
+
+		let retirement = match self.retired[index] {
+			Some(retirement) => retirement,
+			None => return,
+		};
+
+		let busy = self.clients.each(|client| -> Option<()> {
+			if *client.server_token() == server_token {
+				Some(())
+			}
+			else {
+				None
+			}
+		});
+
+		if busy.is_some() {
+			return;
+		}
+
+		// The slot can be taken by another server which is retired later
+		// so the drain timeout must not fire for it
+		if let Some(timeout) = self.retire_timers[index].take() {
+			event_loop.clear_timeout(timeout);
+		}
+
+		self.retired[index] = None;
+		self.paused[index] = false;
+		self.throttled[index] = false;
+
+		if let Some(server) = self.servers.remove(server_token) {
+			// The socket file of the replaced server is used by its replacement
+			// and socket files passed by systemd belong to systemd
+			if retirement == Retirement::Removed && !server.is_activated() {
+				if let Protocol::Unix(ref details) = server.config().listen.protocol {
+					let _ = fs::remove_file(Path::new(&details.path));
+				}
+			}
+
+			info!("{:?} is closed", *server);
+		}
+	}
+
+	/// Test if the listener reached the limit of connections configured for it
+	/// or there is no room for connections at all.
+	fn is_limited(&self, index: usize) -> bool {
+		if self.clients.is_full() {
+			return true;
+		}
+
+		match self.servers[index].config().max_connections {
+			Some(max_connections) => self.connections[index] >= max_connections,
+			None => false,
+		}
+	}
+
+	/// Stop accepting connections on listeners which reached connection limits and
+	/// accept them again on listeners which have room. Connections wait in the backlog
+	/// meanwhile.
+	fn throttle(&mut self, event_loop: &mut mio::EventLoop<Core>) {
+		if self.stage != Stage::Listen {
+			return;
+		}
+
+		for index in 0..self.throttled.len() {
+			let server = match self.servers.get(mio::Token(index)) {
+				Some(server) if !server.socket().is_udp() => server.clone(),
+				_ => continue,
+			};
+
+			let limited = self.is_limited(index);
+			if limited == self.throttled[index] {
+				continue;
+			}
+
+			self.throttled[index] = limited;
+
+			// The paused listener is registered when it is resumed
+			if self.paused[index] {
+				continue;
+			}
+
+			if limited {
+				match unlisten(event_loop, &server) {
+					Ok(_) => info!("{:?} stops accepting connections because the connection limit is reached", *server),
+					Err(msg) => error!("{}", msg),
+				};
+			}
+			else {
+				match register_server(event_loop, &server) {
+					Ok(_) => info!("{:?} accepts connections again", *server),
+					Err(msg) => {
+						error!("{}", msg);
+						self.paused[index] = true;
+					},
+				};
+			}
+		}
+	}
+
+	/// Stop workers and the event loop. If `fast` is `true` parcels which are not processed yet are dropped.
+	fn terminate(&mut self, event_loop: &mut mio::EventLoop<Core>, fast: bool) {
+		if self.stage == Stage::Shutdown {
+			return;
+		}
+
+		self.stage = Stage::Shutdown;
+		self.queue.shutdown(fast);
+		event_loop.shutdown();
+		self.cleanup();
 	}
 
 	fn cleanup(&mut self) {
+		// Socket files are used by the process the sockets are passed to
+		if self.handed_off {
+			return;
+		}
+
 		// Clean resources
 		self.servers.each(|ref serv| -> Option<Error> {
-			// Remove unix socket files
+			// Remove unix socket files except ones systemd owns
 			if let Protocol::Unix(ref details) = serv.config().listen.protocol {
-				let path = Path::new(&details.path);
-				let _ = fs::remove_file(&path);
+				if !serv.is_activated() {
+					let path = Path::new(&details.path);
+					let _ = fs::remove_file(&path);
+				}
 			}
 
 			None
@@ -136,90 +992,267 @@ impl Core {
 
 
 impl mio::Handler for Core {
-	type Timeout = ();
+	type Timeout = Timer;
 	type Message = Request;
 
 	fn ready(&mut self, event_loop: &mut mio::EventLoop<Self>, token: mio::Token, events: mio::EventSet) {
 		let index = token.as_usize();
 
 		// Server socket has a connection request
-		if index < self.servers.len() {
+		if index < self.servers.capacity() {
 			// Do not accept new connections unless the listener is on the listen stage
-			if self.stage != Stage::Listen {
+			if self.stage != Stage::Listen || self.paused[index] || self.throttled[index] || self.servers.get(token).is_none() {
+				return;
+			}
+
+			// Datagram sockets have no connections to accept so all datagrams
+			// available are received and pushed in the queue as they are
+			if self.servers[index].socket().is_udp() {
+				if events.is_readable() {
+					self.receive(token);
+				}
+
 				return;
 			}
 
 			if events.is_readable() || events.is_writable() {
-				// Accept connection
-				let client_token: Result<Option<mio::Token>> = self.servers.then_with(index, &mut self.clients, |serv, clients| {
-					match *serv.socket() {
-						Protocol::Tcp(ref sock) => {
-							// Accept TCP the client connection
-							match accept(sock, event_loop) {
-								Ok(Some(client_socket)) => match clients.add(token, Protocol::Tcp(client_socket)) {
-									Ok(client_token) => Ok(Some(client_token)),
-									Err(msg) => Err(msg)
-								},
-								Ok(None) => Ok(None),
-								_ => Error::new("Cannot accept TCP client connection").result()
-							}
+				// The listener is edge-triggered so connections are accepted until the backlog
+				// is empty. Connections wait in the backlog until there is room for them.
+				loop {
+					if self.is_limited(index) {
+						self.throttle(event_loop);
+						return;
+					}
+
+					// Accept connection
+					let accepted: Result<Accept> = self.servers.then_with(index, &mut self.clients, |serv, clients| {
+						match *serv.socket() {
+							Protocol::Tcp(ref sock) => {
+								// Accept TCP the client connection
+								match accept(sock, event_loop) {
+									Ok(Some(client_socket)) => {
+										// The connection is closed before anything knows about it
+										// if the client address is blocked or exceeds its limits
+										let ip = match client_socket.peer_addr() {
+											Ok(addr) => addr.ip(),
+											Err(_) => return Ok(Accept::Denied),
+										};
+
+										if !serv.config().access.allows(&ip) {
+											info!("{:?} denies the connection from {} by access lists", **serv, ip);
+											return Ok(Accept::Denied);
+										}
+
+										if let Err(msg) = serv.limiter().open(ip) {
+											info!("{:?} denies the connection because {}", **serv, msg);
+											return Ok(Accept::Denied);
+										}
+
+										match clients.add(token, Protocol::Tcp(client_socket), serv.tls_session()) {
+											Ok(client_token) => Ok(Accept::Client(client_token)),
+											Err(msg) => {
+												serv.limiter().close(ip);
+												Err(msg)
+											}
+										}
+									},
+									Ok(None) => Ok(Accept::Empty),
+									_ => Error::new("Cannot accept TCP client connection").result()
+								}
+							},
+							Protocol::Unix(ref sock) => {
+								// Accept UNIX the client connection
+								match accept(sock, event_loop) {
+									Ok(Some(client_socket)) => match clients.add(token, Protocol::Unix(client_socket), None) {
+										Ok(client_token) => Ok(Accept::Client(client_token)),
+										Err(msg) => Err(msg)
+									},
+									Ok(None) => Ok(Accept::Empty),
+									_ => Error::new("Cannot accept UNIX client connection").result()
+								}
+							},
+							Protocol::Udp(_) => Error::new("UDP server does not accept connections").result()
+						}});
+
+					match accepted {
+						Ok(Accept::Client(client_token)) => {
+							self.connections[index] += 1;
+							self.clients[client_token].set_busy(true);
+
+							// Push Open event in the queue
+							self.queue.push(Parcel::Open {
+								server: self.servers[token].clone(),
+								client: self.clients[client_token].clone(),
+							});
+
+							self.throttle(event_loop);
 						},
-						Protocol::Unix(ref sock) => {
-							// Accept UNIX the client connection
-							match accept(sock, event_loop) {
-								Ok(Some(client_socket)) => match clients.add(token, Protocol::Unix(client_socket)) {
-									Ok(client_token) => Ok(Some(client_token)),
-									Err(msg) => Err(msg)
-								},
-								Ok(None) => Ok(None),
-								_ => Error::new("Cannot accept UNIX client connection").result()
-							}
+						Err(err) => {
+							error!("{}", err);
+							return;
 						},
-						Protocol::Udp(_) => Error::new("UDP is not supported").result()
-					}});
-
-				match client_token {
-					Ok(Some(client_token)) => {
-						// Push Open event in the queue
-						self.queue.push(Parcel::Open {
-							server: self.servers[token].clone(),
-							client: self.clients[client_token].clone(),
-						});
-					},
-					Err(err) => {
-						error!("{}", err);
-						return;
-					},
-					_ => ()
-				};
+						// Connections queued behind the denied one are accepted further
+						Ok(Accept::Denied) => (),
+						Ok(Accept::Empty) => return,
+					};
+				}
 			}
 		}
 		else {
-			// Push Ready event in the queue
-			self.queue.push(Parcel::Ready {
-				server: self.servers[*self.clients[token].server_token()].clone(),
-				client: self.clients[token].clone(),
-				events: events,
-			});
+			let client = match self.clients.get(token) {
+				Some(client) => client.clone(),
+				None => return,
+			};
+
+			// Events of the connection passed to a worker already are dropped
+			if client.is_busy() {
+				return;
+			}
+
+			// The outbound connection is not delivered to the stream processing module
+			// when its owner is closed and the token can be reused by another connection
+			if client.state() == State::Connecting && !self.is_owner_alive(&client) {
+				let _ = deregister(event_loop, &client);
+				self.abort(&client, "The owner connection is closed".to_string());
+				return;
+			}
+
+			client.set_busy(true);
+			client.touch();
+
+			let server = self.servers[*client.server_token()].clone();
+
+			if client.state() == State::Connecting {
+				// Push Connect event in the queue
+				self.queue.push(Parcel::Connect {
+					server: server,
+					client: client,
+				});
+			}
+			else {
+				// Push Ready event in the queue
+				self.queue.push(Parcel::Ready {
+					server: server,
+					client: client,
+					events: events,
+				});
+			}
 		}
 	}
 
+	fn timeout(&mut self, event_loop: &mut mio::EventLoop<Self>, timer: Self::Timeout) {
+		match timer {
+			Timer::Connect { client_token, serial } => {
+				let client = match self.clients.get(client_token) {
+					Some(client) => client.clone(),
+					None => return,
+				};
+
+				// The token can be reused by another connection already
+				if client.serial() != serial || client.is_busy() || client.state() != State::Connecting {
+					return;
+				}
+
+				debug!("Timer::Connect {:?}", client_token);
+				let _ = deregister(event_loop, &client);
+				self.abort(&client, "The connection is not established in time".to_string());
+			},
+			Timer::Delay { client_token, serial, events } => {
+				match self.clients.get(client_token) {
+					Some(client) if client.serial() == serial => (),
+					_ => return,
+				};
+
+				self.wait(event_loop, client_token, events, true);
+			},
+			Timer::Module { client_token, serial } => {
+				let client = match self.clients.get(client_token) {
+					Some(client) => client.clone(),
+					None => return,
+				};
+
+				if client.serial() != serial {
+					return;
+				}
+
+				// The connection processed by a worker handles the timer when it returns to the loop
+				if client.is_busy() {
+					client.set_due();
+					return;
+				}
+
+				let state = client.state();
+				if state == State::Connecting || state == State::Handshaking {
+					debug!("Timer::Module of {:?} is dropped because the connection is not established", client_token);
+					return;
+				}
+
+				// The connection stops waiting for I/O events while the module handles the timer
+				if let Err(msg) = register(event_loop, &client, EventSet::none(), true) {
+					error!("{}", msg);
+				}
+
+				self.timeout(&client);
+			},
+			Timer::Expire { client_token, serial } => {
+				let client = match self.clients.get(client_token) {
+					Some(client) => client.clone(),
+					None => return,
+				};
+
+				// The connection processed by a worker sets the timer again when it returns to the loop
+				if client.serial() != serial || client.is_busy() {
+					return;
+				}
+
+				client.replace_timer(None);
+
+				let now = Instant::now();
+				let expired = client
+					.deadlines(&self.servers[*client.server_token()].config().timeout)
+					.into_iter()
+					.find(|&(_, deadline)| deadline <= now);
+
+				match expired {
+					Some((name, _)) => {
+						info!("{:?} is closed because the {} timeout expired", client, name);
+						let _ = deregister(event_loop, &client);
+						self.close(&client);
+					},
+					None => self.schedule(event_loop, &client),
+				};
+			},
+			Timer::Drain => {
+				if self.stage == Stage::Drain {
+					self.expire_drain(event_loop);
+				}
+			},
+			Timer::Retire { server_token } => {
+				if self.retire_timers[server_token.as_usize()].take().is_some() {
+					debug!("Timer::Retire {:?}", server_token);
+					self.close_server(event_loop, server_token);
+				}
+			},
+		};
+	}
+
 	fn tick(&mut self, event_loop: &mut mio::EventLoop<Self>) {
 		self.queue.awake(|| {
 			(event_loop.channel())
 		});
 
-		if let Ok(command) = self.channel.try_recv() {
-			if command == "shutdown" {
-				info!("Received SHUTDOWN command");
-				self.stage = Stage::Shutdown;
-				self.queue.shutdown(true);
-				event_loop.shutdown();
-				self.cleanup();
+		while let Ok((control, reply)) = self.channel.try_recv() {
+			let response = match control {
+				Control::Execute(command) => self.execute(event_loop, command),
+				Control::Reload(reload) => self.reload(event_loop, reload),
+			};
 
-				if let Err(msg) = self.channel.send("ok") {
-					error!("{}", msg);
-				}
+			if let Err(msg) = reply.send(response) {
+				error!("Cannot send the command response because {}", msg);
+			}
+
+			if self.stage == Stage::Shutdown {
+				break;
 			}
 		}
 	}
@@ -228,48 +1261,92 @@ impl mio::Handler for Core {
 		match msg {
 			Request::Close { client_token } => {
 				debug!("Request::Close {:?}", client_token);
+				let closed = match self.clients.get(client_token) {
+					Some(client) => {
+						if let Some(timeout) = client.replace_timer(None) {
+							event_loop.clear_timeout(timeout);
+						}
+
+						let desc = client.descriptor();
+						Some((*client.server_token(), !desc.is_outbound(), desc.addr(), client.serial(), client.take_outbound()))
+					},
+					None => None,
+				};
+
 				// Remove the client connection from the registry
 				// what results in socket closing
 				self.clients
 					.remove(client_token);
+
+				if let Some((server_token, accepted, addr, serial, outbound)) = closed {
+					self.abort_outbound(event_loop, client_token, serial, outbound);
+
+					if accepted {
+						self.connections[server_token.as_usize()] -= 1;
+
+						if let Some(addr) = addr {
+							self.servers[server_token].limiter().close(addr.ip());
+						}
+					}
+
+					self.release(event_loop, server_token);
+				}
+
+				self.finish_drain(event_loop);
+				self.throttle(event_loop);
 			},
 			Request::Open { client_token, events } => {
 				debug!("Request::Open {:?} for {:?}", client_token, events);
 				// Register the client connection for the new events
-				let _ = self.clients[client_token].then_on_socket(|socket| {
-					socket
-						.tcp_and_then(|sock| {
-							if let Err(msg) = event_loop.register(sock, client_token, events, PollOpt::edge() | PollOpt::oneshot()) {
-								error!("{}", msg);
-							}
-						})
-						.unix_and_then(|sock| {
-							if let Err(msg) = event_loop.register(sock, client_token, events, PollOpt::edge() | PollOpt::oneshot()) {
-								error!("{}", msg);
-							}
-						});
-
-					Ok(())
-				});
+				self.wait(event_loop, client_token, events, false);
 			},
 			Request::Wait { client_token, events } => {
 				// Reregister the client connection for the new events
 				debug!("Request::Wait {:?} for {:?}", client_token, events);
-				let _ = self.clients[client_token].then_on_socket(|socket| {
-					socket
-						.tcp_and_then(|sock| {
-							if let Err(msg) = event_loop.reregister(sock, client_token, events, PollOpt::edge() | PollOpt::oneshot()) {
-								error!("{}", msg);
-							}
-						})
-						.unix_and_then(|sock| {
-							if let Err(msg) = event_loop.reregister(sock, client_token, events, PollOpt::edge() | PollOpt::oneshot()) {
-								error!("{}", msg);
-							}
-						});
+				self.wait(event_loop, client_token, events, true);
+			},
+			Request::Delay { client_token, events, delay } => {
+				debug!("Request::Delay {:?} for {:?} in {} ms", client_token, events, delay);
+				let serial = match self.clients.get(client_token) {
+					Some(client) => client.serial(),
+					None => return,
+				};
 
-					Ok(())
-				});
+				let timer = Timer::Delay {
+					client_token: client_token,
+					serial: serial,
+					events: events,
+				};
+
+				if let Err(msg) = event_loop.timeout_ms(timer, delay) {
+					error!("Cannot delay {:?} because {:?}", client_token, msg);
+					self.wait(event_loop, client_token, events, true);
+				}
+			},
+			Request::Connect { server_token, owner, socket, addr, timeout } => {
+				debug!("Request::Connect {:?} for {:?}", addr, owner);
+				self.connect(event_loop, server_token, owner, socket, addr, timeout);
+			},
+			Request::Wake { client_token, serial, state } => {
+				debug!("Request::Wake {:?} to {}", client_token, state);
+				self.wake(event_loop, client_token, serial, state);
+			},
+			Request::Timer { client_token, serial, delay } => {
+				debug!("Request::Timer {:?} in {} ms", client_token, delay);
+				// The token can be reused by another connection already
+				match self.clients.get(client_token) {
+					Some(client) if client.serial() == serial => (),
+					_ => return,
+				};
+
+				let timer = Timer::Module {
+					client_token: client_token,
+					serial: serial,
+				};
+
+				if let Err(msg) = event_loop.timeout_ms(timer, delay) {
+					error!("Cannot set the module timer of {:?} because {:?}", client_token, msg);
+				}
 			},
 		};
 	}
@@ -283,6 +1360,119 @@ impl Drop for Core {
 }
 
 
+/// Register the server socket in the event loop so it accepts connections or receives datagrams.
+fn listen(event_loop: &mut mio::EventLoop<Core>, server: &Server) -> Result<()> {
+	try!(register_server(event_loop, server));
+
+	match server.config().listen.protocol {
+		Protocol::Tcp(ref details) => info!("Listen on {}:{} using TCP", details.address, details.port),
+		Protocol::Udp(ref details) => info!("Listen on {}:{} using UDP", details.address, details.port),
+		Protocol::Unix(ref details) => info!("Listen on {} using UNIX", details.path),
+	};
+
+	Ok(())
+}
+
+
+fn register_server(event_loop: &mut mio::EventLoop<Core>, server: &Server) -> Result<()> {
+	match *server.socket() {
+		Protocol::Tcp(ref listener) => match event_loop.register(listener, *server.token(), EventSet::all(), PollOpt::edge()) {
+			Ok(_) => Ok(()),
+			Err(msg) => Error::new("TCP listener registration failed").because(msg).result(),
+		},
+		Protocol::Unix(ref listener) => match event_loop.register(listener, *server.token(), EventSet::all(), PollOpt::edge()) {
+			Ok(_) => Ok(()),
+			Err(msg) => Error::new("UNIX listener registration failed").because(msg).result(),
+		},
+		Protocol::Udp(ref socket) => match event_loop.register(socket, *server.token(), EventSet::readable(), PollOpt::edge()) {
+			Ok(_) => Ok(()),
+			Err(msg) => Error::new("UDP socket registration failed").because(msg).result(),
+		},
+	}
+}
+
+
+/// Let the stream processing module of the server act on connections through the loop.
+fn attach(event_loop: &mut mio::EventLoop<Core>, server: &Server, config: &SharedConf) {
+	if !server.socket().is_udp() {
+		let dispatcher: Arc<Dispatcher> = Arc::new(EventDispatcher::new(*server.token(), event_loop.channel(), config.clone()));
+		server.forward().attach(dispatcher);
+	}
+}
+
+
+/// Deregister the server socket from the event loop. Connections wait in the backlog
+/// until the socket is registered again.
+fn unlisten(event_loop: &mut mio::EventLoop<Core>, server: &Server) -> Result<()> {
+	let result = match *server.socket() {
+		Protocol::Tcp(ref listener) => event_loop.deregister(listener),
+		Protocol::Unix(ref listener) => event_loop.deregister(listener),
+		Protocol::Udp(ref socket) => event_loop.deregister(socket),
+	};
+
+	match result {
+		Ok(_) => Ok(()),
+		Err(msg) => Error::new(format!("Cannot deregister {:?} from the event loop", server)).because(msg).result(),
+	}
+}
+
+
+fn register(event_loop: &mut mio::EventLoop<Core>, client: &Client, events: mio::EventSet, registered: bool) -> Result<()> {
+	let token = *client.token();
+
+	client.then_on_socket(|socket| {
+		let result = match *socket {
+			Protocol::Tcp(ref sock) => if registered {
+				event_loop.reregister(sock, token, events, PollOpt::edge() | PollOpt::oneshot())
+			}
+			else {
+				event_loop.register(sock, token, events, PollOpt::edge() | PollOpt::oneshot())
+			},
+			Protocol::Unix(ref sock) => if registered {
+				event_loop.reregister(sock, token, events, PollOpt::edge() | PollOpt::oneshot())
+			}
+			else {
+				event_loop.register(sock, token, events, PollOpt::edge() | PollOpt::oneshot())
+			},
+			Protocol::Udp(_) => return Error::new("UDP has no client connections to register").result(),
+		};
+
+		match result {
+			Ok(_) => Ok(()),
+			Err(msg) => Error::new(format!("Cannot register {:?} in the event loop", client)).because(msg).result(),
+		}
+	})
+}
+
+
+/// Milliseconds left until `deadline`, at least one.
+fn milliseconds_until(deadline: Instant) -> u64 {
+	let now = Instant::now();
+	if deadline <= now {
+		return 1;
+	}
+
+	let left = deadline - now;
+	left.as_secs() * 1000 + (left.subsec_nanos() / 1000000) as u64 + 1
+}
+
+
+fn deregister(event_loop: &mut mio::EventLoop<Core>, client: &Client) -> Result<()> {
+	client.then_on_socket(|socket| {
+		let result = match *socket {
+			Protocol::Tcp(ref sock) => event_loop.deregister(sock),
+			Protocol::Unix(ref sock) => event_loop.deregister(sock),
+			Protocol::Udp(_) => return Ok(()),
+		};
+
+		match result {
+			Ok(_) => Ok(()),
+			Err(msg) => Error::new(format!("Cannot deregister {:?} from the event loop", client)).because(msg).result(),
+		}
+	})
+}
+
+
 fn accept<Sock> (sock: &Sock, event_loop: &mut mio::EventLoop<Core>) -> Result<Option<Sock::Output>>
 	where Sock: mio::TryAccept {
 	match sock.accept() {
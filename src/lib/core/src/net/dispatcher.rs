@@ -0,0 +1,105 @@
+//! The way stream processing modules ask the event loop to act on connections.
+
+use std::sync::Mutex;
+use wrust_io::mio;
+use wrust_io::mio::tcp::TcpStream;
+use wrust_io::mio::unix::UnixStream;
+use wrust_types::{Error, Result};
+use wrust_types::net::Protocol;
+use wrust_types::net::connection::State;
+use wrust_conf::{Conf, SharedConf};
+use wrust_module::stream::{Dispatcher, Address, Intention};
+use super::{EventChannel, Request};
+
+
+/// `EventDispatcher` translates calls of the stream processing module of the server
+/// into event loop requests.
+pub struct EventDispatcher {
+	server_token: mio::Token,
+	channel: Mutex<EventChannel>,
+	config: SharedConf,
+}
+
+
+impl EventDispatcher {
+	pub fn new(server_token: mio::Token, channel: EventChannel, config: SharedConf) -> EventDispatcher {
+		EventDispatcher {
+			server_token: server_token,
+			channel: Mutex::new(channel),
+			config: config,
+		}
+	}
+
+	fn send(&self, request: Request) -> Result<()> {
+		let channel = self.channel.lock().unwrap();
+		match channel.send(request) {
+			Ok(_) => Ok(()),
+			Err(_) => Error::new("Cannot send the request to the event loop").result(),
+		}
+	}
+}
+
+
+impl Dispatcher for EventDispatcher {
+	fn connect(self: &Self, owner: Option<(u32, usize)>, addr: &Address, timeout: u64) -> Result<()> {
+		// Sockets are connected in non-blocking mode so the connection
+		// is established later in the event loop
+		let (socket, peer) = match *addr {
+			Protocol::Tcp(ref addr) => match TcpStream::connect(addr) {
+				Ok(stream) => (Protocol::Tcp(stream), Some(*addr)),
+				Err(msg) => return Error::new(format!("Cannot connect to {}", addr)).because(msg).result(),
+			},
+			Protocol::Unix(ref path) => match UnixStream::connect(path) {
+				Ok(stream) => (Protocol::Unix(stream), None),
+				Err(msg) => return Error::new(format!("Cannot connect to {}", path.display())).because(msg).result(),
+			},
+			Protocol::Udp(_) => return Error::new("Cannot connect because UDP has no client connections").result(),
+		};
+
+		self.send(Request::Connect {
+			server_token: self.server_token,
+			owner: owner.map(|(id, serial)| (mio::Token(id as usize), serial)),
+			socket: socket,
+			addr: peer,
+			timeout: timeout,
+		})
+	}
+
+	fn wake(self: &Self, id: u32, intention: Intention) -> Result<()> {
+		self.send(Request::Wake {
+			client_token: mio::Token(id as usize),
+			serial: None,
+			state: try!(wake_state(intention)),
+		})
+	}
+
+	fn wake_serial(self: &Self, id: u32, serial: usize, intention: Intention) -> Result<()> {
+		self.send(Request::Wake {
+			client_token: mio::Token(id as usize),
+			serial: Some(serial),
+			state: try!(wake_state(intention)),
+		})
+	}
+
+	fn timer(self: &Self, id: u32, serial: usize, delay: u64) -> Result<()> {
+		self.send(Request::Timer {
+			client_token: mio::Token(id as usize),
+			serial: serial,
+			delay: delay,
+		})
+	}
+
+	fn config(self: &Self) -> Conf {
+		self.config.snapshot()
+	}
+}
+
+
+/// Get the state the connection woken up with `intention` continues in.
+/// Waking up makes sense only to write or to close the connection.
+fn wake_state(intention: Intention) -> Result<State> {
+	match intention {
+		Intention::Write | Intention::Close(_) => Ok(intention.as_state()),
+		_ => Error::new(format!("The connection cannot be woken up with the {} intention", intention)).result(),
+	}
+}
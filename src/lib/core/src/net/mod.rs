@@ -2,11 +2,16 @@ pub mod client;
 pub mod server;
 pub mod work;
 pub mod core;
+pub mod control;
+mod dispatcher;
 
+pub use self::dispatcher::EventDispatcher;
+
+use std::net::SocketAddr;
 use wrust_io::mio;
-use wrust_types::channel::{Channel};
+use wrust_types::net::connection::State;
+use self::client::ClientProtocol;
 
-pub type CommandChannel = Channel<&'static str>;
 pub type EventChannel = mio::Sender<Request>;
 
 /// Listener event loop messaging enum.
@@ -19,4 +24,43 @@ pub enum Request {
 
 	/// Push the client connection into the queue to `Wait` for further I/O events.
 	Wait { client_token: mio::Token, events: mio::EventSet },
+
+	/// Let the client connection `Wait` for further I/O events after `delay` milliseconds.
+	Delay { client_token: mio::Token, events: mio::EventSet, delay: u64 },
+
+	/// `Connect` the outbound connection `socket` to `addr` opened by the server `server_token`,
+	/// optionally on behalf of the connection with the token and serial in `owner`. If `timeout` is not zero
+	/// the connection fails when it is not established in `timeout` milliseconds.
+	Connect { server_token: mio::Token, owner: Option<(mio::Token, usize)>, socket: ClientProtocol, addr: Option<SocketAddr>, timeout: u64 },
+
+	/// `Wake` the client connection waiting for I/O events so it continues in the `state` given.
+	/// If `serial` is given the client connection must have it.
+	Wake { client_token: mio::Token, serial: Option<usize>, state: State },
+
+	/// Set the stream processing module `Timer` of the client connection with the `serial` number
+	/// which expires in `delay` milliseconds.
+	Timer { client_token: mio::Token, serial: usize, delay: u64 },
+}
+
+
+/// Listener event loop timers.
+#[derive(Debug, Clone, Copy)]
+pub enum Timer {
+	/// The outbound connection with the `serial` number is not established in time.
+	Connect { client_token: mio::Token, serial: usize },
+
+	/// One of timeouts of the client connection with the `serial` number can expire.
+	Expire { client_token: mio::Token, serial: usize },
+
+	/// The client connection with the `serial` number waits for `events` after the delay.
+	Delay { client_token: mio::Token, serial: usize, events: mio::EventSet },
+
+	/// The timer the stream processing module set for the client connection with the `serial` number expired.
+	Module { client_token: mio::Token, serial: usize },
+
+	/// Connections are not drained in time on graceful shutdown.
+	Drain,
+
+	/// Connections of the server replaced or removed on configuration reload are not drained in time.
+	Retire { server_token: mio::Token },
 }
@@ -0,0 +1,100 @@
+use std::env;
+use libc;
+use std::os::unix::io::RawFd;
+use wrust_types::{Error, Result};
+
+/// The first descriptor systemd passes sockets from.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// The name systemd gives to sockets without `FileDescriptorName`.
+const UNNAMED: &'static str = "unknown";
+
+
+/// Read sockets passed by systemd socket activation as pairs of names and descriptors.
+/// Variables are removed from the environment so processes started later do not see them.
+pub fn listen_fds() -> Vec<(String, RawFd)> {
+	let pid = env::var("LISTEN_PID").ok();
+	let fds = env::var("LISTEN_FDS").ok();
+	let names = env::var("LISTEN_FDNAMES").ok();
+
+	env::remove_var("LISTEN_PID");
+	env::remove_var("LISTEN_FDS");
+	env::remove_var("LISTEN_FDNAMES");
+
+	let (pid, fds) = match (pid, fds) {
+		(Some(pid), Some(fds)) => (pid, fds),
+		_ => return Vec::new(),
+	};
+
+	let sockets = match decode(unsafe { libc::getpid() } as u32, &pid, &fds, names.as_ref().map(|names| names.as_str())) {
+		Ok(sockets) => sockets,
+		Err(msg) => {
+			warn!("Sockets passed by systemd are ignored because {}", msg);
+			return Vec::new();
+		},
+	};
+
+	// The event loop needs non-blocking sockets and sockets not taken over
+	// by listeners must not leak into processes started later
+	for &(ref name, fd) in &sockets {
+		unsafe {
+			let flags = libc::fcntl(fd, libc::F_GETFD);
+			if flags < 0 || libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) < 0 {
+				warn!("Cannot set close-on-exec flag on the socket {} passed by systemd", name);
+			}
+
+			let flags = libc::fcntl(fd, libc::F_GETFL);
+			if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) < 0 {
+				warn!("Cannot set non-blocking mode on the socket {} passed by systemd", name);
+			}
+		}
+	}
+
+	sockets
+}
+
+
+fn decode(own_pid: u32, pid: &str, fds: &str, names: Option<&str>) -> Result<Vec<(String, RawFd)>> {
+	match pid.trim().parse::<u32>() {
+		Ok(pid) if pid == own_pid => (),
+		Ok(pid) => return Error::new(format!("they are passed to the process {}", pid)).result(),
+		Err(_) => return Error::new(format!("LISTEN_PID '{}' is invalid", pid)).result(),
+	};
+
+	let count = match fds.trim().parse::<RawFd>() {
+		Ok(count) if count >= 0 => count,
+		_ => return Error::new(format!("LISTEN_FDS '{}' is invalid", fds)).result(),
+	};
+
+	let names: Vec<&str> = match names {
+		Some(names) => names.split(':').collect(),
+		None => Vec::new(),
+	};
+
+	Ok((0..count)
+		.map(|i| {
+			let name = match names.get(i as usize) {
+				Some(name) if !name.is_empty() => name.to_string(),
+				_ => UNNAMED.to_string(),
+			};
+
+			(name, LISTEN_FDS_START + i)
+		})
+		.collect())
+}
+
+
+#[test]
+fn test_listen_fds_decoding() {
+	let sockets = decode(100, "100", "3", Some("http:admin")).unwrap();
+	assert_eq!(sockets, vec![
+		("http".to_string(), 3),
+		("admin".to_string(), 4),
+		("unknown".to_string(), 5),
+	]);
+
+	assert_eq!(decode(100, "100", "0", None).unwrap(), vec![]);
+	assert!(decode(100, "101", "1", None).is_err());
+	assert!(decode(100, "x", "1", None).is_err());
+	assert!(decode(100, "100", "-1", None).is_err());
+}
@@ -1,19 +1,39 @@
 //! Server socket configuration
 
+use std::fmt;
 use std::net::SocketAddr;
 use wrust_types::{Error, Result};
 use wrust_types::net::Protocol;
-use wrust_conf::{Conf, FromConf};
-use ::conf::{ModuleConf, SocketConf};
+use wrust_conf::Conf;
+use wrust_conf::schema::{Field, Section};
+use ::conf::{ModuleConf, SocketConf, TlsConf, TimeoutConf, LimitConf, AccessConf};
 
 
-/// Server socket configuration
-#[derive(Clone)]
-pub struct ServerConf {
-	/// Listening socket configuration
-	pub listen: SocketConf,
-	/// Stream forwarding module
-	pub forward: ModuleConf,
+conf_schema! {
+	/// Server socket configuration
+	#[derive(Clone, PartialEq)]
+	pub struct ServerConf {
+		/// Name the socket passed by systemd socket activation is matched with
+		name: Option<String> = Field::optional(),
+		/// Listening socket configuration
+		listen: SocketConf = Section::required(),
+		/// Stream forwarding module
+		forward: ModuleConf = Section::required(),
+		/// TLS termination settings
+		tls: Option<TlsConf> = Section::optional(),
+		/// Client connection timeouts
+		timeout: TimeoutConf = Section::default(TimeoutConf::default()),
+		/// The maximum number of connections the listener keeps open at once.
+		/// The listener stops accepting connections while the limit is reached.
+		max_connections: Option<usize> = Field::optional(),
+		/// The size of the queue connections wait to be accepted in
+		backlog: Option<i32> = Field::optional(),
+		/// Limits of each client address
+		limits: LimitConf = Section::default(LimitConf::default()),
+		/// Client addresses which can connect
+		access: AccessConf = Section::default(AccessConf::default()),
+	}
+	finish = ServerConf::finish;
 }
 
 
@@ -39,26 +59,44 @@ impl ServerConf {
 			Error::new("Non-TCP or non-UDP address").result()
 		}
 	}
-}
 
+	/// Check settings which depend on the protocol of the listener.
+	fn finish(self, config: &Conf, xpath: &str) -> Result<ServerConf> {
+		let tcp = self.listen.protocol.is_tcp();
+		let udp = self.listen.protocol.is_udp();
+
+		if self.tls.is_some() && !tcp {
+			return Error::new(format!("TLS is supported on TCP listeners only at '{}.tls'", xpath)).result();
+		}
+
+		if config.lookup(&format!("{}.timeout", xpath)).is_some() && udp {
+			return Error::new(format!("Timeouts are not supported on UDP listeners at '{}.timeout'", xpath)).result();
+		}
+
+		if config.lookup(&format!("{}.limits", xpath)).is_some() && !tcp {
+			return Error::new(format!("Client address limits are supported on TCP listeners only at '{}.limits'", xpath)).result();
+		}
+
+		if config.lookup(&format!("{}.access", xpath)).is_some() && !tcp {
+			return Error::new(format!("Access lists are supported on TCP listeners only at '{}.access'", xpath)).result();
+		}
 
-impl FromConf for ServerConf {
-	// Load settings from the config
-	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		// Determine where xpath is reference to
-		let xpath = match config.resolve_reference(xpath) {
-			Some(path) => path,
-			None => return Error::new(format!("Reference or group is not found at '{}'", xpath)).result(),
-		};
+		// The connection limit and the listen backlog size
+		try!(positive(self.max_connections, &format!("{}.max_connections", xpath)));
+		try!(positive(self.backlog, &format!("{}.backlog", xpath)));
+		if udp && (self.max_connections.is_some() || self.backlog.is_some()) {
+			return Error::new(format!("Connection limits are not supported on UDP listeners at '{}'", xpath)).result();
+		}
+
+		Ok(self)
+	}
+}
 
-		// Read listening socket configuration
-		let listen_conf = try!(SocketConf::from_conf(&config, &format!("{}.listen", xpath)));
-		// Read traffic forward target
-		let forward_conf = try!(ModuleConf::from_conf(&config, &format!("{}.forward", xpath)));
 
-		Ok(ServerConf {
-			listen: listen_conf,
-			forward: forward_conf,
-		})
+/// Check the optional `value` of the setting at `xpath` is greater than zero.
+fn positive<T: Default + PartialOrd + fmt::Display>(value: Option<T>, xpath: &str) -> Result<()> {
+	match value {
+		Some(ref value) if *value <= T::default() => Error::new(format!("Value {} must be greater than zero at '{}'", value, xpath)).result(),
+		_ => Ok(()),
 	}
 }
@@ -0,0 +1,187 @@
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::collections::HashMap;
+use std::os::unix::io::{RawFd, FromRawFd};
+use ::conf::SocketConf;
+use super::ServerConf;
+use super::activation;
+
+/// Environment variable the listener sockets are passed to the new process with on upgrade.
+pub const INHERITED_ENV: &'static str = "WRUST_INHERITED_FDS";
+
+/// Environment variable with the pipe the new process tells it is ready through.
+pub const READY_ENV: &'static str = "WRUST_READY_FD";
+
+/// Suffix of the descriptor of the socket passed by systemd socket activation.
+const ACTIVATED_MARK: &'static str = "+activated";
+
+
+/// Listener sockets inherited from the process which started this one.
+/// Sockets passed on upgrade are identified by the address they listen on so
+/// the configuration of the new process decides which of them are used. They keep
+/// telling if systemd passed them to the process which started this one.
+/// Sockets passed by systemd socket activation are identified by their names.
+pub struct Inherited {
+	sockets: HashMap<String, (RawFd, bool)>,
+	activated: HashMap<String, RawFd>,
+	ready: Option<RawFd>,
+}
+
+
+impl Inherited {
+	/// Create `Inherited` with no sockets.
+	pub fn new() -> Inherited {
+		Inherited {
+			sockets: HashMap::new(),
+			activated: HashMap::new(),
+			ready: None,
+		}
+	}
+
+	/// Read inherited sockets from the environment. Variables are removed
+	/// from the environment so processes started later do not see them.
+	pub fn from_env() -> Inherited {
+		let sockets = match env::var(INHERITED_ENV) {
+			Ok(value) => decode(&value),
+			Err(_) => HashMap::new(),
+		};
+
+		let ready = match env::var(READY_ENV) {
+			Ok(value) => value.parse().ok(),
+			Err(_) => None,
+		};
+
+		env::remove_var(INHERITED_ENV);
+		env::remove_var(READY_ENV);
+
+		let mut activated = HashMap::new();
+		for (name, fd) in activation::listen_fds() {
+			if let Some(fd) = activated.insert(name.clone(), fd) {
+				warn!("Socket {} passed by systemd is closed because another one has the same name", name);
+				drop(unsafe { File::from_raw_fd(fd) });
+			}
+		}
+
+		Inherited {
+			sockets: sockets,
+			activated: activated,
+			ready: ready,
+		}
+	}
+
+	/// Encode sockets given as listening addresses, file descriptors and flags of sockets
+	/// passed by systemd socket activation as the value of `INHERITED_ENV`.
+	pub fn encode(sockets: &[(String, RawFd, bool)]) -> String {
+		sockets
+			.iter()
+			.map(|&(ref listen, fd, activated)| if activated {
+				format!("{}={}{}", listen, fd, ACTIVATED_MARK)
+			}
+			else {
+				format!("{}={}", listen, fd)
+			})
+			.collect::<Vec<String>>()
+			.join(";")
+	}
+
+	/// Take the socket which listens on `listen` if it is inherited along with
+	/// the flag of the socket passed by systemd socket activation.
+	pub fn take(&mut self, listen: &SocketConf) -> Option<(RawFd, bool)> {
+		self.sockets.remove(&format!("{}", listen))
+	}
+
+	/// Take the socket passed by systemd with the name of the server `config`.
+	pub fn take_activated(&mut self, config: &ServerConf) -> Option<RawFd> {
+		match config.name {
+			Some(ref name) => self.activated.remove(name),
+			None => None,
+		}
+	}
+
+	/// Test if no sockets are inherited.
+	pub fn is_empty(&self) -> bool {
+		self.sockets.is_empty() && self.activated.is_empty()
+	}
+
+	/// Close sockets no listener has taken.
+	pub fn close_unused(&mut self) {
+		for (listen, (fd, _)) in self.sockets.drain() {
+			info!("Inherited socket {} is closed because no listener uses it", listen);
+			drop(unsafe { File::from_raw_fd(fd) });
+		}
+
+		for (name, fd) in self.activated.drain() {
+			warn!("Socket {} passed by systemd is closed because no listener has that name", name);
+			drop(unsafe { File::from_raw_fd(fd) });
+		}
+	}
+
+	/// Tell the process which started this one that the sockets are taken over.
+	pub fn notify_ready(&mut self) {
+		if let Some(fd) = self.ready.take() {
+			let mut pipe = unsafe { File::from_raw_fd(fd) };
+			if let Err(msg) = pipe.write_all(b"1") {
+				error!("Cannot notify the parent process because {}", msg);
+			}
+		}
+	}
+}
+
+
+impl Drop for Inherited {
+	fn drop(&mut self) {
+		// The parent process sees the pipe closed without notification
+		if let Some(fd) = self.ready.take() {
+			drop(unsafe { File::from_raw_fd(fd) });
+		}
+	}
+}
+
+
+fn decode(value: &str) -> HashMap<String, (RawFd, bool)> {
+	let mut sockets = HashMap::new();
+
+	for item in value.split(';') {
+		// The address can contain '=' so the descriptor is after the last one
+		let mut parts = item.rsplitn(2, '=');
+		let fd = parts.next().map(|fd| fd.trim()).and_then(|fd| if fd.ends_with(ACTIVATED_MARK) {
+			fd[..fd.len() - ACTIVATED_MARK.len()].parse::<RawFd>().ok().map(|fd| (fd, true))
+		}
+		else {
+			fd.parse::<RawFd>().ok().map(|fd| (fd, false))
+		});
+		let listen = parts.next();
+
+		match (listen, fd) {
+			(Some(listen), Some(fd)) => {
+				sockets.insert(listen.to_string(), fd);
+			},
+			_ => {
+				if !item.trim().is_empty() {
+					warn!("Inherited socket '{}' is malformed", item);
+				}
+			},
+		};
+	}
+
+	sockets
+}
+
+
+#[test]
+fn test_inherited_encoding() {
+	let sockets = vec![
+		("tcp 127.0.0.1:8080".to_string(), 5, false),
+		("unix /tmp/a=b.sock".to_string(), 6, true),
+	];
+
+	let decoded = decode(&Inherited::encode(&sockets));
+	assert_eq!(decoded.len(), 2);
+	assert_eq!(decoded.get("tcp 127.0.0.1:8080"), Some(&(5, false)));
+	assert_eq!(decoded.get("unix /tmp/a=b.sock"), Some(&(6, true)));
+
+	assert!(decode("").is_empty());
+	assert!(decode("tcp 127.0.0.1:8080").is_empty());
+	assert!(decode("tcp 127.0.0.1:8080=x").is_empty());
+}
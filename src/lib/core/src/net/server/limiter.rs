@@ -0,0 +1,289 @@
+//! Limits of client IP addresses
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use wrust_types::{Error, Result};
+use ::conf::LimitConf;
+
+
+/// Time in milliseconds addresses which have nothing left to track are kept for.
+const SWEEP_INTERVAL: u64 = 1000;
+
+
+/// Token bucket which refills at the rate per second up to its capacity. More tokens
+/// than the bucket has can be taken so it goes into debt which is paid off first.
+struct Bucket {
+	tokens: f64,
+	refilled: Instant,
+}
+
+
+impl Bucket {
+	fn full(capacity: f64, now: Instant) -> Bucket {
+		Bucket {
+			tokens: capacity,
+			refilled: now,
+		}
+	}
+
+	fn refill(&mut self, rate: f64, capacity: f64, now: Instant) {
+		if now > self.refilled {
+			let elapsed = now - self.refilled;
+			let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1000000000.0;
+
+			self.tokens = (self.tokens + seconds * rate).min(capacity);
+			self.refilled = now;
+		}
+	}
+}
+
+
+/// What is tracked for the client address.
+struct Peer {
+	connections: u32,
+	opened: Bucket,
+	transferred: Bucket,
+}
+
+
+struct Peers {
+	items: HashMap<IpAddr, Peer>,
+	swept: Instant,
+}
+
+
+/// Enforces `LimitConf` for each client address of the server. Connections are counted
+/// by the event loop and bytes by workers which read and write them.
+pub struct Limiter {
+	conf: LimitConf,
+	peers: Arc<Mutex<Peers>>,
+	denied: AtomicUsize,
+}
+
+
+impl Limiter {
+	pub fn new(conf: &LimitConf) -> Limiter {
+		Limiter {
+			conf: conf.clone(),
+			peers: Arc::new(Mutex::new(Peers {
+				items: HashMap::new(),
+				swept: Instant::now(),
+			})),
+			denied: AtomicUsize::new(0),
+		}
+	}
+
+	/// Create the limiter which enforces `conf` and shares addresses tracked with this one
+	/// so connections which are still open count against limits of the new limiter.
+	pub fn with_conf(&self, conf: &LimitConf) -> Limiter {
+		Limiter {
+			conf: conf.clone(),
+			peers: self.peers.clone(),
+			denied: AtomicUsize::new(0),
+		}
+	}
+
+	/// Count the new connection from `ip` or fail if it exceeds limits of the address.
+	pub fn open(&self, ip: IpAddr) -> Result<()> {
+		self.open_at(ip, Instant::now())
+	}
+
+	/// Count the connection from `ip` closed.
+	pub fn close(&self, ip: IpAddr) {
+		if !self.conf.is_enabled() {
+			return;
+		}
+
+		let mut peers = self.peers.lock().unwrap();
+		if let Some(peer) = peers.items.get_mut(&ip) {
+			peer.connections = peer.connections.saturating_sub(1);
+		}
+	}
+
+	/// Get time in milliseconds the connection from `ip` has to wait before
+	/// it reads or writes again if the address spent the bandwidth allowed.
+	pub fn delay(&self, ip: IpAddr) -> Option<u64> {
+		self.delay_at(ip, Instant::now())
+	}
+
+	/// Count `bytes` read or written by the connection from `ip`.
+	pub fn transfer(&self, ip: IpAddr, bytes: usize) {
+		self.transfer_at(ip, bytes, Instant::now())
+	}
+
+	/// The number of connections denied since the server started.
+	pub fn denied(&self) -> usize {
+		self.denied.load(Ordering::SeqCst)
+	}
+
+	fn open_at(&self, ip: IpAddr, now: Instant) -> Result<()> {
+		if !self.conf.is_enabled() {
+			return Ok(());
+		}
+
+		let mut peers = self.peers.lock().unwrap();
+		self.sweep(&mut peers, now);
+
+		let (rate, burst) = self.opened_rate();
+		let peer = self.peer(&mut peers, ip, now);
+
+		if self.conf.connections > 0 && peer.connections >= self.conf.connections {
+			self.denied.fetch_add(1, Ordering::SeqCst);
+			return Error::new(format!("{} connections are open from {} already", peer.connections, ip)).result();
+		}
+
+		if self.conf.rate > 0 {
+			peer.opened.refill(rate, burst, now);
+			if peer.opened.tokens < 1.0 {
+				self.denied.fetch_add(1, Ordering::SeqCst);
+				return Error::new(format!("Connections are opened from {} more often than {} per second", ip, self.conf.rate)).result();
+			}
+
+			peer.opened.tokens -= 1.0;
+		}
+
+		peer.connections += 1;
+
+		Ok(())
+	}
+
+	fn delay_at(&self, ip: IpAddr, now: Instant) -> Option<u64> {
+		if self.conf.bandwidth == 0 {
+			return None;
+		}
+
+		let bandwidth = self.conf.bandwidth as f64;
+		let mut peers = self.peers.lock().unwrap();
+		let peer = self.peer(&mut peers, ip, now);
+
+		peer.transferred.refill(bandwidth, bandwidth, now);
+		if peer.transferred.tokens >= 0.0 {
+			return None;
+		}
+
+		Some((-peer.transferred.tokens * 1000.0 / bandwidth) as u64 + 1)
+	}
+
+	fn transfer_at(&self, ip: IpAddr, bytes: usize, now: Instant) {
+		if self.conf.bandwidth == 0 {
+			return;
+		}
+
+		let bandwidth = self.conf.bandwidth as f64;
+		let mut peers = self.peers.lock().unwrap();
+		let peer = self.peer(&mut peers, ip, now);
+
+		peer.transferred.refill(bandwidth, bandwidth, now);
+		peer.transferred.tokens -= bytes as f64;
+	}
+
+	/// The rate new connections are allowed at and how many can be opened at once.
+	fn opened_rate(&self) -> (f64, f64) {
+		let burst = if self.conf.burst > 0 {
+			self.conf.burst
+		}
+		else {
+			self.conf.rate
+		};
+
+		(self.conf.rate as f64, burst as f64)
+	}
+
+	fn peer<'a>(&self, peers: &'a mut Peers, ip: IpAddr, now: Instant) -> &'a mut Peer {
+		let (_, burst) = self.opened_rate();
+		let bandwidth = self.conf.bandwidth as f64;
+
+		peers.items.entry(ip).or_insert_with(|| Peer {
+			connections: 0,
+			opened: Bucket::full(burst, now),
+			transferred: Bucket::full(bandwidth, now),
+		})
+	}
+
+	/// Forget addresses without connections which buckets are full again so they
+	/// start over the same way as addresses which are seen first.
+	fn sweep(&self, peers: &mut Peers, now: Instant) {
+		if now < peers.swept + Duration::from_millis(SWEEP_INTERVAL) {
+			return;
+		}
+
+		let (rate, burst) = self.opened_rate();
+		let bandwidth = self.conf.bandwidth as f64;
+
+		let idle: Vec<IpAddr> = peers.items
+			.iter_mut()
+			.filter_map(|(ip, peer)| {
+				peer.opened.refill(rate, burst, now);
+				peer.transferred.refill(bandwidth, bandwidth, now);
+
+				if peer.connections == 0 && peer.opened.tokens >= burst && peer.transferred.tokens >= bandwidth {
+					Some(*ip)
+				}
+				else {
+					None
+				}
+			})
+			.collect();
+
+		for ip in idle {
+			peers.items.remove(&ip);
+		}
+
+		peers.swept = now;
+	}
+}
+
+
+#[test]
+fn test_limiter() {
+	let ip: IpAddr = "192.0.2.1".parse().unwrap();
+	let other: IpAddr = "2001:db8::1".parse().unwrap();
+	let start = Instant::now();
+	let later = |ms: u64| start + Duration::from_millis(ms);
+
+	// Concurrent connections
+	let limiter = Limiter::new(&LimitConf { connections: 2, ..LimitConf::default() });
+	assert!(limiter.open_at(ip, start).is_ok());
+	assert!(limiter.open_at(ip, start).is_ok());
+	assert!(limiter.open_at(ip, start).is_err());
+	assert!(limiter.open_at(other, start).is_ok());
+	limiter.close(ip);
+	assert!(limiter.open_at(ip, start).is_ok());
+	assert_eq!(limiter.denied(), 1);
+
+	// Connections open from the previous limiter count against the new one
+	let replacement = limiter.with_conf(&LimitConf { connections: 3, ..LimitConf::default() });
+	assert!(replacement.open_at(ip, start).is_ok());
+	assert!(replacement.open_at(ip, start).is_err());
+	limiter.close(ip);
+	assert!(replacement.open_at(ip, start).is_ok());
+
+	// New connections per second
+	let limiter = Limiter::new(&LimitConf { rate: 2, burst: 3, ..LimitConf::default() });
+	for _ in 0..3 {
+		assert!(limiter.open_at(ip, start).is_ok());
+	}
+	assert!(limiter.open_at(ip, start).is_err());
+	assert!(limiter.open_at(ip, later(250)).is_err());
+	assert!(limiter.open_at(ip, later(500)).is_ok());
+	assert!(limiter.open_at(ip, later(500)).is_err());
+	assert_eq!(limiter.denied(), 3);
+
+	// Bytes per second
+	let limiter = Limiter::new(&LimitConf { bandwidth: 1000, ..LimitConf::default() });
+	assert_eq!(limiter.delay_at(ip, start), None);
+	limiter.transfer_at(ip, 1500, start);
+	assert_eq!(limiter.delay_at(other, start), None);
+	assert_eq!(limiter.delay_at(ip, start), Some(501));
+	assert_eq!(limiter.delay_at(ip, later(600)), None);
+
+	// Disabled limits
+	let limiter = Limiter::new(&LimitConf::default());
+	for _ in 0..100 {
+		assert!(limiter.open_at(ip, start).is_ok());
+	}
+	assert_eq!(limiter.delay_at(ip, start), None);
+}
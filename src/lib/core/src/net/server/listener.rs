@@ -0,0 +1,237 @@
+use std::io;
+use std::mem;
+use std::fs::File;
+use std::sync::Arc;
+use std::path::Path;
+use std::os::unix::io::{RawFd, AsRawFd, FromRawFd};
+use libc;
+use wrust_io::mio;
+use wrust_io::mio::tcp::TcpListener;
+use wrust_io::mio::udp::UdpSocket;
+use wrust_io::mio::unix::UnixListener;
+use wrust_io::tls::ServerConfig;
+use wrust_types::{Error, Result};
+use wrust_types::net::Protocol;
+use wrust_module::{Category, Instance};
+use ::module::Factory;
+use super::{Server, ServerConf, Limiter};
+use super::server::ServerProtocol;
+use super::tls;
+
+
+/// Listener prepared outside of the event loop. It becomes the `Server`
+/// when it is added to the `Registry`.
+pub struct Listener {
+	config: ServerConf,
+	socket: Option<ServerProtocol>,
+	forward: Instance,
+	tls: Option<Arc<ServerConfig>>,
+	activated: bool,
+	limiter: Option<Limiter>,
+}
+
+
+impl Listener {
+	/// Bind the socket and instantiate the module configured.
+	pub fn bind(module_factory: &Factory, config: &ServerConf) -> Result<Listener> {
+		let socket = match config.listen.protocol {
+			Protocol::Tcp(ref _details) => {
+				let addr = try!(config.socket_address());
+
+				match TcpListener::bind(&addr) {
+					Ok(listener) => Protocol::Tcp(listener),
+					Err(msg) => return Error::new("TCP Server socket binding failed").because(msg).result()
+				}
+			},
+			Protocol::Udp(ref _details) => {
+				let addr = try!(config.socket_address());
+
+				match UdpSocket::bound(&addr) {
+					Ok(socket) => Protocol::Udp(socket),
+					Err(msg) => return Error::new("UDP Server socket binding failed").because(msg).result()
+				}
+			},
+			Protocol::Unix(ref details) => {
+				let path = Path::new(&details.path);
+
+				match UnixListener::bind(&path) {
+					Ok(listener) => Protocol::Unix(listener),
+					Err(msg) => return Error::new("UNIX Server socket binding failed").because(msg).result()
+				}
+			},
+		};
+
+		let listener = try!(Listener::configure(module_factory, config));
+
+		Ok(listener.with_socket(socket))
+	}
+
+	/// Take over the socket `fd` inherited from another process instead of binding it
+	/// and instantiate the module configured.
+	pub fn inherit(module_factory: &Factory, config: &ServerConf, fd: RawFd) -> Result<Listener> {
+		let socket = match config.listen.protocol {
+			Protocol::Tcp(_) => Protocol::Tcp(unsafe { TcpListener::from_raw_fd(fd) }),
+			Protocol::Udp(_) => Protocol::Udp(unsafe { UdpSocket::from_raw_fd(fd) }),
+			Protocol::Unix(_) => Protocol::Unix(unsafe { UnixListener::from_raw_fd(fd) }),
+		};
+
+		let listener = try!(Listener::configure(module_factory, config));
+
+		info!("Inherited socket {} is taken over", config.listen);
+
+		Ok(listener.with_socket(socket))
+	}
+
+	/// Take over the socket `fd` passed by systemd socket activation. The socket
+	/// belongs to systemd so its file is not removed when the listener closes.
+	pub fn activate(module_factory: &Factory, config: &ServerConf, fd: RawFd) -> Result<Listener> {
+		// The socket is matched to the listener by the name only so it can be of another kind
+		if let Err(msg) = check_socket(config, fd) {
+			drop(unsafe { File::from_raw_fd(fd) });
+			return Error::new(format!("Socket passed by systemd cannot be used by {}", config.listen)).because(msg).result();
+		}
+
+		let listener = try!(Listener::inherit(module_factory, config, fd));
+
+		Ok(listener.with_activated(true))
+	}
+
+	/// Instantiate the module configured without binding the socket. The listener
+	/// gets the socket of the listener it replaces.
+	pub fn configure(module_factory: &Factory, config: &ServerConf) -> Result<Listener> {
+		// Datagram sockets are forwarded to datagram processing modules
+		// and all others to stream processing modules
+		let category = if config.listen.protocol.is_udp() {
+			Category::Datagram
+		}
+		else {
+			Category::Stream
+		};
+
+		let forward = try!(module_factory.produce(category, &config.forward.module, &config.forward.xpath));
+		let tls = match config.tls {
+			Some(ref tls_conf) => Some(try!(tls::server_config(tls_conf))),
+			None => None,
+		};
+
+		Ok(Listener {
+			config: config.clone(),
+			socket: None,
+			forward: forward,
+			tls: tls,
+			activated: false,
+			limiter: None,
+		})
+	}
+
+	pub fn config(&self) -> &ServerConf {
+		&self.config
+	}
+
+	/// Test if the listener has the socket bound.
+	pub fn is_bound(&self) -> bool {
+		self.socket.is_some()
+	}
+
+	/// Set the socket the listener accepts connections on.
+	pub fn with_socket(mut self, socket: ServerProtocol) -> Listener {
+		self.socket = Some(socket);
+		self
+	}
+
+	/// Set if the socket is passed by systemd socket activation.
+	pub fn with_activated(mut self, activated: bool) -> Listener {
+		self.activated = activated;
+		self
+	}
+
+	/// Set the limiter of client addresses which keeps counting connections
+	/// of the listener replaced.
+	pub fn with_limiter(mut self, limiter: Limiter) -> Listener {
+		self.limiter = Some(limiter);
+		self
+	}
+
+	/// Turn the listener into the `Server` identified with `token`.
+	pub fn into_server(self, token: mio::Token) -> Result<Server> {
+		let socket = match self.socket {
+			Some(socket) => socket,
+			None => return Error::new("Listener has no socket to accept connections on").result(),
+		};
+
+		if let Some(backlog) = self.config.backlog {
+			try!(set_backlog(&socket, backlog));
+		}
+
+		let limiter = match self.limiter {
+			Some(limiter) => limiter,
+			None => Limiter::new(&self.config.limits),
+		};
+
+		Ok(Server::new(token, self.config, socket, self.forward, self.tls, self.activated, limiter))
+	}
+}
+
+
+/// Check the socket `fd` passed from outside has the type and the address family
+/// the listener `config` expects and accepts connections if it is a stream socket.
+fn check_socket(config: &ServerConf, fd: RawFd) -> Result<()> {
+	let inet: &[libc::c_int] = &[libc::AF_INET, libc::AF_INET6];
+	let unix: &[libc::c_int] = &[libc::AF_UNIX];
+
+	let (kind, families, listening) = match config.listen.protocol {
+		Protocol::Tcp(_) => (libc::SOCK_STREAM, inet, true),
+		Protocol::Udp(_) => (libc::SOCK_DGRAM, inet, false),
+		Protocol::Unix(_) => (libc::SOCK_STREAM, unix, true),
+	};
+
+	if try!(socket_option(fd, libc::SO_TYPE)) != kind {
+		return Error::new("The socket type does not match the protocol").result();
+	}
+
+	let mut addr: libc::sockaddr_storage = unsafe { mem::zeroed() };
+	let mut len = mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+	if unsafe { libc::getsockname(fd, &mut addr as *mut libc::sockaddr_storage as *mut libc::sockaddr, &mut len) } < 0 {
+		return Error::new("Cannot get the socket address").because(io::Error::last_os_error()).result();
+	}
+
+	if !families.contains(&(addr.ss_family as libc::c_int)) {
+		return Error::new("The socket address family does not match the protocol").result();
+	}
+
+	if listening && try!(socket_option(fd, libc::SO_ACCEPTCONN)) == 0 {
+		return Error::new("The socket does not listen for connections").result();
+	}
+
+	Ok(())
+}
+
+
+/// Get the integer socket option `name` of the socket `fd`.
+fn socket_option(fd: RawFd, name: libc::c_int) -> Result<libc::c_int> {
+	let mut value: libc::c_int = 0;
+	let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+
+	if unsafe { libc::getsockopt(fd, libc::SOL_SOCKET, name, &mut value as *mut libc::c_int as *mut libc::c_void, &mut len) } < 0 {
+		return Error::new("Cannot get the socket option").because(io::Error::last_os_error()).result();
+	}
+
+	Ok(value)
+}
+
+
+/// Change the size of the queue connections wait to be accepted in. Listening again
+/// on the socket which listens already only changes the size of its queue.
+fn set_backlog(socket: &ServerProtocol, backlog: i32) -> Result<()> {
+	let fd = match *socket {
+		Protocol::Tcp(ref listener) => listener.as_raw_fd(),
+		Protocol::Unix(ref listener) => listener.as_raw_fd(),
+		Protocol::Udp(_) => return Ok(()),
+	};
+
+	if unsafe { libc::listen(fd, backlog) } < 0 {
+		return Error::new(format!("Cannot set the listen backlog to {}", backlog)).because(io::Error::last_os_error()).result();
+	}
+
+	Ok(())
+}
@@ -1,7 +1,19 @@
 mod conf;
 mod server;
+mod inherit;
+mod activation;
+mod listener;
+mod limiter;
 mod registry;
+mod reload;
+mod validate;
+mod tls;
 
 pub use self::conf::ServerConf;
 pub use self::server::Server;
+pub use self::inherit::{Inherited, INHERITED_ENV, READY_ENV};
+pub use self::listener::Listener;
+pub use self::limiter::Limiter;
 pub use self::registry::Registry;
+pub use self::reload::{Reload, Change};
+pub use self::validate::validate;
@@ -1,65 +1,96 @@
 use std::ops::Index;
 use std::sync::Arc;
-use std::path::Path;
 use wrust_io::mio;
-use wrust_io::mio::tcp::TcpListener;
-use wrust_io::mio::unix::UnixListener;
 use wrust_types::{Error, Result};
-use wrust_types::net::Protocol;
-use wrust_module::Category;
 use ::module::Factory;
-use super::{Server, ServerConf};
+use super::{Server, ServerConf, Listener, Inherited};
 
+/// Servers by tokens. The registry has the fixed number of slots so tokens
+/// of servers added while the loop runs never overlap with client tokens.
 pub struct Registry {
 	start_from: usize,
-	items: Vec<Arc<Server>>,
+	items: Vec<Option<Arc<Server>>>,
 }
 
 
 impl Registry {
-	pub fn new(start_from: usize) -> Registry {
+	pub fn new(start_from: usize, capacity: usize) -> Registry {
 		Registry {
 			start_from: start_from,
-			items: Vec::new(),
+			items: (0..capacity).map(|_| None).collect(),
 		}
 	}
 
-	pub fn add(&mut self, module_factory: &Factory, config: &ServerConf) -> Result<mio::Token> {
-		let socket = match config.listen.protocol {
-			Protocol::Tcp(ref _details) => {
-				let addr = try!(config.socket_address());
-
-				match TcpListener::bind(&addr) {
-					Ok(listener) => Protocol::Tcp(listener),
-					Err(msg) => return Error::new("TCP Server socket binding failed").because(msg).result()
-				}
-			},
-			Protocol::Unix(ref details) => {
-				let path = Path::new(&details.path);
-
-				match UnixListener::bind(&path) {
-					Ok(listener) => Protocol::Unix(listener),
-					Err(msg) => return Error::new("UNIX Server socket binding failed").because(msg).result()
-				}
-			},
-			_ => return Error::new("Cannot bind UDP socket because the protocol is unsupported").result()
+	/// Add the server which takes over the socket passed by systemd with the same name
+	/// or the inherited socket listening on the same address if there is one or binds a new one.
+	pub fn add(&mut self, module_factory: &Factory, config: &ServerConf, inherited: &mut Inherited) -> Result<mio::Token> {
+		let listener = if let Some(fd) = inherited.take_activated(config) {
+			try!(Listener::activate(module_factory, config, fd))
+		}
+		else if let Some((fd, activated)) = inherited.take(&config.listen) {
+			try!(Listener::inherit(module_factory, config, fd)).with_activated(activated)
+		}
+		else {
+			try!(Listener::bind(module_factory, config))
+		};
+
+		self.insert(listener)
+	}
+
+	/// Add the `listener` to the first free slot.
+	pub fn insert(&mut self, listener: Listener) -> Result<mio::Token> {
+		let index = match self.items.iter().position(|item| item.is_none()) {
+			Some(index) => index,
+			None => return Error::new(format!("Cannot add the Server to the Registry of {} servers", self.items.len())).result(),
 		};
 
-		let forward = try!(module_factory.produce(Category::Stream, &config.forward.name, &config.forward.xpath));
-		let token = mio::Token(self.start_from + self.items.len());
+		let token = mio::Token(self.start_from + index);
 
-		self.items.push(Arc::new(Server::new(token, config.clone(), socket, forward)));
+		self.items[index] = Some(Arc::new(try!(listener.into_server(token))));
 
 		Ok(token)
 	}
 
-	pub fn len(&self) -> usize {
+	/// Get the server if it is still in the registry.
+	pub fn get(&self, index: mio::Token) -> Option<&Arc<Server>> {
+		match self.items.get(index.as_usize()) {
+			Some(&Some(ref server)) => Some(server),
+			_ => None,
+		}
+	}
+
+	pub fn remove(&mut self, index: mio::Token) -> Option<Arc<Server>> {
+		match self.items.get_mut(index.as_usize()) {
+			Some(item) => item.take(),
+			None => None,
+		}
+	}
+
+	/// The number of tokens reserved for servers.
+	pub fn capacity(&self) -> usize {
 		self.items.len()
 	}
 
+	/// The number of free slots.
+	pub fn available(&self) -> usize {
+		self.items.iter().filter(|item| item.is_none()).count()
+	}
+
+	pub fn len(&self) -> usize {
+		self.items.len() - self.available()
+	}
+
+	/// Get tokens of servers in the registry.
+	pub fn tokens(&self) -> Vec<mio::Token> {
+		self.items
+			.iter()
+			.filter_map(|item| item.as_ref().map(|server| *server.token()))
+			.collect()
+	}
+
 	pub fn each<Func, R>(&self, mut func: Func) -> Option<R>
 		where Func: FnMut(&Server) -> Option<R> {
-		for item in &self.items {
+		for item in self.items.iter().filter_map(|item| item.as_ref()) {
 			let result = func(item);
 			if result.is_some() {
 				return result;
@@ -71,11 +102,10 @@ impl Registry {
 
 	pub fn then_with<F, Ctx, T>(&self, index: usize, context: &mut Ctx, mut func: F) -> Result<T>
 		where F: FnMut(&Arc<Server>, &mut Ctx) -> Result<T> {
-		if index < self.items.len() {
-			func(&self.items[index], context)
-		}
-		else {
-			Error::new(format!("Server index is out of bounds ({} not in [0;{}))", index, self.items.len())).result()
+		match self.items.get(index) {
+			Some(&Some(ref server)) => func(server, context),
+			Some(&None) => Error::new(format!("Server #{} does not exist", index)).result(),
+			None => Error::new(format!("Server index is out of bounds ({} not in [0;{}))", index, self.items.len())).result(),
 		}
 	}
 }
@@ -84,7 +114,10 @@ impl Index<usize> for Registry {
 	type Output = Arc<Server>;
 
 	fn index(&self, index: usize) -> &Self::Output {
-		&self.items[index]
+		match self.items[index] {
+			Some(ref server) => server,
+			None => panic!("Server #{} does not exist", index),
+		}
 	}
 }
 
@@ -92,6 +125,6 @@ impl Index<mio::Token> for Registry {
 	type Output = Arc<Server>;
 
 	fn index(&self, index: mio::Token) -> &Self::Output {
-		&self.items[index.as_usize()]
+		&self[index.as_usize()]
 	}
 }
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+use wrust_types::Result;
+use wrust_types::net::Protocol;
+use wrust_conf::Conf;
+use ::module::Factory;
+use super::{ServerConf, Listener};
+
+
+/// Change of the listener on configuration reload.
+pub enum Change {
+	/// The listener at the index in the previous configuration stays as it is.
+	Keep(usize),
+	/// The listener at the index in the previous configuration continues
+	/// on the same socket with new settings.
+	Replace(usize, Listener),
+	/// The new listener.
+	Bind(Listener),
+}
+
+
+/// Changes of listeners in the order of the new configuration. Listeners
+/// of the previous configuration which are not referred are removed.
+pub struct Reload {
+	changes: Vec<Change>,
+}
+
+
+impl Reload {
+	/// Compare the `previous` configuration with the new one, bind sockets of new listeners and
+	/// instantiate modules of listeners which settings or module settings changed. Listeners are
+	/// matched by the socket they listen on.
+	pub fn prepare(module_factory: &Factory, previous_config: &Conf, previous: &[ServerConf], config: &Conf, servers: &[ServerConf]) -> Result<Reload> {
+		let mut matched = vec![false; previous.len()];
+		let mut changes = Vec::new();
+
+		for server in servers {
+			let index = (0..previous.len()).find(|&index| !matched[index] && previous[index].listen == server.listen);
+
+			let change = match index {
+				Some(index) => {
+					matched[index] = true;
+
+					let unchanged = previous[index] == *server
+						&& previous_config.lookup(&previous[index].forward.xpath) == config.lookup(&server.forward.xpath);
+
+					if unchanged {
+						Ok(Change::Keep(index))
+					}
+					else {
+						Listener::configure(module_factory, server).map(|listener| Change::Replace(index, listener))
+					}
+				},
+				None => Listener::bind(module_factory, server).map(|listener| Change::Bind(listener)),
+			};
+
+			match change {
+				Ok(change) => changes.push(change),
+				Err(msg) => {
+					discard(changes);
+					return Err(msg);
+				},
+			};
+		}
+
+		Ok(Reload {
+			changes: changes,
+		})
+	}
+
+	pub fn changes(&self) -> &Vec<Change> {
+		&self.changes
+	}
+
+	/// Destructure `self` to changes.
+	pub fn into_changes(self) -> Vec<Change> {
+		self.changes
+	}
+
+	/// Drop changes which are not applied.
+	pub fn discard(self) {
+		discard(self.changes)
+	}
+}
+
+
+/// Remove socket files of UNIX listeners bound for the reload which failed.
+fn discard(changes: Vec<Change>) {
+	for change in changes {
+		if let Change::Bind(listener) = change {
+			if let Protocol::Unix(ref details) = listener.config().listen.protocol {
+				let _ = fs::remove_file(Path::new(&details.path));
+			}
+		}
+	}
+}
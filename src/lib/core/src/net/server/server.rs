@@ -1,14 +1,21 @@
 use std::fmt;
+use std::sync::Arc;
+use std::os::unix::io::{RawFd, AsRawFd};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use wrust_io::mio;
 use wrust_io::mio::tcp::TcpListener;
+use wrust_io::mio::udp::UdpSocket;
 use wrust_io::mio::unix::UnixListener;
+use wrust_io::tls::{ServerConfig, ServerSession};
+use wrust_types::{Error, Result};
 use wrust_types::net::Protocol;
 use wrust_types::net::connection::Descriptor;
-use wrust_module::stream::{Behavior, Intention, Flush};
-use super::ServerConf;
+use wrust_module::{Instance, datagram};
+use wrust_module::stream::{Behavior, Dispatcher, Intention, Flush, Session};
+use super::{ServerConf, Limiter};
 
 
-pub type ServerProtocol = Protocol<TcpListener, (), UnixListener>;
+pub type ServerProtocol = Protocol<TcpListener, UdpSocket, UnixListener>;
 
 
 #[allow(dead_code)]
@@ -17,6 +24,10 @@ pub struct Server {
 	config: ServerConf,
 	socket: ServerProtocol,
 	forward: ForwardProxy,
+	tls: Option<Arc<ServerConfig>>,
+	activated: bool,
+	limiter: Limiter,
+	datagram_seq: AtomicUsize,
 }
 
 
@@ -24,11 +35,11 @@ unsafe impl Sync for Server {}
 
 
 pub struct ForwardProxy {
-	instance: Box<Behavior>,
+	instance: Instance,
 }
 
 impl ForwardProxy {
-	pub fn new(instance: Box<Behavior>) -> ForwardProxy {
+	pub fn new(instance: Instance) -> ForwardProxy {
 		ForwardProxy {
 			instance: instance
 		}
@@ -36,35 +47,82 @@ impl ForwardProxy {
 }
 
 impl Behavior for ForwardProxy {
-	fn open(self: &Self, desc: &Descriptor) -> Intention {
-		self.instance
-			.open(desc)
+	fn attach(self: &Self, dispatcher: Arc<Dispatcher>) {
+		if let Instance::Stream(ref instance) = self.instance {
+			instance.attach(dispatcher)
+		}
+	}
+
+	fn open(self: &Self, desc: &Descriptor) -> (Intention, Session) {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.open(desc),
+			_ => (Intention::Close(Some(Error::new("Forward module is not a stream processing module"))), Box::new(())),
+		}
+	}
+
+	fn connected(self: &Self, desc: &Descriptor, result: Result<()>) -> (Intention, Session) {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.connected(desc, result),
+			_ => (Intention::Close(Some(Error::new("Forward module is not a stream processing module"))), Box::new(())),
+		}
+	}
+
+	fn read(self: &Self, desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.read(desc, session, buf),
+			_ => Intention::Close(Some(Error::new("Forward module is not a stream processing module"))),
+		}
 	}
 
-	fn read(self: &Self, desc: &Descriptor, buf: &Vec<u8>) -> Intention {
-		self.instance
-			.read(desc, buf)
+	fn eof(self: &Self, desc: &Descriptor, session: &mut Session) -> Intention {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.eof(desc, session),
+			_ => Intention::Close(Some(Error::new("Forward module is not a stream processing module"))),
+		}
+	}
+
+	fn timeout(self: &Self, desc: &Descriptor, session: &mut Session) -> Intention {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.timeout(desc, session),
+			_ => Intention::Close(Some(Error::new("Forward module is not a stream processing module"))),
+		}
 	}
 
-	fn write(self: &Self, desc: &Descriptor, buf: &mut Vec<u8>) -> (Intention, Flush) {
-		self.instance
-			.write(desc, buf)
+	fn write(self: &Self, desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush) {
+		match self.instance {
+			Instance::Stream(ref instance) => instance.write(desc, session, buf),
+			_ => (Intention::Close(Some(Error::new("Forward module is not a stream processing module"))), Flush::Auto),
+		}
+	}
+
+	fn close(self: &Self, desc: &Descriptor, session: Session) {
+		if let Instance::Stream(ref instance) = self.instance {
+			instance.close(desc, session)
+		}
 	}
+}
 
-	fn close(self: &Self, desc: &Descriptor) {
-		self.instance
-			.close(desc)
+impl datagram::Behavior for ForwardProxy {
+	fn receive(self: &Self, desc: &Descriptor, buf: &Vec<u8>, replies: &mut Vec<Vec<u8>>) -> Result<()> {
+		match self.instance {
+			Instance::Datagram(ref instance) => instance.receive(desc, buf, replies),
+			_ => Error::new("Forward module is not a datagram processing module").result(),
+		}
 	}
 }
 
 
 impl Server {
-	pub fn new(token: mio::Token, config: ServerConf, socket: ServerProtocol, forward: Box<Behavior>) -> Server {
+	pub fn new(token: mio::Token, config: ServerConf, socket: ServerProtocol, forward: Instance, tls: Option<Arc<ServerConfig>>, activated: bool, limiter: Limiter) -> Server {
 		Server {
 			token: token,
 			config: config,
 			socket: socket,
 			forward: ForwardProxy::new(forward),
+			tls: tls,
+			activated: activated,
+			limiter: limiter,
+			datagram_seq: AtomicUsize::new(0),
 		}
 	}
 
@@ -83,6 +141,52 @@ impl Server {
 	pub fn forward(&self) -> &ForwardProxy {
 		&self.forward
 	}
+
+	/// Test if the socket is passed by systemd socket activation.
+	pub fn is_activated(&self) -> bool {
+		self.activated
+	}
+
+	/// Get limits of client addresses.
+	pub fn limiter(&self) -> &Limiter {
+		&self.limiter
+	}
+
+	/// Create a new TLS session for the accepted client connection if the server terminates TLS.
+	pub fn tls_session(&self) -> Option<ServerSession> {
+		match self.tls {
+			Some(ref config) => Some(ServerSession::new(config)),
+			None => None,
+		}
+	}
+
+	/// Get the file descriptor of the socket.
+	pub fn raw_fd(&self) -> RawFd {
+		match self.socket {
+			Protocol::Tcp(ref listener) => listener.as_raw_fd(),
+			Protocol::Udp(ref socket) => socket.as_raw_fd(),
+			Protocol::Unix(ref listener) => listener.as_raw_fd(),
+		}
+	}
+
+	/// Duplicate the socket so another server can accept connections on it.
+	pub fn clone_socket(&self) -> Result<ServerProtocol> {
+		let socket = match self.socket {
+			Protocol::Tcp(ref listener) => listener.try_clone().map(Protocol::Tcp),
+			Protocol::Udp(ref socket) => socket.try_clone().map(Protocol::Udp),
+			Protocol::Unix(ref listener) => listener.try_clone().map(Protocol::Unix),
+		};
+
+		match socket {
+			Ok(socket) => Ok(socket),
+			Err(msg) => Error::new(format!("Cannot duplicate the socket of {:?}", self)).because(msg).result(),
+		}
+	}
+
+	/// Get the next identifier for a datagram received by the UDP server.
+	pub fn next_datagram_id(&self) -> u32 {
+		self.datagram_seq.fetch_add(1, Ordering::SeqCst) as u32
+	}
 }
 
 
@@ -0,0 +1,71 @@
+//! TLS server configuration loading
+
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use wrust_io::tls::{ServerConfig, Certificate, PrivateKey};
+use wrust_io::tls::internal::pemfile;
+use wrust_types::{Error, Result};
+use ::conf::TlsConf;
+
+
+/// Create TLS server configuration shared by all sessions of the server.
+pub fn server_config(conf: &TlsConf) -> Result<Arc<ServerConfig>> {
+	let certs = try!(load_certs(&conf.certificate));
+	let key = try!(load_private_key(&conf.private_key));
+
+	let mut config = ServerConfig::new();
+	config.set_single_cert(certs, key);
+
+	// Require clients to present the certificate signed by one of CAs given
+	if let Some(ref path) = conf.client_ca {
+		let roots = try!(load_certs(path));
+		config.set_client_auth_roots(roots, true);
+	}
+
+	Ok(Arc::new(config))
+}
+
+
+fn load_certs(path: &String) -> Result<Vec<Certificate>> {
+	let file = match File::open(path) {
+		Ok(file) => file,
+		Err(msg) => return Error::new(format!("Cannot open certificate file {}", path)).because(msg).result(),
+	};
+
+	match pemfile::certs(&mut BufReader::new(file)) {
+		Ok(ref certs) if certs.is_empty() => Error::new(format!("No certificates found in {}", path)).result(),
+		Ok(certs) => Ok(certs),
+		Err(_) => Error::new(format!("Cannot parse certificate file {}", path)).result(),
+	}
+}
+
+
+fn load_private_key(path: &String) -> Result<PrivateKey> {
+	// Try RSA keys first and then PKCS8 encoded keys
+	for pkcs8 in &[false, true] {
+		let file = match File::open(path) {
+			Ok(file) => file,
+			Err(msg) => return Error::new(format!("Cannot open private key file {}", path)).because(msg).result(),
+		};
+
+		let mut reader = BufReader::new(file);
+		let keys = if *pkcs8 {
+			pemfile::pkcs8_private_keys(&mut reader)
+		}
+		else {
+			pemfile::rsa_private_keys(&mut reader)
+		};
+
+		match keys {
+			Ok(mut keys) => {
+				if !keys.is_empty() {
+					return Ok(keys.remove(0));
+				}
+			},
+			Err(_) => return Error::new(format!("Cannot parse private key file {}", path)).result(),
+		};
+	}
+
+	Error::new(format!("No private keys found in {}", path)).result()
+}
@@ -0,0 +1,129 @@
+use std::path::Path;
+use wrust_types::net::Protocol;
+use wrust_conf::{Conf, FromConf, Validation};
+use wrust_module::Category;
+use ::conf::{ModuleConf, SocketConf, NetSocketConf, UnixSocketConf, TlsConf, TimeoutConf, LimitConf, AccessConf};
+use ::module::Factory;
+use super::ServerConf;
+
+
+/// Check the list of servers at `xpath` and record every problem found into `validation`.
+/// Servers which are valid are returned.
+pub fn validate(config: &Conf, xpath: &str, module_factory: &Factory, validation: &mut Validation) -> Vec<ServerConf> {
+	let count = match config.count(xpath) {
+		Some(count) => count,
+		None => {
+			validation.error(format!("Expected array at '{}' but found nothing", xpath));
+			return Vec::new();
+		},
+	};
+
+	let mut servers: Vec<(String, ServerConf)> = Vec::new();
+
+	for i in 0..count {
+		let item_xpath = format!("{}.[{}]", xpath, i);
+		if let Some(server) = validate_server(config, &item_xpath, module_factory, validation) {
+			servers.push((item_xpath, server));
+		}
+	}
+
+	// Listen addresses and names must be unique
+	for (i, &(ref item_xpath, ref server)) in servers.iter().enumerate() {
+		for &(ref other_xpath, ref other) in servers.iter().take(i) {
+			if server.listen == other.listen {
+				validation.error(format!("Listen address {} at '{}.listen' is already used at '{}.listen'", server.listen, item_xpath, other_xpath));
+			}
+
+			if server.name.is_some() && server.name == other.name {
+				validation.error(format!("Name at '{}.name' is already used at '{}.name'", item_xpath, other_xpath));
+			}
+		}
+	}
+
+	servers.into_iter().map(|(_, server)| server).collect()
+}
+
+
+fn validate_server(config: &Conf, xpath: &str, module_factory: &Factory, validation: &mut Validation) -> Option<ServerConf> {
+	let xpath = match config.resolve_reference(xpath) {
+		Some(path) => path,
+		None => {
+			validation.error(format!("Reference or group is not found at '{}'", xpath));
+			return None;
+		},
+	};
+
+	let errors = validation.len();
+
+	validation.unknown_keys(config, &xpath, ServerConf::keys());
+
+	// Check every part of the server so all problems are found
+	let listen_xpath = format!("{}.listen", xpath);
+	let listen = validation.check(SocketConf::from_conf(config, &listen_xpath));
+	if let Some(ref listen) = listen {
+		let listen_xpath = config.resolve_reference(&listen_xpath).unwrap_or(listen_xpath);
+
+		match listen.protocol {
+			Protocol::Tcp(_) | Protocol::Udp(_) => validation.unknown_keys(config, &listen_xpath, NetSocketConf::keys()),
+			Protocol::Unix(ref details) => {
+				validation.unknown_keys(config, &listen_xpath, UnixSocketConf::keys());
+
+				// The socket file is created in the directory which should exist
+				match Path::new(&details.path).parent() {
+					Some(directory) if directory != Path::new("") && !directory.is_dir() => {
+						validation.error(format!("Directory {} does not exist at '{}.path'", directory.display(), listen_xpath));
+					},
+					_ => (),
+				};
+			},
+		};
+	}
+
+	let forward_xpath = format!("{}.forward", xpath);
+	if let Some(forward) = validation.check(ModuleConf::from_conf(config, &forward_xpath)) {
+		if let Some(ref listen) = listen {
+			let category = if listen.protocol.is_udp() {
+				Category::Datagram
+			}
+			else {
+				Category::Stream
+			};
+
+			if !module_factory.is_registered(category, &forward.module) {
+				validation.error(format!("Module {:?}:{} is not registered at '{}'", category, forward.module, forward_xpath));
+			}
+		}
+	}
+
+	let tls_xpath = format!("{}.tls", xpath);
+	if config.lookup(&tls_xpath).is_some() {
+		validation.check(TlsConf::from_conf(config, &tls_xpath));
+		validation.unknown_keys(config, &tls_xpath, TlsConf::keys());
+	}
+
+	let timeout_xpath = format!("{}.timeout", xpath);
+	if config.lookup(&timeout_xpath).is_some() {
+		validation.check(TimeoutConf::from_conf(config, &timeout_xpath));
+		validation.unknown_keys(config, &timeout_xpath, TimeoutConf::keys());
+	}
+
+	let limits_xpath = format!("{}.limits", xpath);
+	if config.lookup(&limits_xpath).is_some() {
+		validation.check(LimitConf::from_conf(config, &limits_xpath));
+		validation.unknown_keys(config, &limits_xpath, LimitConf::keys());
+	}
+
+	let access_xpath = format!("{}.access", xpath);
+	if config.lookup(&access_xpath).is_some() {
+		validation.check(AccessConf::from_conf(config, &access_xpath));
+		validation.unknown_keys(config, &access_xpath, AccessConf::keys());
+	}
+
+	// Parse the server as whole for checks across its parts when the parts are valid
+	if validation.len() > errors {
+		None
+	}
+	else {
+		validation.check(ServerConf::from_conf(config, &xpath))
+	}
+}
@@ -1,5 +1,6 @@
 //! Client socket I/O operation `Queue`.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
@@ -23,8 +24,16 @@ pub enum Parcel {
 	Open { server: Arc<Server>, client: Arc<Client> },
 	/// Close connection.
 	Close { server: Arc<Server>, client: Arc<Client> },
+	/// The outbound connection is established or failed.
+	Connect { server: Arc<Server>, client: Arc<Client> },
+	/// The stream processing module timer expired.
+	Timeout { server: Arc<Server>, client: Arc<Client> },
 	/// I/O ready event.
 	Ready { server: Arc<Server>, client: Arc<Client>, events: mio::EventSet },
+	/// The outbound connection failed to establish because of `reason`.
+	Abort { server: Arc<Server>, client: Arc<Client>, reason: String },
+	/// Datagram received by the UDP server from the `peer`.
+	Datagram { server: Arc<Server>, peer: SocketAddr, data: Vec<u8> },
 }
 
 
@@ -1,17 +1,20 @@
 //! Client socket I/O operation `Worker`. 
 
-use std::io::Write;
+use std::io::{self, Read, Write};
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::Ordering;
 use std::thread;
 use wrust_async::crossbeam::sync::chase_lev::Steal;
 use wrust_io::mio::{TryRead, TryWrite, EventSet};
+use wrust_io::tls::{Session as TlsSession, ServerSession};
 use wrust_types::{Result, Error};
 use wrust_types::net::Protocol;
-use wrust_types::net::connection::State;
-use wrust_module::stream::{Behavior, Intention, Flush};
+use wrust_types::net::connection::{State, Descriptor};
+use wrust_module::datagram;
+use wrust_module::stream::{Behavior, Intention, Flush, Session};
 use ::net::{EventChannel, Request};
-use ::net::client::{Client, LeftData};
+use ::net::client::{Client, ClientProtocol, LeftData};
 use ::net::server::Server;
 use super::{Queue, Parcel};
 
@@ -59,25 +62,50 @@ impl Worker {
 								trace!("{} -> {:?} closes {:?}", id, *server, *client);
 								Worker::close(&server, &client, &event_channel);
 							},
+							Parcel::Connect { server, client } => {
+								trace!("{} -> {:?} connects {:?}", id, *server, *client);
+								Worker::connected(&server, &client, &event_channel);
+							},
+							Parcel::Timeout { server, client } => {
+								trace!("{} -> {:?} times {:?} out", id, *server, *client);
+								Worker::timeout(&server, &client, &event_channel);
+							},
+							Parcel::Abort { server, client, reason } => {
+								trace!("{} -> {:?} aborts {:?}", id, *server, *client);
+								Worker::abort(&server, &client, reason, &event_channel);
+							},
 							Parcel::Ready { server, client, events } => {
 								trace!("{} -> {:?} processes {:?} for {:?}", id, *server, *client, events);
 
+								// Peers which reset or close connections can report only the error or
+								// the hang up event. The I/O operation of the state fails then and
+								// the connection is closed.
 								match client.state() {
+									State::Handshaking => {
+										Worker::handshake(&server, &client, events, &event_channel);
+									},
 									State::Reading => {
-										assert!(events.is_readable(), "unexpected events; events={:?}", events);
 										Worker::read(&server, &client, &event_channel);
 									},
-									State::Writing => {
-										assert!(events.is_writable(), "unexpected events; events={:?}", events);
+									State::Writing | State::Flushing => {
 										Worker::write(&server, &client, &event_channel);
 									},
-									State::Flushing => {
-										assert!(events.is_writable(), "unexpected events; events={:?}", events);
-										Worker::write(&server, &client, &event_channel);
+									State::Opened => {
+										// The connection waits for the stream processing module
+										// to wake it so only the error or the hang up is reported
+										debug!("{:?} is closed because it gets {:?} while waiting", *client, events);
+										Worker::close(&server, &client, &event_channel);
+									},
+									state => {
+										error!("{:?} is closed because it is not expected to get {:?} in the {} state", *client, events, state);
+										Worker::close(&server, &client, &event_channel);
 									},
-									_ => unimplemented!(),
 								};
 							},
+							Parcel::Datagram { server, peer, data } => {
+								trace!("{} -> {:?} receives {} bytes from {}", id, *server, data.len(), peer);
+								Worker::datagram(&server, &peer, &data);
+							},
 						};
 					},
 				};
@@ -96,12 +124,35 @@ impl Worker {
 	}
 
 	fn open(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
+		// Secured connections must complete the TLS handshake before
+		// the stream processing module knows about them
+		if client.is_tls() {
+			client.set_state(State::Handshaking);
+
+			event_channel
+				.send(Request::Open {
+						client_token: *client.token(),
+						events: State::Handshaking.as_event_set(),
+					})
+				.unwrap();
+		}
+		else {
+			Worker::start(server, client, event_channel, false);
+		}
+	}
+
+	fn start(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel, registered: bool) {
 		// Ask the stream processing module what to do next
-		let further_action = server.forward()
+		let (further_action, session) = server.forward()
 			.open(client.descriptor());
 
+		Worker::begin(client, event_channel, further_action, session, registered);
+	}
+
+	fn begin(client: &Arc<Client>, event_channel: &EventChannel, further_action: Intention, session: Session, registered: bool) {
 		// Close the client connection if the stream processing module said to
-		// or register in the event loop
+		// or register in the event loop. The session is kept only for connections
+		// the stream processing module is going to serve.
 		if let Intention::Close(err) = further_action {
 			if err.is_some() {
 				error!("{}", err.unwrap());
@@ -112,22 +163,153 @@ impl Worker {
 				.unwrap();
 		}
 		else {
+			client.set_session(session);
+
 			// Change the client state
 			client.set_state(further_action.as_state());
 
+			let request = if registered {
+				Request::Wait {
+					client_token: *client.token(),
+					events: further_action.as_event_set(),
+				}
+			}
+			else {
+				Request::Open {
+					client_token: *client.token(),
+					events: further_action.as_event_set(),
+				}
+			};
+
 			event_channel
-				.send(Request::Open {
-						client_token: *client.token(),
-						events: further_action.as_event_set(),
-					})
+				.send(request)
 				.unwrap();
 		};
 	}
 
+	fn connected(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
+		// The socket becomes writable when the connection is established or failed
+		let connect_result = client.then_on_socket(|sock| -> Result<()> {
+			let socket_error = match sock {
+				&mut Protocol::Tcp(ref stream) => stream.take_socket_error(),
+				&mut Protocol::Unix(_) => Ok(()),
+				_ => return Error::new("Cannot connect because UDP has no client connections").result()
+			};
+
+			match socket_error {
+				Ok(_) => Ok(()),
+				Err(msg) => Error::new("Cannot establish the outbound connection").because(msg).result()
+			}
+		});
+
+		match connect_result {
+			Ok(_) => {
+				// Ask the stream processing module what to do next
+				let (further_action, session) = server.forward()
+					.connected(client.descriptor(), Ok(()));
+
+				Worker::begin(client, event_channel, further_action, session, true);
+			},
+			Err(msg) => {
+				debug!("{:?} failed because {}", client, msg);
+				Worker::fail(server, client, msg, event_channel);
+			},
+		};
+	}
+
+	fn abort(server: &Arc<Server>, client: &Arc<Client>, reason: String, event_channel: &EventChannel) {
+		Worker::fail(server, client, Error::new(reason), event_channel);
+	}
+
+	fn fail(server: &Arc<Server>, client: &Arc<Client>, err: Error, event_channel: &EventChannel) {
+		// Let the stream processing module know the outbound connection failed
+		let _ = server.forward()
+			.connected(client.descriptor(), Err(err));
+
+		event_channel
+			.send(Request::Close { client_token: *client.token() })
+			.unwrap();
+	}
+
+	fn handshake(server: &Arc<Server>, client: &Arc<Client>, events: EventSet, event_channel: &EventChannel) {
+		// Drive the TLS handshake with data available
+		let handshake_result = client.then_on_tls(|sock, session| -> Result<bool> {
+			if events.is_readable() || events.is_hup() || events.is_error() {
+				let (_, eof) = try!(Worker::tls_read_packets(sock, session));
+				if eof {
+					return Error::new("The client closed the connection during the TLS handshake").result();
+				}
+			}
+
+			try!(Worker::tls_write_packets(sock, session));
+
+			Ok(session.is_handshaking())
+		});
+
+		match handshake_result {
+			Ok(true) => {
+				// The handshake is still in progress
+				let mut events = EventSet::readable();
+				if client.tls_wants_write() {
+					events = events | EventSet::writable();
+				}
+
+				event_channel
+					.send(Request::Wait {
+							client_token: *client.token(),
+							events: events,
+						})
+					.unwrap();
+			},
+			Ok(false) => {
+				// The handshake is complete so the stream processing module can start
+				let (further_action, session) = server.forward()
+					.open(client.descriptor());
+
+				// Plaintext which arrived along with the end of the handshake is in the session
+				// already and no I/O events come for it so the module gets it right away
+				if further_action == Intention::Read {
+					let mut buf = Vec::new();
+					let read_result = client.then_on_tls(|_, session| match session.read_to_end(&mut buf) {
+						Ok(_) => Ok(()),
+						Err(msg) => Error::new("Cannot read from TLS session").because(msg).result(),
+					});
+
+					if let Err(msg) = read_result {
+						debug!("{:?} is closed because {}", client, msg);
+						client.set_session(session);
+						Worker::close(server, client, event_channel);
+						return;
+					}
+
+					if !buf.is_empty() {
+						client.set_session(session);
+						client.set_state(State::Reading);
+						Worker::transfer(server, client, buf.len());
+						Worker::received(server, client, event_channel, &buf);
+						return;
+					}
+				}
+
+				Worker::begin(client, event_channel, further_action, session, true);
+			},
+			Err(msg) => {
+				error!("{}", msg);
+
+				event_channel
+					.send(Request::Close { client_token: *client.token() })
+					.unwrap();
+			},
+		};
+	}
+
 	fn close(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
-		// Ask the stream processing to free resources associated with the connection
-		server.forward()
-			.close(client.descriptor());
+		// Ask the stream processing to free resources associated with the connection.
+		// Connections without the session are not known to the module yet.
+		if let Some(session) = client.take_session() {
+			server.forward()
+				.close(client.descriptor(), session);
+		}
 
 		// Send the event loop request to close the connection
 		event_channel
@@ -135,7 +317,35 @@ impl Worker {
 			.unwrap();
 	}
 
-	fn reregister(client: &Arc<Client>, event_channel: &EventChannel, intention: Intention) {
+	fn timeout(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
+		// Ask the stream processing module what to do next
+		let further_action = match client.then_on_session(|session| server.forward().timeout(client.descriptor(), session)) {
+			Ok(further_action) => further_action,
+			Err(msg) => Intention::Close(Some(msg)),
+		};
+
+		// Data left unwritten goes first so the connection keeps its state
+		// and waits until it can write again
+		let closing = match further_action {
+			Intention::Close(_) => true,
+			_ => false,
+		};
+
+		if !closing && client.has_left_data() {
+			event_channel
+				.send(Request::Wait {
+						client_token: *client.token(),
+						events: EventSet::writable(),
+					})
+				.unwrap();
+
+			return;
+		}
+
+		Worker::reregister(server, client, event_channel, further_action);
+	}
+
+	fn reregister(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel, intention: Intention) {
 		// Close the client connection if the stream processing module said to
 		// or reregister in the event loop
 		if let Intention::Close(err) = intention {
@@ -143,9 +353,7 @@ impl Worker {
 				error!("{}", err.unwrap());
 			}
 
-			event_channel
-				.send(Request::Close { client_token: *client.token() })
-				.unwrap();
+			Worker::close(server, client, event_channel);
 		}
 		else {
 			// Change the client state
@@ -161,6 +369,10 @@ impl Worker {
 	}
 
 	fn read(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
+		if Worker::delay(server, client, event_channel, EventSet::readable()) {
+			return;
+		}
+
 		// Read data from the socket
 		let mut buf: Vec<u8> = Vec::new();
 		let read_result = Worker::try_read_buf(client, &mut buf);
@@ -176,22 +388,34 @@ impl Worker {
 				// Change the client state
 				client.set_state(State::Flushing);
 
-				event_channel
-					.send(Request::Wait {
-							client_token: *client.token(),
-							events: EventSet::writable(),
-						})
-					.unwrap();
+				// The stream processing module decides whether it writes
+				// the data left or waits for more data to write
+				let further_action = match client.then_on_session(|session| server.forward().eof(client.descriptor(), session)) {
+					Ok(further_action) => further_action,
+					Err(msg) => Intention::Close(Some(msg)),
+				};
+
+				match further_action {
+					Intention::Write => {
+						event_channel
+							.send(Request::Wait {
+									client_token: *client.token(),
+									events: EventSet::writable(),
+								})
+							.unwrap();
+					},
+					Intention::Read => {
+						// Read channel is closed so further reading has no reason
+						Worker::close(server, client, event_channel);
+					},
+					further_action => {
+						Worker::reregister(server, client, event_channel, further_action);
+					},
+				};
 			},
-			Ok(Some(_)) => {
-				// Pass read data to the stream processing module
-				let further_action = server.forward()
-					.read(client.descriptor(), &mut buf);
-
-				// Re-register the socket with the event loop. The current
-				// state is used to determine whether we are currently reading
-				// or writing.
-				Worker::reregister(client, event_channel, further_action);
+			Ok(Some(count)) => {
+				Worker::transfer(server, client, count);
+				Worker::received(server, client, event_channel, &buf);
 			},
 			Ok(None) => {
 				event_channel
@@ -201,13 +425,32 @@ impl Worker {
 						})
 					.unwrap();
 			},
-			Err(e) => {
-				panic!("got an error trying to read; err={:?}", e);
+			Err(msg) => {
+				// The connection cannot go on after the socket or the TLS session failed
+				debug!("{:?} is closed because {}", client, msg);
+				Worker::close(server, client, event_channel);
 			}
 		}
 	}
 
+	fn received(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel, buf: &Vec<u8>) {
+		// Pass read data to the stream processing module
+		let further_action = match client.then_on_session(|session| server.forward().read(client.descriptor(), session, buf)) {
+			Ok(further_action) => further_action,
+			Err(msg) => Intention::Close(Some(msg)),
+		};
+
+		// Re-register the socket with the event loop. The current
+		// state is used to determine whether we are currently reading
+		// or writing.
+		Worker::reregister(server, client, event_channel, further_action);
+	}
+
 	fn write(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel) {
+		if Worker::delay(server, client, event_channel, EventSet::writable()) {
+			return;
+		}
+
 		// If there is data left unwritten since the last write operation
 		// then we try to write it before we get data from the stream processing module
 		// and write to the stream
@@ -221,8 +464,10 @@ impl Worker {
 			None => {
 				// Get the new chunk of data from the module
 				let mut buf = Vec::new();
-				let further_action = server.forward()
-					.write(client.descriptor(), &mut buf);
+				let further_action = match client.then_on_session(|session| server.forward().write(client.descriptor(), session, &mut buf)) {
+					Ok(further_action) => further_action,
+					Err(msg) => (Intention::Close(Some(msg)), Flush::Auto),
+				};
 
 				(buf, further_action)
 			}
@@ -233,23 +478,32 @@ impl Worker {
 		// Check the result of the I/O operation
 		match write_result {
 			Ok(Some(n)) => {
+				Worker::transfer(server, client, n);
+
 				if n < buf.len() {
 					// Not all data has been written. Drain the written part and
 					// left unwritten data for future write tries.
 					buf.drain(0..n);
 					client.set_left_data(Some(LeftData::new(buf, further_action.0, further_action.1)));
-					Worker::reregister(client, event_channel, Intention::Write);
+					Worker::reregister(server, client, event_channel, Intention::Write);
 				}
 				else {
+					// Encrypted data can be left buffered in the TLS session and it
+					// must be written before the connection goes further
+					if client.tls_wants_write() {
+						client.set_left_data(Some(LeftData::new(Vec::new(), further_action.0, further_action.1)));
+						Worker::reregister(server, client, event_channel, Intention::Write);
+
+						return;
+					}
+
 					// When one half of the socket is closed valid intentions
 					// only are Close ot Write.
 					if client.state() == State::Flushing {
 						if further_action.0 == Intention::Read {
 							// Read channel is closed at the moment so further reading
 							// has no reason. Closing the connection.
-							event_channel
-								.send(Request::Close { client_token: *client.token() })
-								.unwrap();
+							Worker::close(server, client, event_channel);
 
 							return;
 						}
@@ -257,14 +511,16 @@ impl Worker {
 
 					// Force flush buffered data because the modele asked for that
 					if further_action.1 == Flush::Force {
-						match Worker::try_flush(client) {
-							Err(msg) => error!("{}", msg),
-							Ok(_) => {}
-						};
+						if let Err(msg) = Worker::try_flush(client) {
+							debug!("{:?} is closed because {}", client, msg);
+							Worker::close(server, client, event_channel);
+
+							return;
+						}
 					}
 
 					// Re-register the socket with the event loop.
-					Worker::reregister(client, event_channel, further_action.0);
+					Worker::reregister(server, client, event_channel, further_action.0);
 				}
 			}
 			Ok(None) => {
@@ -277,13 +533,101 @@ impl Worker {
 						})
 					.unwrap();
 			}
-			Err(e) => {
-				panic!("got an error trying to write; err={:?}", e);
+			Err(msg) => {
+				// Data left unwritten is dropped along with the connection
+				debug!("{:?} is closed because {}", client, msg);
+				Worker::close(server, client, event_channel);
+			}
+		}
+	}
+
+	/// Let the client connection wait for `events` later if its address spent the bandwidth
+	/// allowed. Returns `true` if the connection is delayed.
+	fn delay(server: &Arc<Server>, client: &Arc<Client>, event_channel: &EventChannel, events: EventSet) -> bool {
+		let delay = match client.descriptor().addr() {
+			Some(addr) if !client.descriptor().is_outbound() => server.limiter().delay(addr.ip()),
+			_ => None,
+		};
+
+		match delay {
+			Some(delay) => {
+				event_channel
+					.send(Request::Delay {
+							client_token: *client.token(),
+							events: events,
+							delay: delay,
+						})
+					.unwrap();
+
+				true
+			},
+			None => false,
+		}
+	}
+
+	/// Count bytes the client connection read or wrote against the bandwidth of its address.
+	fn transfer(server: &Arc<Server>, client: &Arc<Client>, count: usize) {
+		if let Some(addr) = client.descriptor().addr() {
+			if !client.descriptor().is_outbound() {
+				server.limiter().transfer(addr.ip(), count);
+			}
+		}
+	}
+
+	fn datagram(server: &Arc<Server>, peer: &SocketAddr, data: &Vec<u8>) {
+		let desc = Descriptor::new(server.next_datagram_id(), Some(*peer));
+
+		// Pass the datagram to the datagram processing module
+		let mut replies: Vec<Vec<u8>> = Vec::new();
+		if let Err(msg) = datagram::Behavior::receive(server.forward(), &desc, data, &mut replies) {
+			error!("{}", msg);
+		}
+
+		// Send replies to the originating peer
+		for reply in &replies {
+			if let Err(msg) = Worker::try_send_to(server, peer, reply) {
+				error!("{}", msg);
 			}
 		}
 	}
 
+	fn try_send_to(server: &Arc<Server>, peer: &SocketAddr, buf: &Vec<u8>) -> Result<()> {
+		match *server.socket() {
+			Protocol::Udp(ref socket) => match socket.send_to(buf, peer) {
+				Ok(Some(count)) if count == buf.len() => Ok(()),
+				Ok(Some(count)) => Error::new(format!("Datagram to {} is truncated to {} of {} bytes", peer, count, buf.len())).result(),
+				Ok(None) => Error::new(format!("Datagram to {} is dropped because the socket is not ready", peer)).result(),
+				Err(msg) => Error::new(format!("Cannot send datagram to {}", peer)).because(msg).result()
+			},
+			_ => Error::new("Cannot send datagram through non-UDP server socket").result()
+		}
+	}
+
 	fn try_read_buf(client: &Arc<Client>, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+		if client.is_tls() {
+			return client.then_on_tls(|sock, session| -> Result<Option<usize>> {
+				let (_, eof) = try!(Worker::tls_read_packets(sock, session));
+
+				// Take decrypted data
+				if let Err(msg) = session.read_to_end(buf) {
+					return Error::new("Cannot read from TLS session").because(msg).result();
+				}
+
+				// Send TLS records the session could produce while processing packets
+				try!(Worker::tls_write_packets(sock, session));
+
+				if !buf.is_empty() {
+					Ok(Some(buf.len()))
+				}
+				else if eof {
+					Ok(Some(0))
+				}
+				else {
+					Ok(None)
+				}
+			});
+		}
+
 		client.then_on_socket(|sock| -> Result<Option<usize>> {
 			match sock {
 				&mut Protocol::Tcp(ref mut stream) => match stream.try_read_buf(buf) {
@@ -294,12 +638,30 @@ impl Worker {
 					Ok(count) => Ok(count),
 					Err(msg) => Error::new("Cannot read from client socket").because(msg).result()
 				},
-				_ => Error::new("Cannot read from client socket because UDP is not supported").result()
+				_ => Error::new("Cannot read from client socket because UDP has no client connections").result()
 			}
 		})
 	}
 
 	fn try_write_buf(client: &Arc<Client>, buf: &mut Vec<u8>) -> Result<Option<usize>> {
+		if client.is_tls() {
+			return client.then_on_tls(|sock, session| -> Result<Option<usize>> {
+				// Data encrypted before must be written first
+				if !try!(Worker::tls_write_packets(sock, session)) {
+					return Ok(Some(0));
+				}
+
+				// Encrypt data and try to write it
+				if let Err(msg) = session.write_all(buf) {
+					return Error::new("Cannot write to TLS session").because(msg).result();
+				}
+
+				try!(Worker::tls_write_packets(sock, session));
+
+				Ok(Some(buf.len()))
+			});
+		}
+
 		client.then_on_socket(|sock| -> Result<Option<usize>> {
 			match sock {
 				&mut Protocol::Tcp(ref mut stream) => match stream.try_write(buf) {
@@ -310,7 +672,7 @@ impl Worker {
 					Ok(count) => Ok(count),
 					Err(msg) => Error::new("Cannot write to client socket").because(msg).result()
 				},
-				_ => Error::new("Cannot write to client socket because UDP is not supported").result()
+				_ => Error::new("Cannot write to client socket because UDP has no client connections").result()
 			}
 		})
 	}
@@ -326,8 +688,55 @@ impl Worker {
 					Ok(()) => Ok(()),
 					Err(msg) => Error::new("Cannot flush client socket").because(msg).result()
 				},
-				_ => Error::new("Cannot flush client socket because UDP is not supported").result()
+				_ => Error::new("Cannot flush client socket because UDP has no client connections").result()
 			}
 		})
 	}
+
+	/// Read all TLS records available in the socket into the session and process them.
+	/// Returns the number of bytes read and `true` if the peer closed the connection.
+	fn tls_read_packets(sock: &mut ClientProtocol, session: &mut ServerSession) -> Result<(usize, bool)> {
+		let mut total = 0;
+
+		loop {
+			let read_result = match sock {
+				&mut Protocol::Tcp(ref mut stream) => session.read_tls(stream),
+				&mut Protocol::Unix(ref mut stream) => session.read_tls(stream),
+				_ => return Error::new("Cannot read TLS records because UDP has no client connections").result()
+			};
+
+			match read_result {
+				Ok(0) => return Ok((total, true)),
+				Ok(count) => {
+					total += count;
+
+					if let Err(msg) = session.process_new_packets() {
+						return Error::new("Cannot process TLS records").because(msg).result();
+					}
+				},
+				Err(ref msg) if msg.kind() == io::ErrorKind::WouldBlock => return Ok((total, false)),
+				Err(msg) => return Error::new("Cannot read TLS records from client socket").because(msg).result()
+			};
+		}
+	}
+
+	/// Write TLS records pending in the session into the socket.
+	/// Returns `true` if all records are written.
+	fn tls_write_packets(sock: &mut ClientProtocol, session: &mut ServerSession) -> Result<bool> {
+		while session.wants_write() {
+			let write_result = match sock {
+				&mut Protocol::Tcp(ref mut stream) => session.write_tls(stream),
+				&mut Protocol::Unix(ref mut stream) => session.write_tls(stream),
+				_ => return Error::new("Cannot write TLS records because UDP has no client connections").result()
+			};
+
+			match write_result {
+				Ok(_) => (),
+				Err(ref msg) if msg.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+				Err(msg) => return Error::new("Cannot write TLS records to client socket").because(msg).result()
+			};
+		}
+
+		Ok(true)
+	}
 }
@@ -2,5 +2,7 @@
 
 extern crate bytes as bytes_dep;
 extern crate mio as mio_dep;
+extern crate rustls as rustls_dep;
 
 pub mod mio;
+pub mod tls;
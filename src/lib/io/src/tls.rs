@@ -0,0 +1 @@
+pub use rustls_dep::*;
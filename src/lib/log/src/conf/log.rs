@@ -4,6 +4,7 @@ use std::str::FromStr;
 use log::LogLevelFilter;
 use wrust_types::{Error, Result};
 use wrust_conf::{Conf, FromConf};
+use wrust_conf::schema::Field;
 
 
 /// Logging device
@@ -26,48 +27,49 @@ pub struct LogConf {
 }
 
 
+conf_schema! {
+	/// Logger settings as they are written in the config
+	pub struct LogSettings {
+		/// Logging device, `stderr` or `file`
+		device: String = Field::required(),
+		/// Logging level
+		level: String = Field::default("info".to_string()),
+		/// Colorize messages written to stderr
+		colorize: bool = Field::default(false),
+		/// Directory log files are written to
+		directory: Option<String> = Field::optional(),
+		/// Size of the log file it is rotated at
+		rotate_size: Option<usize> = Field::optional(),
+	}
+}
+
+
+impl LogConf {
+	/// Names of settings the logger reads.
+	pub fn keys() -> &'static [&'static str] {
+		LogSettings::keys()
+	}
+}
+
+
 impl FromConf for LogConf {
 	// Load settings from the config
 	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
-		// Check if core section exists
-		if None == config.lookup(xpath) {
-			return Error::new(format!("Group does not exist at path '{}'", xpath)).result();
-		}
-
-		// Get device option
-		let device = match config.lookup_str(&format!("{}.device", xpath)) {
-			Some(device) => device.trim().to_lowercase(),
-			None => return Error::new(format!("Logging device is required at '{}'", xpath)).result()
-		};
+		let settings = try!(LogSettings::from_conf(config, xpath));
 
+		let device = settings.device.trim().to_lowercase();
 		let log_device: LogDevice = if device == "stderr" {
-			// Get colorize option
-			let colorize = config.lookup_boolean_or(&format!("{}.colorize", xpath), false);
-
-			LogDevice::Stderr(colorize)
+			LogDevice::Stderr(settings.colorize)
 		}
 		else if device == "file" {
-			// Get directory option
-			let directory = match config.lookup_str(&format!("{}.directory", xpath)) {
-				Some(directory) => Some(directory.to_string()),
-				None => None
-			};
-
-			// Get rotate size
-			let rotate_size = match config.lookup_integer64(&format!("{}.rotate_size", xpath)) {
-				Some(size) => Some(size as usize),
-				None => None
-			};
-
-			LogDevice::File(directory, rotate_size)
+			LogDevice::File(settings.directory, settings.rotate_size)
 		}
 		else {
-			return Error::new(format!("Unknown logging device {} at '{}.device'", device, xpath)).result()	
+			return Error::new(format!("Unknown logging device {} at '{}.device'", device, xpath)).result()
 		};
 
 		// Get logging level
-		let level = config.lookup_str_or(&format!("{}.level", xpath), "info");
-		let log_level: LogLevelFilter = match LogLevelFilter::from_str(level) {
+		let log_level: LogLevelFilter = match LogLevelFilter::from_str(&settings.level) {
 			Ok(log_level) => log_level,
 			Err(_) => return Error::new(format!("Invalid loging level at '{}'.level", xpath)).result()
 		};
@@ -2,19 +2,40 @@ extern crate log;
 extern crate flexi_logger;
 extern crate ansi_term;
 extern crate wrust_types;
-extern crate wrust_conf;
+#[macro_use] extern crate wrust_conf;
 
 pub mod conf;
 
-use log::{LogLevel, LogRecord};
+use std::sync::Arc;
+use log::{LogLevel, LogLevelFilter, LogRecord, MaxLogLevelFilter};
 use wrust_types::{Error, Result};
 use wrust_conf::{Conf, FromConf};
 use conf::{LogConf, LogDevice};
 
 
+/// Handle which changes the logging level while the server runs.
+#[derive(Clone)]
+pub struct Level {
+	filter: Arc<MaxLogLevelFilter>,
+}
+
+
+impl Level {
+	/// Get the current logging level.
+	pub fn get(&self) -> LogLevelFilter {
+		self.filter.get()
+	}
+
+	/// Change the logging level.
+	pub fn set(&self, level: LogLevelFilter) {
+		self.filter.set(level)
+	}
+}
+
+
 /// Initialize logging system using configuration given
-pub fn init(config: LogConf) -> Result<()> {
-	use flexi_logger::{self, LogConfig};
+pub fn init(config: LogConf) -> Result<Level> {
+	use flexi_logger::{FlexiLogger, LogConfig};
 
 	// Start from the default logger configuration
 	let mut flexi_config = LogConfig::new();
@@ -40,15 +61,29 @@ pub fn init(config: LogConf) -> Result<()> {
 		},
 	};
 
+	// The logger passes records of all levels and the maximum level filter
+	// decides which records are logged so the level can be changed later
+	let logger = match FlexiLogger::new(Some(LogLevelFilter::Trace.to_string()), flexi_config) {
+		Ok(logger) => logger,
+		Err(_) => return Error::new("Logger initialization failed").result()
+	};
+
 	// Initialize logger
-	match flexi_logger::init(flexi_config, Some(config.level.to_string())) {
-		Ok(_) => Ok(()),
-		Err(_) => Error::new("Logger initialization failed").result()
+	let mut filter = None;
+	let result = log::set_logger(|max_level| {
+		max_level.set(config.level);
+		filter = Some(max_level);
+		Box::new(logger)
+	});
+
+	match (result, filter) {
+		(Ok(_), Some(filter)) => Ok(Level { filter: Arc::new(filter) }),
+		_ => Error::new("Logger initialization failed").result()
 	}
 }
 
 
-pub fn init_from_conf(config: &Conf, xpath: &str) -> Result<()> {
+pub fn init_from_conf(config: &Conf, xpath: &str) -> Result<Level> {
 	// Initialize logger
 	match LogConf::from_conf(&config, xpath) {
 		Ok(settings) => init(settings),
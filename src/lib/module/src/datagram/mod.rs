@@ -0,0 +1,13 @@
+//! Datagram processing module facility and behavior.
+
+use wrust_types::Result;
+use wrust_types::net::connection::Descriptor;
+
+/// Each datagram processing module must folow the `Behavior`.
+pub trait Behavior: Send + Sync {
+	/// A new datagram has been received into `buf` from the peer which address
+	/// is available through `desc`. The datagram processing module can push
+	/// zero or more reply datagrams into `replies` and they will be sent back
+	/// to the originating peer in the same order.
+	fn receive(self: &Self, desc: &Descriptor, buf: &Vec<u8>, replies: &mut Vec<Vec<u8>>) -> Result<()>;
+}
@@ -5,15 +5,38 @@ extern crate wrust_io;
 extern crate wrust_conf;
 
 pub mod stream;
+pub mod datagram;
 
 use wrust_conf::Conf;
 
 
 /// Module category
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Category {
 	/// Stream processing module
 	Stream,
+	/// Datagram processing module
+	Datagram,
+}
+
+
+/// Module instance produced by the module factory.
+pub enum Instance {
+	/// Stream processing module instance
+	Stream(Box<stream::Behavior>),
+	/// Datagram processing module instance
+	Datagram(Box<datagram::Behavior>),
+}
+
+
+impl Instance {
+	/// The category of the module instance.
+	pub fn category(&self) -> Category {
+		match *self {
+			Instance::Stream(_) => Category::Stream,
+			Instance::Datagram(_) => Category::Datagram,
+		}
+	}
 }
 
 
@@ -34,5 +57,3 @@ pub trait Facility {
 	/// The module category
 	fn category() -> Category;
 }
-
-
@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Instant;
+use wrust_types::Result;
+use wrust_types::net::Protocol;
+use wrust_conf::Conf;
+use super::Intention;
+
+
+/// Address of the outbound connection.
+pub type Address = Protocol<SocketAddr, (), PathBuf>;
+
+
+/// The way the stream processing module can ask the server core to act on connections.
+/// The `Dispatcher` is passed to the module with `Behavior::attach` once the server
+/// core is ready and it can be used from any thread.
+pub trait Dispatcher: Send + Sync {
+	/// Open a new non-blocking outbound connection to `addr`, optionally on behalf of
+	/// the connection identified with the identifier and serial in `owner`. The outbound
+	/// connection is processed by the same module like accepted ones, its `Descriptor`
+	/// is outbound and has `owner()` set if given. When the connection is established
+	/// or fails `Behavior::connected` is called. The connection fails if the owner is
+	/// closed before it is established. If `timeout` (in milliseconds) is not zero
+	/// the connection fails when it is not established in time.
+	fn connect(self: &Self, owner: Option<(u32, usize)>, addr: &Address, timeout: u64) -> Result<()>;
+
+	/// Wake the connection identified with `id` which is waiting for I/O events so it
+	/// continues with the `intention` given. Only `Intention::Write` and `Intention::Close`
+	/// are accepted, other intentions fail.
+	fn wake(self: &Self, id: u32, intention: Intention) -> Result<()>;
+
+	/// The same as `wake` but the request is ignored if the connection identified with `id`
+	/// is not the one with `serial` anymore. See `Waker` which is the handy way to do that.
+	fn wake_serial(self: &Self, id: u32, serial: usize, intention: Intention) -> Result<()>;
+
+	/// Ask the server core to call `Behavior::timeout` for the connection identified
+	/// with `id` and `serial` in `delay` milliseconds. The timer is dropped if the connection
+	/// is closed before. If the connection is processed at that moment the call is deferred
+	/// until the processing is done.
+	fn timer(self: &Self, id: u32, serial: usize, delay: u64) -> Result<()>;
+
+	/// Get the current configuration snapshot. The server publishes the new snapshot
+	/// when the configuration is reloaded so modules can follow settings at runtime.
+	fn config(self: &Self) -> Conf;
+
+	/// Ask the server core to call `Behavior::timeout` for the connection identified
+	/// with `id` and `serial` at the `deadline` given.
+	fn timer_at(self: &Self, id: u32, serial: usize, deadline: Instant) -> Result<()> {
+		let now = Instant::now();
+		let delay = if deadline > now {
+			let left = deadline - now;
+			left.as_secs() * 1000 + (left.subsec_nanos() / 1000000) as u64
+		}
+		else {
+			0
+		};
+
+		self.timer(id, serial, delay)
+	}
+}
@@ -12,6 +12,9 @@ pub enum Intention {
 	Read,
 	/// The stream processing module is going to write more data to the stream.
 	Write,
+	/// The stream processing module neither reads nor writes until it wakes
+	/// the stream with `Waker`.
+	Wait,
 	/// The stream processing module is going to close the stream.
 	Close(Option<Error>),
 }
@@ -23,6 +26,7 @@ impl Intention {
 		match *self {
 			Intention::Read => mio::EventSet::readable(),
 			Intention::Write => mio::EventSet::writable(),
+			Intention::Wait => mio::EventSet::none(),
 			Intention::Close(_) => mio::EventSet::none(),
 		}
 	}
@@ -32,6 +36,7 @@ impl Intention {
 		match *self {
 			Intention::Read => State::Reading,
 			Intention::Write => State::Writing,
+			Intention::Wait => State::Opened,
 			Intention::Close(_) => State::Closed,
 		}
 	}
@@ -43,6 +48,7 @@ impl fmt::Display for Intention {
 		match *self {
 			Intention::Read => write!(f, "Read"),
 			Intention::Write => write!(f, "Write"),
+			Intention::Wait => write!(f, "Wait"),
 			Intention::Close(Some(ref err)) => write!(f, "Close with error {}", err),
 			Intention::Close(None) => write!(f, "Close"),
 		}
@@ -65,6 +71,12 @@ impl cmp::PartialEq for Intention {
 					_ => false
 				}
 			},
+			Intention::Wait => {
+				match *other {
+					Intention::Wait => true,
+					_ => false
+				}
+			},
 			Intention::Close(_) => {
 				match *other {
 					Intention::Close(_) => true,
@@ -2,29 +2,74 @@
 
 mod intention;
 mod flush;
+mod dispatcher;
+mod waker;
 
+use std::any::Any;
+use std::sync::Arc;
+use wrust_types::Result;
 use wrust_types::net::connection::Descriptor;
 
 pub use self::intention::Intention;
 pub use self::flush::Flush;
+pub use self::dispatcher::{Dispatcher, Address};
+pub use self::waker::Waker;
+
+/// The per-connection state of the stream processing module. The session
+/// is created in `open` or `connected`, the server core keeps it with the connection
+/// and hands it back on every further call so no locking is needed to reach it.
+/// The module gets its own state back with `downcast_mut`.
+pub type Session = Box<Any + Send>;
 
 /// Each stream processing module must folow the `Behavior`.
 pub trait Behavior: Send + Sync {
+	/// The server core is ready and the stream processing module can keep
+	/// the `dispatcher` to ask the core to act on connections.
+	fn attach(self: &Self, _dispatcher: Arc<Dispatcher>) {
+	}
+
 	/// When a new client connection is accepted `open` method is executed where
-	/// the stream processing module must decide what it intents to do next.
+	/// the stream processing module must decide what it intents to do next
+	/// and create the session of the connection.
 	/// The processing module can close the client connection immediately
-	/// returning `Intention::Close` and in that case `close` method of
-	/// the implemented trait will not be called.
-	fn open(self: &Self, desc: &Descriptor) -> Intention;
+	/// returning `Intention::Close` and in that case the session is dropped and
+	/// `close` method of the implemented trait will not be called.
+	fn open(self: &Self, desc: &Descriptor) -> (Intention, Session);
+
+	/// The outbound connection requested with `Dispatcher::connect` is established
+	/// or failed what is reported with `result`. On success the stream processing module
+	/// decides what it intents to do next the same way as in `open`. On failure
+	/// the returned intention is ignored and the connection is dropped.
+	fn connected(self: &Self, desc: &Descriptor, result: Result<()>) -> (Intention, Session) {
+		match result {
+			Ok(_) => self.open(desc),
+			Err(err) => (Intention::Close(Some(err)), Box::new(())),
+		}
+	}
 
 	/// A new data chunk has been read from the client connection into `buf` and
 	/// the stream processing module can handle it.
-	fn read(self: &Self, desc: &Descriptor, buf: &Vec<u8>) -> Intention;
+	fn read(self: &Self, desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention;
+
+	/// The peer shut down its half of the connection and nothing more can be read.
+	/// By default data left is written and the connection is closed when the stream
+	/// processing module intents to read again. Returning `Intention::Wait` keeps
+	/// the connection open to write data which comes later.
+	fn eof(self: &Self, _desc: &Descriptor, _session: &mut Session) -> Intention {
+		Intention::Write
+	}
+
+	/// The timer requested with `Dispatcher::timer` expired and the stream processing
+	/// module decides what it intents to do next with the connection.
+	fn timeout(self: &Self, _desc: &Descriptor, _session: &mut Session) -> Intention {
+		Intention::Read
+	}
 
 	/// The stream processing module is ready to output some data in `buf`.
-	fn write(self: &Self, desc: &Descriptor, buf: &mut Vec<u8>) -> (Intention, Flush);
+	fn write(self: &Self, desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush);
 
 	/// The client connection is going to be close and the stream processing module has a chance
-	/// to free related resources.
-	fn close(self: &Self, desc: &Descriptor);
+	/// to free related resources kept out of the `session`. That happens also when the stream
+	/// processing module returns `Intention::Close` from `read` or `write`.
+	fn close(self: &Self, desc: &Descriptor, session: Session);
 }
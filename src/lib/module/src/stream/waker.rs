@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use wrust_types::Result;
+use wrust_types::net::connection::Descriptor;
+use super::{Dispatcher, Intention};
+
+
+/// The handle which lets the stream processing module reschedule the connection
+/// from any thread, e.g. when data for the client arrives asynchronously.
+/// The `Waker` refers to exactly one connection so it does nothing
+/// after the connection is closed even if its identifier is reused.
+#[derive(Clone)]
+pub struct Waker {
+	dispatcher: Arc<Dispatcher>,
+	id: u32,
+	serial: usize,
+}
+
+
+impl Waker {
+	/// Create a new `Waker` of the connection described with `desc`.
+	pub fn new(dispatcher: Arc<Dispatcher>, desc: &Descriptor) -> Waker {
+		Waker {
+			dispatcher: dispatcher,
+			id: desc.id(),
+			serial: desc.serial(),
+		}
+	}
+
+	/// Get identifier of the connection.
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+
+	/// Get the number which tells apart connections reusing the identifier.
+	pub fn serial(&self) -> usize {
+		self.serial
+	}
+
+	/// Reschedule the connection as writable so `Behavior::write` is called
+	/// without waiting for the client traffic.
+	pub fn wake(&self) -> Result<()> {
+		self.dispatcher.wake_serial(self.id, self.serial, Intention::Write)
+	}
+
+	/// Ask the server core to call `Behavior::timeout` for the connection in `delay` milliseconds.
+	pub fn timer(&self, delay: u64) -> Result<()> {
+		self.dispatcher.timer(self.id, self.serial, delay)
+	}
+
+	/// Close the connection.
+	pub fn close(&self) -> Result<()> {
+		self.dispatcher.wake_serial(self.id, self.serial, Intention::Close(None))
+	}
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::str::FromStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use ::{Error, Result};
+
+
+/// Range of IPv4 or IPv6 addresses written as the address and the prefix length,
+/// e.g. `10.0.0.0/8` or `2001:db8::/32`. The address alone is the range of one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cidr {
+	addr: IpAddr,
+	prefix: u8,
+}
+
+
+impl Cidr {
+	/// Create the range of addresses which share `prefix` bits with `addr`.
+	pub fn new(addr: IpAddr, prefix: u8) -> Result<Cidr> {
+		let bits = match addr {
+			IpAddr::V4(_) => 32,
+			IpAddr::V6(_) => 128,
+		};
+
+		if prefix > bits {
+			return Error::new(format!("Prefix length {} of {} is longer than {} bits", prefix, addr, bits)).result();
+		}
+
+		// Bits of the host part are dropped so equal ranges are equal
+		let addr = match addr {
+			IpAddr::V4(addr) => {
+				let octets = masked(&addr.octets(), prefix);
+				IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+			},
+			IpAddr::V6(addr) => {
+				let octets = masked(&addr.octets(), prefix);
+				let mut segments = [0u16; 8];
+				for (i, segment) in segments.iter_mut().enumerate() {
+					*segment = (octets[i * 2] as u16) << 8 | octets[i * 2 + 1] as u16;
+				}
+
+				IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3], segments[4], segments[5], segments[6], segments[7]))
+			},
+		};
+
+		Ok(Cidr {
+			addr: addr,
+			prefix: prefix,
+		})
+	}
+
+	pub fn addr(&self) -> &IpAddr {
+		&self.addr
+	}
+
+	pub fn prefix(&self) -> u8 {
+		self.prefix
+	}
+
+	/// Test if `ip` is in the range. IPv4 addresses mapped to IPv6 ones, as peers of
+	/// listeners on IPv6 addresses are seen, are in ranges of IPv4 addresses.
+	pub fn contains(&self, ip: &IpAddr) -> bool {
+		match (self.addr, *ip) {
+			(IpAddr::V4(ref range), IpAddr::V4(ref ip)) => masked(&ip.octets(), self.prefix) == range.octets().to_vec(),
+			(IpAddr::V6(ref range), IpAddr::V6(ref ip)) => masked(&ip.octets(), self.prefix) == range.octets().to_vec(),
+			(IpAddr::V4(_), IpAddr::V6(ref ip)) => match ipv4_mapped(ip) {
+				Some(ip) => self.contains(&IpAddr::V4(ip)),
+				None => false,
+			},
+			(IpAddr::V6(_), IpAddr::V4(_)) => false,
+		}
+	}
+}
+
+
+impl FromStr for Cidr {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Cidr> {
+		let mut parts = s.trim().splitn(2, '/');
+		let addr = parts.next().unwrap_or("");
+
+		let addr: IpAddr = match addr.parse() {
+			Ok(addr) => addr,
+			Err(_) => return Error::new(format!("Invalid IP address '{}' of range '{}'", addr, s)).result(),
+		};
+
+		let prefix = match parts.next() {
+			Some(prefix) => match prefix.parse() {
+				Ok(prefix) => prefix,
+				Err(_) => return Error::new(format!("Invalid prefix length '{}' of range '{}'", prefix, s)).result(),
+			},
+			None => match addr {
+				IpAddr::V4(_) => 32,
+				IpAddr::V6(_) => 128,
+			},
+		};
+
+		Cidr::new(addr, prefix)
+	}
+}
+
+
+impl fmt::Display for Cidr {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}/{}", self.addr, self.prefix)
+	}
+}
+
+
+/// Keep the first `prefix` bits of `octets` and clear others.
+fn masked(octets: &[u8], prefix: u8) -> Vec<u8> {
+	octets
+		.iter()
+		.enumerate()
+		.map(|(i, octet)| {
+			let bits = (prefix as usize).saturating_sub(i * 8);
+			if bits >= 8 {
+				*octet
+			}
+			else {
+				octet & !(0xffu8 >> bits)
+			}
+		})
+		.collect()
+}
+
+
+/// Get the IPv4 address `ip` is mapped from, e.g. `::ffff:192.0.2.1`.
+fn ipv4_mapped(ip: &Ipv6Addr) -> Option<Ipv4Addr> {
+	let octets = ip.octets();
+	if octets[..10].iter().any(|octet| *octet != 0) || octets[10] != 0xff || octets[11] != 0xff {
+		return None;
+	}
+
+	Some(Ipv4Addr::new(octets[12], octets[13], octets[14], octets[15]))
+}
+
+
+#[test]
+fn test_cidr() {
+	let ip = |s: &str| -> IpAddr { s.parse().unwrap() };
+
+	let range: Cidr = "10.1.2.3/8".parse().unwrap();
+	assert_eq!(range, "10.0.0.0/8".parse().unwrap());
+	assert_eq!(format!("{}", range), "10.0.0.0/8");
+	assert!(range.contains(&ip("10.200.0.1")));
+	assert!(range.contains(&ip("::ffff:10.0.0.1")));
+	assert!(!range.contains(&ip("11.0.0.1")));
+	assert!(!range.contains(&ip("::a00:1")));
+
+	let range: Cidr = "192.0.2.128/25".parse().unwrap();
+	assert!(range.contains(&ip("192.0.2.255")));
+	assert!(!range.contains(&ip("192.0.2.127")));
+
+	let range: Cidr = "2001:db8::/32".parse().unwrap();
+	assert!(range.contains(&ip("2001:db8:ffff::1")));
+	assert!(!range.contains(&ip("2001:db9::1")));
+	assert!(!range.contains(&ip("32.1.13.184")));
+
+	let single: Cidr = "::1".parse().unwrap();
+	assert_eq!(single.prefix(), 128);
+	assert!(single.contains(&ip("::1")));
+	assert!(!single.contains(&ip("::2")));
+
+	let all: Cidr = "0.0.0.0/0".parse().unwrap();
+	assert!(all.contains(&ip("203.0.113.7")));
+
+	assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+	assert!("10.0.0/8".parse::<Cidr>().is_err());
+	assert!("10.0.0.0/x".parse::<Cidr>().is_err());
+}
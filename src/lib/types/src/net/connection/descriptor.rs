@@ -6,8 +6,14 @@ use std::net::SocketAddr;
 pub struct Descriptor {
 	/// Identifier
 	id: u32,
+	/// Number which tells apart connections reusing the same identifier
+	serial: usize,
 	/// Peer address if available
 	addr: Option<SocketAddr>,
+	/// The connection is opened by the server
+	outbound: bool,
+	/// Identifier and serial of the connection which opened this outbound connection
+	owner: Option<(u32, usize)>,
 }
 
 
@@ -16,19 +22,55 @@ impl Descriptor {
 	pub fn new(id: u32, addr: Option<SocketAddr>) -> Descriptor {
 		Descriptor {
 			id: id,
+			serial: 0,
 			addr: addr,
+			outbound: false,
+			owner: None,
 		}
 	}
 
+	/// Create a new outbound connection `Descriptor`. If the connection is opened
+	/// on behalf of another connection its identifier and serial are the `owner`.
+	pub fn outbound(id: u32, addr: Option<SocketAddr>, owner: Option<(u32, usize)>) -> Descriptor {
+		Descriptor {
+			id: id,
+			serial: 0,
+			addr: addr,
+			outbound: true,
+			owner: owner,
+		}
+	}
+
+	/// Set the number which tells apart connections reusing the same identifier.
+	pub fn with_serial(mut self, serial: usize) -> Descriptor {
+		self.serial = serial;
+		self
+	}
+
 	/// Get identifier of the connection.
 	pub fn id(&self) -> u32 {
 		self.id
 	}
 
+	/// Get the number which tells apart connections reusing the same identifier.
+	pub fn serial(&self) -> usize {
+		self.serial
+	}
+
 	/// Get peer address of the connection.
 	pub fn addr(&self) -> Option<SocketAddr> {
 		self.addr
 	}
+
+	/// Get identifier and serial of the connection which opened this outbound connection.
+	pub fn owner(&self) -> Option<(u32, usize)> {
+		self.owner
+	}
+
+	/// Test if the connection is opened by the server.
+	pub fn is_outbound(&self) -> bool {
+		self.outbound
+	}
 }
 
 #[test]
@@ -40,4 +82,20 @@ fn test_descriptor() {
 
 	let addr = desc.addr();
 	assert_eq!(addr, None);
+
+	assert_eq!(desc.owner(), None);
+	assert_eq!(desc.is_outbound(), false);
+
+	let desc = Descriptor::outbound(2, None, Some((1, 5)));
+	assert_eq!(desc.owner(), Some((1, 5)));
+	assert_eq!(desc.is_outbound(), true);
+
+	let desc = Descriptor::outbound(3, None, None);
+	assert_eq!(desc.owner(), None);
+	assert_eq!(desc.is_outbound(), true);
+
+	assert_eq!(desc.serial(), 0);
+	let desc = desc.with_serial(7);
+	assert_eq!(desc.id(), 3);
+	assert_eq!(desc.serial(), 7);
 }
\ No newline at end of file
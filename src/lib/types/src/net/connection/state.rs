@@ -6,6 +6,10 @@ use wrust_io::mio;
 pub enum State {
 	/// Connection is opened and waiting for the further change state.
 	Opened,
+	/// Connection is performing the TLS handshake.
+	Handshaking,
+	/// Outbound connection is being established.
+	Connecting,
 	/// Connection is reading data from stream.
 	Reading,
 	/// Connection is writing data into the stream.
@@ -21,6 +25,8 @@ impl State {
 	/// Convert `self` to MIO `EventSet`.
 	pub fn as_event_set(&self) -> mio::EventSet {
 		match *self {
+			State::Handshaking => mio::EventSet::readable() | mio::EventSet::writable(),
+			State::Connecting => mio::EventSet::writable(),
 			State::Reading => mio::EventSet::readable(),
 			State::Writing | State::Flushing => mio::EventSet::writable(),
 			_ => mio::EventSet::none(),
@@ -32,6 +38,8 @@ impl fmt::Display for State {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		match *self {
 			State::Opened => write!(f, "Opened"),
+			State::Handshaking => write!(f, "Handshaking"),
+			State::Connecting => write!(f, "Connecting"),
 			State::Reading => write!(f, "Reading"),
 			State::Writing => write!(f, "Writing"),
 			State::Flushing => write!(f, "Flushing"),
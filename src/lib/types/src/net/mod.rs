@@ -1,6 +1,8 @@
 //! Networking types and utilities.
 
 mod protocol;
+mod cidr;
 pub mod connection;
 
-pub use self::protocol::Protocol;
\ No newline at end of file
+pub use self::protocol::Protocol;
+pub use self::cidr::Cidr;
\ No newline at end of file
@@ -22,7 +22,7 @@ const PROTOCOL_UNIX: &'static str = "UNIX";
 /// println!("UNIX address {:?}", unix_addr.unix().unwrap());
 /// ```
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Protocol<TcpData = (), UdpData = (), UnixData = ()> {
 	/// `Tcp` protocol with associated data of `TcpData` type
 	Tcp(TcpData),
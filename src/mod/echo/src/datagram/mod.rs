@@ -0,0 +1,59 @@
+use wrust_types::Result;
+use wrust_types::net::connection::Descriptor;
+use wrust_conf::Conf;
+use wrust_module::{Facility, Category};
+use wrust_module::datagram::Behavior;
+
+const MOD_NAME: &'static str = "echo_datagram";
+
+pub struct Module {
+	reverse: bool,
+}
+
+#[inline(never)]
+impl Facility for Module {
+	fn new(config: &Conf, xpath: &String) -> Self {
+		// Read configuration
+		let reverse = config.lookup_boolean_or(&format!("{}.reverse", xpath), false);
+
+		Module {
+			reverse: reverse,
+		}
+	}
+
+	fn name() -> String {
+		MOD_NAME.to_string()
+	}
+
+	fn version() -> String {
+		format!("{}.{}.{}", env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"), env!("CARGO_PKG_VERSION_PATCH")).to_string()
+	}
+
+	fn category() -> Category {
+		Category::Datagram
+	}
+}
+
+
+#[inline(never)]
+impl Behavior for Module {
+	fn receive(self: &Self, _desc: &Descriptor, buf: &Vec<u8>, replies: &mut Vec<Vec<u8>>) -> Result<()> {
+		let mut reply = buf.clone();
+
+		if self.reverse {
+			if let Some(c) = reply.pop() {
+				reply.reverse();
+				if c == b'\n' {
+					reply.push(c);
+				}
+				else {
+					reply.insert(0, c);
+				}
+			}
+		}
+
+		replies.push(reply);
+
+		Ok(())
+	}
+}
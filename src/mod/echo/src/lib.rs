@@ -4,5 +4,7 @@ extern crate wrust_conf;
 extern crate wrust_module;
 
 mod module;
+mod datagram;
 
 pub use self::module::Module;
+pub use self::datagram::Module as DatagramModule;
@@ -1,16 +1,12 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::sync::Mutex;
 use wrust_types::Error;
 use wrust_types::net::connection::Descriptor;
 use wrust_conf::Conf;
 use wrust_module::{Facility, Category};
-use wrust_module::stream::{Behavior, Intention, Flush};
+use wrust_module::stream::{Behavior, Intention, Flush, Session};
 
 const MOD_NAME: &'static str = "echo";
 
 pub struct Module {
-	client: Mutex<RefCell<HashMap<u32, Vec<u8>>>>,
 	reverse: bool,
 }
 
@@ -21,7 +17,6 @@ impl Facility for Module {
 		let reverse = config.lookup_boolean_or(&format!("{}.reverse", xpath), false);
 
 		Module {
-			client: Mutex::new(RefCell::new(HashMap::new())),
 			reverse: reverse,
 		}
 	}
@@ -42,20 +37,14 @@ impl Facility for Module {
 
 #[inline(never)]
 impl Behavior for Module {
-	fn open(self: &Self, desc: &Descriptor) -> Intention {
-		let cell = self.client.lock().unwrap();
-		cell.borrow_mut()
-			.insert(desc.id(), Vec::new());
+	fn open(self: &Self, _desc: &Descriptor) -> (Intention, Session) {
+		let client_buf: Vec<u8> = Vec::new();
 
-		Intention::Read
+		(Intention::Read, Box::new(client_buf))
 	}
 
-	fn read(self: &Self, desc: &Descriptor, buf: &Vec<u8>) -> Intention {
-		let cell = self.client.lock().unwrap();
-		let mut cell_buf = cell.borrow_mut();
-		let mut_buf = cell_buf.get_mut(&desc.id());
-
-		match mut_buf {
+	fn read(self: &Self, _desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention {
+		match session.downcast_mut::<Vec<u8>>() {
 			Some(client_buf) => {
 				client_buf.extend(buf.iter());
 
@@ -65,12 +54,8 @@ impl Behavior for Module {
 		}
 	}
 
-	fn write(self: &Self, desc: &Descriptor, buf: &mut Vec<u8>) -> (Intention, Flush) {
-		let cell = self.client.lock().unwrap();
-		let mut cell_buf = cell.borrow_mut();
-		let mut_buf = cell_buf.get_mut(&desc.id());
-
-		match mut_buf {
+	fn write(self: &Self, _desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush) {
+		match session.downcast_mut::<Vec<u8>>() {
 			Some(client_buf) => {
 				let should_close = match client_buf.first() {
 					Some(&b'Q') => true,
@@ -102,9 +87,6 @@ impl Behavior for Module {
 		}
 	}
 
-	fn close(self: &Self, desc: &Descriptor) {
-		let cell = self.client.lock().unwrap();
-		cell.borrow_mut()
-			.remove(&desc.id());
+	fn close(self: &Self, _desc: &Descriptor, _session: Session) {
 	}
 }
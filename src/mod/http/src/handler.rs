@@ -0,0 +1,43 @@
+//! HTTP request handlers.
+
+use request::Request;
+use response::Response;
+
+
+/// Each HTTP request handler must follow the `Handler`. The handler is shared
+/// between all worker threads so it must be thread-safe.
+pub trait Handler: Send + Sync {
+	/// Handle the complete `req`uest and build the response to it.
+	fn handle(self: &Self, req: &Request) -> Response;
+}
+
+
+/// The handler which answers every request with the same configured response.
+pub struct DefaultHandler {
+	content_type: String,
+	body: String,
+}
+
+
+impl DefaultHandler {
+	/// Create a new `DefaultHandler` which responds with `body` of `content_type` given.
+	pub fn new(content_type: String, body: String) -> DefaultHandler {
+		DefaultHandler {
+			content_type: content_type,
+			body: body,
+		}
+	}
+}
+
+
+impl Handler for DefaultHandler {
+	fn handle(self: &Self, req: &Request) -> Response {
+		match req.method() {
+			"GET" | "HEAD" => Response::new(200)
+				.header("Content-Type", self.content_type.clone())
+				.body(self.body.clone()),
+			_ => Response::new(405)
+				.header("Allow", "GET, HEAD"),
+		}
+	}
+}
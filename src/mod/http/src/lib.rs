@@ -0,0 +1,18 @@
+//! Wrustlr HTTP/1.1 stream processing module.
+
+extern crate wrust_types;
+extern crate wrust_io;
+extern crate wrust_conf;
+extern crate wrust_module;
+
+mod module;
+pub mod request;
+pub mod response;
+pub mod parser;
+pub mod handler;
+
+pub use self::module::Module;
+pub use self::request::Request;
+pub use self::response::Response;
+pub use self::parser::Parser;
+pub use self::handler::{Handler, DefaultHandler};
@@ -0,0 +1,202 @@
+use std::cmp;
+use std::io::Read;
+use wrust_types::Error;
+use wrust_types::net::connection::Descriptor;
+use wrust_conf::Conf;
+use wrust_module::{Facility, Category};
+use wrust_module::stream::{Behavior, Intention, Flush, Session};
+use parser::Parser;
+use response::Response;
+use handler::{Handler, DefaultHandler};
+
+const MOD_NAME: &'static str = "http";
+
+const DEFAULT_MAX_HEAD_SIZE: i64 = 8192;
+const DEFAULT_MAX_BODY_SIZE: i64 = 1048576;
+const DEFAULT_SERVER_NAME: &'static str = "Wrustlr";
+const DEFAULT_CONTENT_TYPE: &'static str = "text/plain; charset=utf-8";
+const DEFAULT_BODY: &'static str = "Hello from Wrustlr!\n";
+const STREAM_CHUNK_SIZE: u64 = 65536;
+
+/// The client connection state.
+struct Connection {
+	parser: Parser,
+	output: Vec<u8>,
+	stream: Option<(Box<Read + Send>, u64)>,
+	closing: bool,
+}
+
+pub struct Module {
+	handler: Box<Handler>,
+	max_head_size: usize,
+	max_body_size: usize,
+	server_name: String,
+	error: Option<String>,
+}
+
+impl Module {
+	/// Create a new instance of the module which dispatches requests to the `handler` given.
+	pub fn with_handler(config: &Conf, xpath: &String, handler: Box<Handler>) -> Module {
+		// Read configuration
+		let max_head_size = config.lookup_integer64_or(&format!("{}.max_head_size", xpath), DEFAULT_MAX_HEAD_SIZE);
+		let max_body_size = config.lookup_integer64_or(&format!("{}.max_body_size", xpath), DEFAULT_MAX_BODY_SIZE);
+		let server_name = config.lookup_str_or(&format!("{}.server_name", xpath), DEFAULT_SERVER_NAME);
+
+		// The module cannot fail to instantiate so configuration errors
+		// are reported when client connections are opened
+		let mut error = None;
+
+		if max_head_size < 1 {
+			error = Some(format!("Head size limit must be positive at '{}.max_head_size'", xpath));
+		}
+
+		if max_body_size < 1 {
+			error = Some(format!("Body size limit must be positive at '{}.max_body_size'", xpath));
+		}
+
+		Module {
+			handler: handler,
+			max_head_size: max_head_size as usize,
+			max_body_size: max_body_size as usize,
+			server_name: server_name.to_string(),
+			error: error,
+		}
+	}
+
+	/// Handle all complete requests buffered in the connection parser
+	/// and put responses into the connection output.
+	fn process(&self, conn: &mut Connection) {
+		// Pipelined requests wait until the streamed response is complete
+		while !conn.closing && conn.stream.is_none() {
+			let (mut resp, head_only, keep_alive) = match conn.parser.next() {
+				Ok(Some(req)) => (self.handler.handle(&req), req.method() == "HEAD", req.keep_alive()),
+				Ok(None) => break,
+				Err(msg) => (Response::new(400).header("Content-Type", "text/plain").body(format!("{}\n", msg)), false, false),
+			};
+
+			if !resp.has_header("Server") {
+				resp = resp.header("Server", self.server_name.clone());
+			}
+
+			resp.serialize(&mut conn.output, head_only, keep_alive);
+
+			if !head_only {
+				conn.stream = match resp.take_stream() {
+					Some((_, 0)) => None,
+					stream => stream,
+				};
+			}
+
+			if !keep_alive {
+				conn.closing = true;
+			}
+		}
+	}
+}
+
+#[inline(never)]
+impl Facility for Module {
+	fn new(config: &Conf, xpath: &String) -> Self {
+		let content_type = config.lookup_str_or(&format!("{}.content_type", xpath), DEFAULT_CONTENT_TYPE);
+		let body = config.lookup_str_or(&format!("{}.body", xpath), DEFAULT_BODY);
+
+		Module::with_handler(config, xpath, Box::new(DefaultHandler::new(content_type.to_string(), body.to_string())))
+	}
+
+	fn name() -> String {
+		MOD_NAME.to_string()
+	}
+
+	fn version() -> String {
+		format!("{}.{}.{}", env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"), env!("CARGO_PKG_VERSION_PATCH")).to_string()
+	}
+
+	fn category() -> Category {
+		Category::Stream
+	}
+}
+
+
+#[inline(never)]
+impl Behavior for Module {
+	fn open(self: &Self, _desc: &Descriptor) -> (Intention, Session) {
+		if let Some(ref msg) = self.error {
+			return (Intention::Close(Some(Error::new(msg.clone()))), Box::new(()));
+		}
+
+		let conn = Connection {
+			parser: Parser::new(self.max_head_size, self.max_body_size),
+			output: Vec::new(),
+			stream: None,
+			closing: false,
+		};
+
+		(Intention::Read, Box::new(conn))
+	}
+
+	fn read(self: &Self, _desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention {
+		match session.downcast_mut::<Connection>() {
+			Some(conn) => {
+				conn.parser.feed(buf);
+				self.process(conn);
+
+				if conn.output.is_empty() {
+					Intention::Read
+				}
+				else {
+					Intention::Write
+				}
+			},
+			None => Intention::Close(Some(Error::new("Client connection is undefined")))
+		}
+	}
+
+	fn write(self: &Self, _desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush) {
+		let conn = match session.downcast_mut::<Connection>() {
+			Some(conn) => conn,
+			None => return (Intention::Close(Some(Error::new("Client connection is undefined"))), Flush::Auto)
+		};
+
+		buf.append(&mut conn.output);
+
+		// Send the next piece of the streamed body
+		if let Some((mut reader, remaining)) = conn.stream.take() {
+			let mut chunk = Vec::new();
+			match reader.by_ref().take(cmp::min(STREAM_CHUNK_SIZE, remaining)).read_to_end(&mut chunk) {
+				Ok(0) => {
+					// The body is shorter than promised so the connection
+					// cannot be reused
+					conn.closing = true;
+				},
+				Ok(count) => {
+					buf.append(&mut chunk);
+
+					if remaining > count as u64 {
+						conn.stream = Some((reader, remaining - count as u64));
+					}
+				},
+				Err(_) => {
+					conn.closing = true;
+				},
+			};
+
+			// Proceed with pipelined requests when the body is done
+			if conn.stream.is_none() {
+				self.process(conn);
+			}
+		}
+
+		if conn.stream.is_some() || !conn.output.is_empty() {
+			(Intention::Write, Flush::Auto)
+		}
+		else if conn.closing {
+			(Intention::Close(None), Flush::Force)
+		}
+		else {
+			(Intention::Read, Flush::Force)
+		}
+	}
+
+	fn close(self: &Self, _desc: &Descriptor, _session: Session) {
+	}
+}
@@ -0,0 +1,382 @@
+//! Incremental HTTP/1.x request parser.
+
+use std::mem;
+use std::str;
+use wrust_types::{Error, Result};
+use request::Request;
+
+
+/// Parsing stage of the current request.
+#[derive(Debug)]
+enum Stage {
+	/// Waiting for the request line and headers.
+	Head,
+	/// Waiting for the body of the fixed length.
+	Body(Request, usize),
+	/// Waiting for the next chunk of the chunked body.
+	Chunked(Request),
+}
+
+
+/// Incremental HTTP/1.x request parser. Data is fed into the parser as it
+/// arrives from the client and complete requests are taken out one by one
+/// so pipelined requests are handled naturally.
+///
+/// # Examples
+///
+/// ```
+/// use wrust_mod_http::Parser;
+///
+/// let mut parser = Parser::new(8192, 1048576);
+/// parser.feed(b"GET / HTTP/1.1\r\nHost: local");
+/// assert!(parser.next().unwrap().is_none());
+///
+/// parser.feed(b"host\r\n\r\n");
+/// let req = parser.next().unwrap().unwrap();
+/// assert_eq!(req.header("Host"), Some("localhost"));
+/// ```
+#[derive(Debug)]
+pub struct Parser {
+	buf: Vec<u8>,
+	stage: Stage,
+	max_head_size: usize,
+	max_body_size: usize,
+}
+
+
+impl Parser {
+	/// Create a new `Parser` limiting the size of the request line with headers
+	/// to `max_head_size` and the size of the body to `max_body_size` bytes.
+	pub fn new(max_head_size: usize, max_body_size: usize) -> Parser {
+		Parser {
+			buf: Vec::new(),
+			stage: Stage::Head,
+			max_head_size: max_head_size,
+			max_body_size: max_body_size,
+		}
+	}
+
+	/// Append data received from the client.
+	pub fn feed(&mut self, data: &[u8]) {
+		self.buf.extend(data.iter());
+	}
+
+	/// Test if the parser has no buffered data.
+	pub fn is_empty(&self) -> bool {
+		self.buf.is_empty()
+	}
+
+	/// Take the next complete request. Returns `Ok(None)` if more data is required.
+	/// After an error the connection should be closed because the stream position is lost.
+	pub fn next(&mut self) -> Result<Option<Request>> {
+		loop {
+			match mem::replace(&mut self.stage, Stage::Head) {
+				Stage::Head => {
+					let end = match find(&self.buf, b"\r\n\r\n") {
+						Some(pos) => pos,
+						None => {
+							if self.buf.len() > self.max_head_size {
+								return Error::new("Request head is too large").result();
+							}
+
+							return Ok(None);
+						}
+					};
+
+					if end > self.max_head_size {
+						return Error::new("Request head is too large").result();
+					}
+
+					let head: Vec<u8> = self.buf.drain(0..end + 4).collect();
+					let request = try!(parse_head(&head[..end]));
+
+					// Determine how the body is framed. Only the chunked transfer coding
+					// is supported and the length given along with it makes the framing ambiguous.
+					let chunked = match request.header("Transfer-Encoding") {
+						Some(value) => {
+							if value.trim().to_lowercase() != "chunked" {
+								return Error::new(format!("Unsupported transfer encoding {}", value)).result();
+							}
+
+							if request.header("Content-Length").is_some() {
+								return Error::new("Content length is given along with transfer encoding").result();
+							}
+
+							true
+						},
+						None => false,
+					};
+
+					if chunked {
+						self.stage = Stage::Chunked(request);
+					}
+					else {
+						let length = match request.header("Content-Length") {
+							Some(value) => match value.trim().parse::<usize>() {
+								Ok(length) => length,
+								Err(_) => return Error::new(format!("Invalid content length {}", value)).result(),
+							},
+							None => 0,
+						};
+
+						if length > self.max_body_size {
+							return Error::new("Request body is too large").result();
+						}
+
+						if length == 0 {
+							return Ok(Some(request));
+						}
+
+						self.stage = Stage::Body(request, length);
+					}
+				},
+				Stage::Body(mut request, length) => {
+					if self.buf.len() < length {
+						self.stage = Stage::Body(request, length);
+						return Ok(None);
+					}
+
+					*request.body_mut() = self.buf.drain(0..length).collect();
+
+					return Ok(Some(request));
+				},
+				Stage::Chunked(mut request) => {
+					// Chunk size lines and trailers are limited the same way as the head
+					let line_end = match find(&self.buf, b"\r\n") {
+						Some(pos) if pos > self.max_head_size => {
+							return Error::new("Chunk size line is too large").result();
+						},
+						Some(pos) => pos,
+						None => {
+							if self.buf.len() > self.max_head_size {
+								return Error::new("Chunk size line is too large").result();
+							}
+
+							self.stage = Stage::Chunked(request);
+							return Ok(None);
+						}
+					};
+
+					let size = try!(parse_chunk_size(&self.buf[..line_end]));
+
+					if size == 0 {
+						// The last chunk can be followed by trailers which are ignored
+						let trailers_end = if self.buf[line_end..].starts_with(b"\r\n\r\n") {
+							Some(line_end + 4)
+						}
+						else {
+							match find(&self.buf[line_end..], b"\r\n\r\n") {
+								Some(pos) => Some(line_end + pos + 4),
+								None => None,
+							}
+						};
+
+						match trailers_end {
+							Some(end) if end - line_end > self.max_head_size => {
+								return Error::new("Request trailers are too large").result();
+							},
+							Some(end) => {
+								self.buf.drain(0..end);
+								return Ok(Some(request));
+							},
+							None => {
+								if self.buf.len() - line_end > self.max_head_size {
+									return Error::new("Request trailers are too large").result();
+								}
+
+								self.stage = Stage::Chunked(request);
+								return Ok(None);
+							}
+						};
+					}
+
+					// The body never exceeds the limit so the room left is known
+					// without adding the size which can be as large as usize
+					if size > self.max_body_size - request.body().len() {
+						return Error::new("Request body is too large").result();
+					}
+
+					// Wait until the whole chunk with the trailing CRLF is available
+					let chunk_start = line_end + 2;
+					let chunk_end = chunk_start + size;
+					if self.buf.len() < chunk_end + 2 {
+						self.stage = Stage::Chunked(request);
+						return Ok(None);
+					}
+
+					if &self.buf[chunk_end..chunk_end + 2] != b"\r\n" {
+						return Error::new("Chunk is not terminated with CRLF").result();
+					}
+
+					request.body_mut().extend(self.buf[chunk_start..chunk_end].iter());
+					self.buf.drain(0..chunk_end + 2);
+					self.stage = Stage::Chunked(request);
+				},
+			};
+		}
+	}
+}
+
+
+/// Find the first occurrence of `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+	if haystack.len() < needle.len() {
+		return None;
+	}
+
+	(0..haystack.len() - needle.len() + 1)
+		.position(|i| &haystack[i..i + needle.len()] == needle)
+}
+
+
+/// Parse the request line and headers.
+fn parse_head(head: &[u8]) -> Result<Request> {
+	let head = match str::from_utf8(head) {
+		Ok(head) => head,
+		Err(msg) => return Error::new("Request head is not valid UTF-8").because(msg).result(),
+	};
+
+	let mut lines = head.split("\r\n");
+
+	// Parse the request line
+	let request_line = lines.next().unwrap_or("");
+	let parts: Vec<&str> = request_line.split(' ').collect();
+	if parts.len() != 3 || parts[0].is_empty() || parts[1].is_empty() {
+		return Error::new(format!("Invalid request line '{}'", request_line)).result();
+	}
+
+	let version = match parts[2] {
+		"HTTP/1.1" => (1, 1),
+		"HTTP/1.0" => (1, 0),
+		_ => return Error::new(format!("Unsupported protocol version {}", parts[2])).result(),
+	};
+
+	// Parse headers
+	let mut headers = Vec::new();
+	for line in lines {
+		if line.starts_with(' ') || line.starts_with('\t') {
+			return Error::new("Obsolete header line folding is not supported").result();
+		}
+
+		match line.find(':') {
+			Some(pos) if pos > 0 => {
+				headers.push((line[..pos].to_string(), line[pos + 1..].trim().to_string()));
+			},
+			_ => return Error::new(format!("Invalid header line '{}'", line)).result(),
+		};
+	}
+
+	Ok(Request::new(parts[0].to_string(), parts[1].to_string(), version, headers))
+}
+
+
+/// Parse the chunk size line ignoring chunk extensions.
+fn parse_chunk_size(line: &[u8]) -> Result<usize> {
+	let line = match str::from_utf8(line) {
+		Ok(line) => line,
+		Err(msg) => return Error::new("Chunk size is not valid UTF-8").because(msg).result(),
+	};
+
+	let size = match line.find(';') {
+		Some(pos) => &line[..pos],
+		None => line,
+	};
+
+	match usize::from_str_radix(size.trim(), 16) {
+		Ok(size) => Ok(size),
+		Err(_) => Error::new(format!("Invalid chunk size '{}'", size)).result(),
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use parser::Parser;
+
+	#[test]
+	fn test_incremental() {
+		let mut parser = Parser::new(1024, 1024);
+
+		for b in b"POST /submit HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello".iter() {
+			assert!(parser.next().unwrap().is_none());
+			parser.feed(&[*b]);
+		}
+
+		let req = parser.next().unwrap().unwrap();
+		assert_eq!(req.method(), "POST");
+		assert_eq!(req.target(), "/submit");
+		assert_eq!(req.body(), &b"hello".to_vec());
+		assert!(parser.is_empty());
+	}
+
+	#[test]
+	fn test_pipelining() {
+		let mut parser = Parser::new(1024, 1024);
+		parser.feed(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\nGET /c");
+
+		assert_eq!(parser.next().unwrap().unwrap().target(), "/a");
+		assert_eq!(parser.next().unwrap().unwrap().target(), "/b");
+		assert!(parser.next().unwrap().is_none());
+
+		parser.feed(b" HTTP/1.0\r\n\r\n");
+		let req = parser.next().unwrap().unwrap();
+		assert_eq!(req.target(), "/c");
+		assert_eq!(req.version(), (1, 0));
+	}
+
+	#[test]
+	fn test_chunked() {
+		let mut parser = Parser::new(1024, 1024);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6;ext=1\r\n world\r\n");
+		assert!(parser.next().unwrap().is_none());
+
+		parser.feed(b"0\r\nX-Trailer: yes\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+		let req = parser.next().unwrap().unwrap();
+		assert_eq!(req.body(), &b"hello world".to_vec());
+
+		assert_eq!(parser.next().unwrap().unwrap().method(), "GET");
+	}
+
+	#[test]
+	fn test_errors() {
+		let mut parser = Parser::new(1024, 4);
+		parser.feed(b"GARBAGE\r\n\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(1024, 4);
+		parser.feed(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(16, 4);
+		parser.feed(b"GET /very/long/path HTTP/1.1\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(1024, 4);
+		parser.feed(b"GET / HTTP/2.0\r\n\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(1024, 4);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nhi\r\nffffffffffffffff\r\nhello\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(64, 1024);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n");
+		assert!(parser.next().unwrap().is_none());
+		parser.feed(&[b'0'; 100]);
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(64, 1024);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n");
+		assert!(parser.next().unwrap().is_none());
+		parser.feed(&[b'a'; 100]);
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(1024, 1024);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n");
+		assert!(parser.next().is_err());
+
+		let mut parser = Parser::new(1024, 1024);
+		parser.feed(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 5\r\n\r\n0\r\n\r\n");
+		assert!(parser.next().is_err());
+	}
+}
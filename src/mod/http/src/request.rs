@@ -0,0 +1,120 @@
+//! HTTP request.
+
+/// HTTP request received from the client.
+#[derive(Debug, Clone)]
+pub struct Request {
+	method: String,
+	target: String,
+	version: (u8, u8),
+	headers: Vec<(String, String)>,
+	body: Vec<u8>,
+}
+
+
+impl Request {
+	/// Create a new `Request` without body.
+	pub fn new(method: String, target: String, version: (u8, u8), headers: Vec<(String, String)>) -> Request {
+		Request {
+			method: method,
+			target: target,
+			version: version,
+			headers: headers,
+			body: Vec::new(),
+		}
+	}
+
+	/// Get the request method.
+	pub fn method(&self) -> &str {
+		&self.method
+	}
+
+	/// Get the request target as it is sent by the client.
+	pub fn target(&self) -> &str {
+		&self.target
+	}
+
+	/// Get the path part of the request target.
+	pub fn path(&self) -> &str {
+		match self.target.find('?') {
+			Some(pos) => &self.target[..pos],
+			None => &self.target,
+		}
+	}
+
+	/// Get the query part of the request target if any.
+	pub fn query(&self) -> Option<&str> {
+		match self.target.find('?') {
+			Some(pos) => Some(&self.target[pos + 1..]),
+			None => None,
+		}
+	}
+
+	/// Get the protocol version as (major, minor) pair.
+	pub fn version(&self) -> (u8, u8) {
+		self.version
+	}
+
+	/// Get all request headers in order they were received.
+	pub fn headers(&self) -> &Vec<(String, String)> {
+		&self.headers
+	}
+
+	/// Get the value of the first header with `name` given. Header names are case-insensitive.
+	pub fn header(&self, name: &str) -> Option<&str> {
+		let name = name.to_lowercase();
+		for &(ref key, ref value) in &self.headers {
+			if key.to_lowercase() == name {
+				return Some(value);
+			}
+		}
+
+		None
+	}
+
+	/// Get the request body.
+	pub fn body(&self) -> &Vec<u8> {
+		&self.body
+	}
+
+	/// Get the mutable request body.
+	pub fn body_mut(&mut self) -> &mut Vec<u8> {
+		&mut self.body
+	}
+
+	/// Test if the client wants to keep the connection open after the response.
+	pub fn keep_alive(&self) -> bool {
+		let connection = match self.header("Connection") {
+			Some(value) => value.to_lowercase(),
+			None => String::new(),
+		};
+
+		if connection.contains("close") {
+			false
+		}
+		else if self.version >= (1, 1) {
+			true
+		}
+		else {
+			connection.contains("keep-alive")
+		}
+	}
+}
+
+
+#[test]
+fn test_request() {
+	let req = Request::new("GET".to_string(), "/index.html?lang=en".to_string(), (1, 1), vec![("Host".to_string(), "localhost".to_string())]);
+
+	assert_eq!(req.method(), "GET");
+	assert_eq!(req.path(), "/index.html");
+	assert_eq!(req.query(), Some("lang=en"));
+	assert_eq!(req.header("host"), Some("localhost"));
+	assert_eq!(req.header("Accept"), None);
+	assert_eq!(req.keep_alive(), true);
+
+	let req = Request::new("GET".to_string(), "/".to_string(), (1, 0), Vec::new());
+	assert_eq!(req.keep_alive(), false);
+
+	let req = Request::new("GET".to_string(), "/".to_string(), (1, 0), vec![("Connection".to_string(), "Keep-Alive".to_string())]);
+	assert_eq!(req.keep_alive(), true);
+}
@@ -0,0 +1,196 @@
+//! HTTP response.
+
+use std::io::Read;
+
+/// HTTP response built by the request handler.
+///
+/// # Examples
+///
+/// ```
+/// use wrust_mod_http::Response;
+///
+/// let resp = Response::new(200)
+/// 	.header("Content-Type", "text/plain")
+/// 	.body("Hello!");
+///
+/// assert_eq!(resp.status(), 200);
+/// ```
+pub struct Response {
+	status: u16,
+	reason: String,
+	headers: Vec<(String, String)>,
+	body: Vec<u8>,
+	stream: Option<(Box<Read + Send>, u64)>,
+}
+
+
+impl Response {
+	/// Create a new empty `Response` with the `status` code given.
+	pub fn new(status: u16) -> Response {
+		Response {
+			status: status,
+			reason: reason_phrase(status).to_string(),
+			headers: Vec::new(),
+			body: Vec::new(),
+			stream: None,
+		}
+	}
+
+	/// Consumes self and returns the response with the reason phrase replaced.
+	pub fn reason<S>(mut self, reason: S) -> Self
+		where S: Into<String> {
+		self.reason = reason.into();
+		self
+	}
+
+	/// Consumes self and returns the response with the header added.
+	pub fn header<N, V>(mut self, name: N, value: V) -> Self
+		where N: Into<String>, V: Into<String> {
+		self.headers.push((name.into(), value.into()));
+		self
+	}
+
+	/// Consumes self and returns the response with the body replaced.
+	pub fn body<B>(mut self, body: B) -> Self
+		where B: Into<Vec<u8>> {
+		self.body = body.into();
+		self
+	}
+
+	/// Consumes self and returns the response which body of `length` bytes is read
+	/// from `reader` piece by piece while the response is written to the client.
+	/// The body set with `body` is ignored in that case.
+	pub fn stream<R>(mut self, reader: R, length: u64) -> Self
+		where R: 'static + Read + Send {
+		self.stream = Some((Box::new(reader), length));
+		self
+	}
+
+	/// Take the body stream out of the response.
+	pub fn take_stream(&mut self) -> Option<(Box<Read + Send>, u64)> {
+		self.stream.take()
+	}
+
+	/// Get the status code.
+	pub fn status(&self) -> u16 {
+		self.status
+	}
+
+	/// Get the response headers.
+	pub fn headers(&self) -> &Vec<(String, String)> {
+		&self.headers
+	}
+
+	/// Test if the header with `name` given is set. Header names are case-insensitive.
+	pub fn has_header(&self, name: &str) -> bool {
+		let name = name.to_lowercase();
+		self.headers
+			.iter()
+			.any(|&(ref key, _)| key.to_lowercase() == name)
+	}
+
+	/// Get the response body.
+	pub fn get_body(&self) -> &Vec<u8> {
+		&self.body
+	}
+
+	/// Serialize the response into `buf`. The body is omitted when `head_only` is `true`,
+	/// e.g. for responses to HEAD requests, but Content-Length still reflects its size.
+	pub fn serialize(&self, buf: &mut Vec<u8>, head_only: bool, keep_alive: bool) {
+		buf.extend(format!("HTTP/1.1 {} {}\r\n", self.status, self.reason).as_bytes());
+
+		for &(ref name, ref value) in &self.headers {
+			buf.extend(format!("{}: {}\r\n", name, value).as_bytes());
+		}
+
+		if !self.has_header("Content-Length") && has_body(self.status) {
+			let length = match self.stream {
+				Some((_, length)) => length,
+				None => self.body.len() as u64,
+			};
+
+			buf.extend(format!("Content-Length: {}\r\n", length).as_bytes());
+		}
+
+		if !self.has_header("Connection") {
+			if keep_alive {
+				buf.extend(b"Connection: keep-alive\r\n".iter());
+			}
+			else {
+				buf.extend(b"Connection: close\r\n".iter());
+			}
+		}
+
+		buf.extend(b"\r\n".iter());
+
+		if !head_only && has_body(self.status) && self.stream.is_none() {
+			buf.extend(self.body.iter());
+		}
+	}
+}
+
+
+/// Test if the response with `status` can have the body.
+fn has_body(status: u16) -> bool {
+	!(status < 200 || status == 204 || status == 304)
+}
+
+
+/// Get the standard reason phrase for the `status` code.
+pub fn reason_phrase(status: u16) -> &'static str {
+	match status {
+		100 => "Continue",
+		101 => "Switching Protocols",
+		200 => "OK",
+		201 => "Created",
+		202 => "Accepted",
+		204 => "No Content",
+		206 => "Partial Content",
+		301 => "Moved Permanently",
+		302 => "Found",
+		303 => "See Other",
+		304 => "Not Modified",
+		307 => "Temporary Redirect",
+		400 => "Bad Request",
+		401 => "Unauthorized",
+		403 => "Forbidden",
+		404 => "Not Found",
+		405 => "Method Not Allowed",
+		408 => "Request Timeout",
+		411 => "Length Required",
+		413 => "Payload Too Large",
+		414 => "URI Too Long",
+		416 => "Range Not Satisfiable",
+		431 => "Request Header Fields Too Large",
+		500 => "Internal Server Error",
+		501 => "Not Implemented",
+		502 => "Bad Gateway",
+		503 => "Service Unavailable",
+		505 => "HTTP Version Not Supported",
+		_ => "Unknown",
+	}
+}
+
+
+#[test]
+fn test_serialize() {
+	let resp = Response::new(200)
+		.header("Content-Type", "text/plain")
+		.body("Hi");
+
+	let mut buf = Vec::new();
+	resp.serialize(&mut buf, false, true);
+	assert_eq!(String::from_utf8(buf).unwrap(), "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: keep-alive\r\n\r\nHi");
+
+	let mut buf = Vec::new();
+	resp.serialize(&mut buf, true, false);
+	assert_eq!(String::from_utf8(buf).unwrap(), "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 2\r\nConnection: close\r\n\r\n");
+
+	let mut buf = Vec::new();
+	Response::new(200).stream(&b"Streamed"[..], 8).serialize(&mut buf, false, true);
+	assert_eq!(String::from_utf8(buf).unwrap(), "HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: keep-alive\r\n\r\n");
+
+	let mut buf = Vec::new();
+	Response::new(304).serialize(&mut buf, false, true);
+	assert_eq!(String::from_utf8(buf).unwrap(), "HTTP/1.1 304 Not Modified\r\nConnection: keep-alive\r\n\r\n");
+}
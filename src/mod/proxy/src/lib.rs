@@ -0,0 +1,12 @@
+//! Wrustlr TCP reverse proxy module.
+
+extern crate wrust_types;
+extern crate wrust_io;
+extern crate wrust_conf;
+extern crate wrust_module;
+
+mod module;
+pub mod upstream;
+
+pub use self::module::Module;
+pub use self::upstream::{Upstream, Pool, Balance};
@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wrust_types::{Error, Result};
+use wrust_types::net::connection::Descriptor;
+use wrust_conf::{Conf, FromConf};
+use wrust_module::{Facility, Category};
+use wrust_module::stream::{Behavior, Dispatcher, Waker, Intention, Flush, Session};
+use upstream::{Upstream, Pool, Balance};
+
+const MOD_NAME: &'static str = "proxy";
+
+const DEFAULT_BALANCE: &'static str = "round_robin";
+const DEFAULT_CONNECT_TIMEOUT: i64 = 3000;
+const DEFAULT_FAIL_TIMEOUT: i64 = 10000;
+const DEFAULT_BUFFER_SIZE: i64 = 65536;
+
+/// The client connection forwarded to the upstream. The tunnel is the session
+/// of both the client and the upstream connections.
+struct Tunnel {
+	/// The client connection
+	client: Waker,
+	/// Index of the upstream in the pool
+	upstream: usize,
+	/// Upstreams which failed to connect
+	tried: Vec<usize>,
+	/// The upstream connection when it is established
+	peer: Option<Waker>,
+	to_upstream: Vec<u8>,
+	to_client: Vec<u8>,
+	client_closed: bool,
+	upstream_closed: bool,
+	/// The client shut down sending and only receives the rest of the upstream data
+	client_eof: bool,
+	/// Reading from the client waits until the upstream takes buffered data
+	client_paused: bool,
+	/// Reading from the upstream waits until the client takes buffered data
+	upstream_paused: bool,
+}
+
+type SharedTunnel = Arc<Mutex<Tunnel>>;
+
+pub struct Module {
+	pool: Mutex<Pool>,
+	/// Tunnels by client connection identifiers and serials while upstreams are connecting
+	connecting: Mutex<HashMap<(u32, usize), SharedTunnel>>,
+	dispatcher: Mutex<Option<Arc<Dispatcher>>>,
+	connect_timeout: u64,
+	/// Bytes buffered for the other side which pause reading
+	buffer_size: usize,
+	error: Option<String>,
+}
+
+impl Module {
+	fn dispatcher(&self) -> Result<Arc<Dispatcher>> {
+		match *self.dispatcher.lock().unwrap() {
+			Some(ref dispatcher) => Ok(dispatcher.clone()),
+			None => Error::new("The proxy module is not attached to the server core").result(),
+		}
+	}
+
+	/// Connect the client connection of the `tunnel` to the next upstream it has not tried yet.
+	fn connect(&self, dispatcher: &Arc<Dispatcher>, tunnel: &mut Tunnel) -> Result<()> {
+		let now = Instant::now();
+		let mut pool = self.pool.lock().unwrap();
+
+		loop {
+			let index = match pool.acquire(now, &tunnel.tried) {
+				Some(index) => index,
+				None => return Error::new("No upstream is available").result(),
+			};
+
+			tunnel.upstream = index;
+
+			// Connecting can fail immediately, e.g. when the UNIX socket does not exist
+			match dispatcher.connect(Some((tunnel.client.id(), tunnel.client.serial())), pool.upstream(index).addr(), self.connect_timeout) {
+				Ok(_) => return Ok(()),
+				Err(_) => {
+					pool.mark_failed(index, now);
+					pool.release(index);
+					tunnel.tried.push(index);
+				},
+			};
+		}
+	}
+
+	fn release(&self, index: usize) {
+		self.pool.lock().unwrap().release(index);
+	}
+}
+
+#[inline(never)]
+impl Facility for Module {
+	fn new(config: &Conf, xpath: &String) -> Self {
+		// Read configuration
+		let balance = config.lookup_str_or(&format!("{}.balance", xpath), DEFAULT_BALANCE);
+		let connect_timeout = config.lookup_integer64_or(&format!("{}.connect_timeout", xpath), DEFAULT_CONNECT_TIMEOUT);
+		let fail_timeout = config.lookup_integer64_or(&format!("{}.fail_timeout", xpath), DEFAULT_FAIL_TIMEOUT);
+		let buffer_size = config.lookup_integer64_or(&format!("{}.buffer_size", xpath), DEFAULT_BUFFER_SIZE);
+
+		// The module cannot fail to instantiate so configuration errors
+		// are reported when client connections are opened
+		let mut error = None;
+
+		let balance = match Balance::from_str(balance) {
+			Ok(balance) => balance,
+			Err(msg) => {
+				error = Some(format!("{} at '{}.balance'", msg, xpath));
+				Balance::RoundRobin
+			},
+		};
+
+		if buffer_size < 1 {
+			error = Some(format!("Buffer size must be positive at '{}.buffer_size'", xpath));
+		}
+
+		let upstreams = match Vec::<Upstream>::from_conf(config, &format!("{}.upstreams", xpath)) {
+			Ok(upstreams) => upstreams,
+			Err(msg) => {
+				error = Some(format!("{}", msg));
+				Vec::new()
+			},
+		};
+
+		Module {
+			pool: Mutex::new(Pool::new(upstreams, balance, Duration::from_millis(fail_timeout as u64))),
+			connecting: Mutex::new(HashMap::new()),
+			dispatcher: Mutex::new(None),
+			connect_timeout: connect_timeout as u64,
+			buffer_size: buffer_size as usize,
+			error: error,
+		}
+	}
+
+	fn name() -> String {
+		MOD_NAME.to_string()
+	}
+
+	fn version() -> String {
+		format!("{}.{}.{}", env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"), env!("CARGO_PKG_VERSION_PATCH")).to_string()
+	}
+
+	fn category() -> Category {
+		Category::Stream
+	}
+}
+
+
+#[inline(never)]
+impl Behavior for Module {
+	fn attach(self: &Self, dispatcher: Arc<Dispatcher>) {
+		*self.dispatcher.lock().unwrap() = Some(dispatcher);
+	}
+
+	fn open(self: &Self, desc: &Descriptor) -> (Intention, Session) {
+		if let Some(ref msg) = self.error {
+			return (Intention::Close(Some(Error::new(msg.clone()))), Box::new(()));
+		}
+
+		let dispatcher = match self.dispatcher() {
+			Ok(dispatcher) => dispatcher,
+			Err(msg) => return (Intention::Close(Some(msg)), Box::new(())),
+		};
+
+		let mut tunnel = Tunnel {
+			client: Waker::new(dispatcher.clone(), desc),
+			upstream: 0,
+			tried: Vec::new(),
+			peer: None,
+			to_upstream: Vec::new(),
+			to_client: Vec::new(),
+			client_closed: false,
+			upstream_closed: false,
+			client_eof: false,
+			client_paused: false,
+			upstream_paused: false,
+		};
+
+		if let Err(msg) = self.connect(&dispatcher, &mut tunnel) {
+			return (Intention::Close(Some(msg)), Box::new(()));
+		}
+
+		let tunnel = Arc::new(Mutex::new(tunnel));
+		self.connecting.lock().unwrap().insert((desc.id(), desc.serial()), tunnel.clone());
+
+		// Data the client sends while the upstream connects is buffered
+		(Intention::Read, Box::new(tunnel))
+	}
+
+	fn connected(self: &Self, desc: &Descriptor, result: Result<()>) -> (Intention, Session) {
+		let owner = match desc.owner() {
+			Some(owner) => owner,
+			None => return (Intention::Close(Some(Error::new("Upstream connection has no client connection"))), Box::new(())),
+		};
+
+		// The client can close the connection while the upstream is connecting
+		let shared = match result {
+			Ok(_) => self.connecting.lock().unwrap().remove(&owner),
+			Err(_) => self.connecting.lock().unwrap().get(&owner).cloned(),
+		};
+
+		let shared = match shared {
+			Some(shared) => shared,
+			None => return (Intention::Close(None), Box::new(())),
+		};
+
+		let dispatcher = match self.dispatcher() {
+			Ok(dispatcher) => dispatcher,
+			Err(msg) => return (Intention::Close(Some(msg)), Box::new(())),
+		};
+
+		let further_action = {
+			let mut tunnel = shared.lock().unwrap();
+
+			match result {
+				Ok(_) => {
+					self.pool.lock().unwrap().mark_healthy(tunnel.upstream);
+
+					// The client closed after the tunnel was taken out of connecting ones
+					if tunnel.client_closed && tunnel.to_upstream.is_empty() {
+						tunnel.upstream_closed = true;
+						self.release(tunnel.upstream);
+						return (Intention::Close(None), Box::new(()));
+					}
+
+					tunnel.peer = Some(Waker::new(dispatcher, desc));
+
+					if tunnel.to_upstream.is_empty() {
+						Intention::Read
+					}
+					else {
+						Intention::Write
+					}
+				},
+				Err(_) => {
+					// Mark the upstream as failed and try the next one
+					{
+						let mut pool = self.pool.lock().unwrap();
+						pool.mark_failed(tunnel.upstream, Instant::now());
+						pool.release(tunnel.upstream);
+					}
+
+					let index = tunnel.upstream;
+					tunnel.tried.push(index);
+
+					if self.connect(&dispatcher, &mut tunnel).is_err() {
+						self.connecting.lock().unwrap().remove(&owner);
+						tunnel.upstream_closed = true;
+						let _ = tunnel.client.close();
+					}
+
+					Intention::Close(None)
+				},
+			}
+		};
+
+		(further_action, Box::new(shared))
+	}
+
+	fn read(self: &Self, desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention {
+		let mut tunnel = match session.downcast_ref::<SharedTunnel>() {
+			Some(shared) => shared.lock().unwrap(),
+			None => return Intention::Close(Some(Error::new("Tunnel is undefined"))),
+		};
+
+		// The side stops reading when the other side does not take data fast enough
+		// and goes on when the data buffered is taken
+		if desc.is_outbound() {
+			// Data from the upstream goes to the client
+			tunnel.to_client.extend(buf.iter());
+			let _ = tunnel.client.wake();
+
+			if tunnel.to_client.len() >= self.buffer_size {
+				tunnel.upstream_paused = true;
+				return Intention::Wait;
+			}
+		}
+		else {
+			// Data from the client goes to the upstream
+			tunnel.to_upstream.extend(buf.iter());
+
+			if let Some(ref peer) = tunnel.peer {
+				let _ = peer.wake();
+			}
+
+			if tunnel.to_upstream.len() >= self.buffer_size {
+				tunnel.client_paused = true;
+				return Intention::Wait;
+			}
+		}
+
+		Intention::Read
+	}
+
+	fn eof(self: &Self, desc: &Descriptor, session: &mut Session) -> Intention {
+		let mut tunnel = match session.downcast_ref::<SharedTunnel>() {
+			Some(shared) => shared.lock().unwrap(),
+			None => return Intention::Close(Some(Error::new("Tunnel is undefined"))),
+		};
+
+		// The client which is done sending still gets the upstream response
+		if !desc.is_outbound() {
+			tunnel.client_eof = true;
+		}
+
+		Intention::Write
+	}
+
+	fn write(self: &Self, desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush) {
+		let mut tunnel = match session.downcast_ref::<SharedTunnel>() {
+			Some(shared) => shared.lock().unwrap(),
+			None => return (Intention::Close(Some(Error::new("Tunnel is undefined"))), Flush::Auto),
+		};
+
+		// The connection is closed when the other side is closed and all its data is sent
+		let (done, waiting) = if desc.is_outbound() {
+			buf.append(&mut tunnel.to_upstream);
+
+			if tunnel.client_paused {
+				tunnel.client_paused = false;
+				let _ = tunnel.client.wake();
+			}
+
+			(tunnel.client_closed, tunnel.upstream_paused)
+		}
+		else {
+			buf.append(&mut tunnel.to_client);
+
+			if tunnel.upstream_paused {
+				tunnel.upstream_paused = false;
+
+				if let Some(ref peer) = tunnel.peer {
+					let _ = peer.wake();
+				}
+			}
+
+			(tunnel.upstream_closed, tunnel.client_eof || tunnel.client_paused)
+		};
+
+		if done {
+			(Intention::Close(None), Flush::Force)
+		}
+		else if waiting {
+			(Intention::Wait, Flush::Force)
+		}
+		else {
+			(Intention::Read, Flush::Force)
+		}
+	}
+
+	fn close(self: &Self, desc: &Descriptor, session: Session) {
+		let shared = match session.downcast_ref::<SharedTunnel>() {
+			Some(shared) => shared.clone(),
+			None => return,
+		};
+
+		let mut tunnel = shared.lock().unwrap();
+
+		if desc.is_outbound() {
+			tunnel.peer = None;
+			tunnel.upstream_closed = true;
+			self.release(tunnel.upstream);
+
+			if tunnel.client_closed {
+				return;
+			}
+
+			if !tunnel.to_client.is_empty() {
+				let _ = tunnel.client.wake();
+			}
+			else {
+				let _ = tunnel.client.close();
+			}
+		}
+		else {
+			tunnel.client_closed = true;
+
+			match tunnel.peer {
+				Some(ref peer) if !tunnel.to_upstream.is_empty() => {
+					let _ = peer.wake();
+				},
+				Some(ref peer) => {
+					let _ = peer.close();
+				},
+				None => {
+					// The upstream which is still connecting is aborted by the server core
+					// and finds no tunnel when it is reported as failed
+					if self.connecting.lock().unwrap().remove(&(desc.id(), desc.serial())).is_some() {
+						self.release(tunnel.upstream);
+					}
+				},
+			};
+		}
+	}
+}
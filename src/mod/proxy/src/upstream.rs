@@ -0,0 +1,286 @@
+//! Upstream pools with balancing and passive health marking.
+
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use wrust_types::{Error, Result};
+use wrust_types::net::Protocol;
+use wrust_conf::{Conf, FromConf};
+use wrust_module::stream::Address;
+
+
+/// The way the upstream is chosen for a new client connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Balance {
+	/// Upstreams are chosen in turn.
+	RoundRobin,
+	/// The upstream with the least number of active connections is chosen.
+	LeastConnections,
+}
+
+
+impl FromStr for Balance {
+	type Err = Error;
+
+	fn from_str(s: &str) -> Result<Balance> {
+		match s {
+			"round_robin" => Ok(Balance::RoundRobin),
+			"least_connections" => Ok(Balance::LeastConnections),
+			_ => Error::new(format!("Unknown balancing method '{}'", s)).result(),
+		}
+	}
+}
+
+
+/// The upstream address with connection statistics.
+pub struct Upstream {
+	addr: Address,
+	active: usize,
+	down_until: Option<Instant>,
+}
+
+
+impl Upstream {
+	pub fn new(addr: Address) -> Upstream {
+		Upstream {
+			addr: addr,
+			active: 0,
+			down_until: None,
+		}
+	}
+
+	pub fn addr(&self) -> &Address {
+		&self.addr
+	}
+
+	/// The number of client connections forwarded to the upstream at the moment.
+	pub fn active(&self) -> usize {
+		self.active
+	}
+
+	/// Test if the upstream is not marked as failed at the moment `now`.
+	pub fn is_available(&self, now: Instant) -> bool {
+		match self.down_until {
+			Some(until) => now >= until,
+			None => true,
+		}
+	}
+}
+
+
+impl FromConf for Upstream {
+	// Load settings from the config
+	fn from_conf(config: &Conf, xpath: &str) -> Result<Self> {
+		let protocol = match config.lookup_str(&format!("{}.protocol", xpath)) {
+			Some(name) => name.to_lowercase(),
+			None => return Error::new(format!("Protocol is undefined at '{}'", xpath)).result(),
+		};
+
+		let addr = match &protocol[..] {
+			"tcp" => {
+				let address = match config.lookup_str(&format!("{}.address", xpath)) {
+					Some(address) => address,
+					None => return Error::new(format!("Address is required at '{}'", xpath)).result(),
+				};
+
+				let port = match config.lookup_integer32(&format!("{}.port", xpath)) {
+					Some(port) => port as u16,
+					None => return Error::new(format!("Port is required at '{}'", xpath)).result(),
+				};
+
+				let resolved = match (address, port).to_socket_addrs() {
+					Ok(mut addrs) => addrs.next(),
+					Err(msg) => return Error::new(format!("Cannot resolve {}:{} at '{}'", address, port, xpath)).because(msg).result(),
+				};
+
+				match resolved {
+					Some(addr) => Protocol::Tcp(addr),
+					None => return Error::new(format!("Cannot resolve {}:{} at '{}'", address, port, xpath)).result(),
+				}
+			},
+			"unix" => match config.lookup_str(&format!("{}.path", xpath)) {
+				Some(path) => Protocol::Unix(PathBuf::from(path)),
+				None => return Error::new(format!("Path is required at '{}'", xpath)).result(),
+			},
+			_ => return Error::new(format!("Invalid upstream protocol '{}' at '{}'", protocol, xpath)).result(),
+		};
+
+		Ok(Upstream::new(addr))
+	}
+}
+
+
+/// The list of upstreams client connections are forwarded to.
+pub struct Pool {
+	upstreams: Vec<Upstream>,
+	balance: Balance,
+	fail_timeout: Duration,
+	next: usize,
+}
+
+
+impl Pool {
+	/// Create a new pool of `upstreams` chosen with the `balance` method given.
+	/// Upstreams which failed are skipped for `fail_timeout`.
+	pub fn new(upstreams: Vec<Upstream>, balance: Balance, fail_timeout: Duration) -> Pool {
+		Pool {
+			upstreams: upstreams,
+			balance: balance,
+			fail_timeout: fail_timeout,
+			next: 0,
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.upstreams.len()
+	}
+
+	pub fn upstream(&self, index: usize) -> &Upstream {
+		&self.upstreams[index]
+	}
+
+	/// Choose the upstream for a new connection skipping upstreams `tried` already
+	/// and count the connection as active. When all the rest upstreams are marked
+	/// as failed they are tried anyway because health is only known from real connections.
+	pub fn acquire(&mut self, now: Instant, tried: &[usize]) -> Option<usize> {
+		let count = self.upstreams.len();
+		if count == 0 {
+			return None;
+		}
+
+		let chosen = match self.choose(now, tried, true) {
+			Some(index) => Some(index),
+			None => self.choose(now, tried, false),
+		};
+
+		if let Some(index) = chosen {
+			self.next = (index + 1) % count;
+			self.upstreams[index].active += 1;
+		}
+
+		chosen
+	}
+
+	/// The connection forwarded to the upstream is done.
+	pub fn release(&mut self, index: usize) {
+		let upstream = &mut self.upstreams[index];
+		if upstream.active > 0 {
+			upstream.active -= 1;
+		}
+	}
+
+	/// The connection to the upstream failed so it is skipped for a while.
+	pub fn mark_failed(&mut self, index: usize, now: Instant) {
+		self.upstreams[index].down_until = Some(now + self.fail_timeout);
+	}
+
+	/// The connection to the upstream succeeded.
+	pub fn mark_healthy(&mut self, index: usize) {
+		self.upstreams[index].down_until = None;
+	}
+
+	fn choose(&self, now: Instant, tried: &[usize], available_only: bool) -> Option<usize> {
+		let count = self.upstreams.len();
+		let mut chosen: Option<usize> = None;
+
+		// Upstreams are scanned in turn starting from the next one so
+		// ties are resolved the round-robin way
+		for i in 0..count {
+			let index = (self.next + i) % count;
+			let upstream = &self.upstreams[index];
+
+			if tried.contains(&index) || (available_only && !upstream.is_available(now)) {
+				continue;
+			}
+
+			match self.balance {
+				Balance::RoundRobin => return Some(index),
+				Balance::LeastConnections => {
+					chosen = match chosen {
+						Some(best) if self.upstreams[best].active <= upstream.active => Some(best),
+						_ => Some(index),
+					};
+				},
+			};
+		}
+
+		chosen
+	}
+}
+
+
+#[cfg(test)]
+mod tests {
+	use std::net::SocketAddr;
+	use std::str::FromStr;
+	use std::time::{Duration, Instant};
+	use wrust_types::net::Protocol;
+	use super::*;
+
+	fn pool(count: usize, balance: Balance) -> Pool {
+		let upstreams = (0..count)
+			.map(|i| Upstream::new(Protocol::Tcp(SocketAddr::from_str(&format!("127.0.0.1:{}", 9000 + i)).unwrap())))
+			.collect();
+
+		Pool::new(upstreams, balance, Duration::from_secs(10))
+	}
+
+	#[test]
+	fn test_round_robin() {
+		let mut pool = pool(3, Balance::RoundRobin);
+		let now = Instant::now();
+
+		assert_eq!(pool.acquire(now, &[]), Some(0));
+		assert_eq!(pool.acquire(now, &[]), Some(1));
+		assert_eq!(pool.acquire(now, &[]), Some(2));
+		assert_eq!(pool.acquire(now, &[]), Some(0));
+		assert_eq!(pool.acquire(now, &[1]), Some(2));
+	}
+
+	#[test]
+	fn test_least_connections() {
+		let mut pool = pool(3, Balance::LeastConnections);
+		let now = Instant::now();
+
+		assert_eq!(pool.acquire(now, &[]), Some(0));
+		assert_eq!(pool.acquire(now, &[]), Some(1));
+		assert_eq!(pool.acquire(now, &[]), Some(2));
+
+		pool.release(1);
+		assert_eq!(pool.acquire(now, &[]), Some(1));
+
+		pool.release(2);
+		pool.release(0);
+		assert_eq!(pool.acquire(now, &[]), Some(2));
+		assert_eq!(pool.acquire(now, &[]), Some(0));
+		assert_eq!(pool.upstream(0).active(), 1);
+	}
+
+	#[test]
+	fn test_passive_health() {
+		let mut pool = pool(2, Balance::RoundRobin);
+		let now = Instant::now();
+
+		pool.mark_failed(0, now);
+		assert_eq!(pool.acquire(now, &[]), Some(1));
+		assert_eq!(pool.acquire(now, &[]), Some(1));
+
+		// Failed upstreams are tried when nothing else is left
+		assert_eq!(pool.acquire(now, &[1]), Some(0));
+		assert_eq!(pool.acquire(now, &[0, 1]), None);
+
+		// .. and come back when the fail timeout expires
+		assert!(pool.upstream(0).is_available(now + Duration::from_secs(10)));
+
+		pool.mark_healthy(0);
+		assert!(pool.upstream(0).is_available(now));
+	}
+
+	#[test]
+	fn test_balance_from_str() {
+		assert_eq!(Balance::from_str("round_robin").unwrap(), Balance::RoundRobin);
+		assert_eq!(Balance::from_str("least_connections").unwrap(), Balance::LeastConnections);
+		assert!(Balance::from_str("random").is_err());
+	}
+}
@@ -0,0 +1,106 @@
+//! HTTP-date formatting and parsing.
+
+const WEEKDAYS: [&'static str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&'static str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+
+/// Format seconds since UNIX epoch as IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn format(secs: u64) -> String {
+	let days = secs / 86400;
+	let time = secs % 86400;
+	let (year, month, day) = civil_from_days(days as i64);
+
+	format!("{}, {:02} {} {:04} {:02}:{:02}:{:02} GMT",
+		WEEKDAYS[(days % 7) as usize],
+		day,
+		MONTHS[(month - 1) as usize],
+		year,
+		time / 3600,
+		time % 3600 / 60,
+		time % 60)
+}
+
+
+/// Parse IMF-fixdate into seconds since UNIX epoch.
+pub fn parse(value: &str) -> Option<u64> {
+	let parts: Vec<&str> = value.split_whitespace().collect();
+	if parts.len() != 6 || parts[5] != "GMT" {
+		return None;
+	}
+
+	let day = match parts[1].parse::<i64>() {
+		Ok(day) if day >= 1 && day <= 31 => day,
+		_ => return None,
+	};
+
+	let month = match MONTHS.iter().position(|name| *name == parts[2]) {
+		Some(index) => index as i64 + 1,
+		None => return None,
+	};
+
+	// IMF-fixdate has four digits of the year which also keeps seconds in range
+	let year = match parts[3].parse::<i64>() {
+		Ok(year) if year >= 1970 && year <= 9999 => year,
+		_ => return None,
+	};
+
+	let time: Vec<i64> = parts[4]
+		.split(':')
+		.filter_map(|part| part.parse::<u8>().ok())
+		.map(|part| part as i64)
+		.collect();
+
+	if time.len() != 3 || time[0] > 23 || time[1] > 59 || time[2] > 60 {
+		return None;
+	}
+
+	let days = days_from_civil(year, month, day);
+
+	Some((days * 86400 + time[0] * 3600 + time[1] * 60 + time[2]) as u64)
+}
+
+
+/// Convert days since UNIX epoch into (year, month, day).
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+	let z = days + 719468;
+	let era = if z >= 0 { z } else { z - 146096 } / 146097;
+	let doe = z - era * 146097;
+	let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+	let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+	let mp = (5 * doy + 2) / 153;
+	let day = doy - (153 * mp + 2) / 5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400;
+
+	(if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+
+/// Convert (year, month, day) into days since UNIX epoch.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+	let year = if month <= 2 { year - 1 } else { year };
+	let era = if year >= 0 { year } else { year - 399 } / 400;
+	let yoe = year - era * 400;
+	let mp = if month > 2 { month - 3 } else { month + 9 };
+	let doy = (153 * mp + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+	era * 146097 + doe - 719468
+}
+
+
+#[test]
+fn test_http_date() {
+	assert_eq!(format(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+	assert_eq!(format(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+	assert_eq!(format(951782400), "Tue, 29 Feb 2000 00:00:00 GMT");
+
+	assert_eq!(parse("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784111777));
+	assert_eq!(parse("Tue, 29 Feb 2000 00:00:00 GMT"), Some(951782400));
+	assert_eq!(parse("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+	assert_eq!(parse("garbage"), None);
+	assert_eq!(parse("Fri, 31 Dec 9999 23:59:59 GMT"), Some(253402300799));
+	assert_eq!(parse("Sun, 06 Nov 99999999999999999 08:49:37 GMT"), None);
+	assert_eq!(parse("Sun, 06 Nov 10000 08:49:37 GMT"), None);
+	assert_eq!(parse("Sun, 06 Nov 1994 08:-1:37 GMT"), None);
+}
@@ -0,0 +1,194 @@
+//! Static file request handler.
+
+use std::fs::{self, File, Metadata};
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use wrust_mod_http::{Handler, Request, Response};
+use range::{self, Range};
+use {date, mime, path};
+
+
+/// The handler which serves files from the document root.
+pub struct StaticHandler {
+	root: PathBuf,
+	index: Vec<String>,
+}
+
+
+impl StaticHandler {
+	/// Create a new `StaticHandler` serving files from `root` and trying
+	/// `index` file names in order when a directory is requested.
+	pub fn new(root: &Path, index: Vec<String>) -> StaticHandler {
+		// Symbolic links are resolved so the resolved paths of files can be checked against the root
+		let root = match fs::canonicalize(root) {
+			Ok(path) => path,
+			Err(_) => root.to_path_buf(),
+		};
+
+		StaticHandler {
+			root: root,
+			index: index,
+		}
+	}
+
+	/// Find the file to serve for the request `target`.
+	fn lookup(&self, req: &Request) -> Result<(PathBuf, Metadata), Response> {
+		let file_path = match path::resolve(&self.root, req.path()) {
+			Some(file_path) => file_path,
+			None => return Err(Response::new(403)),
+		};
+
+		let metadata = try!(self.metadata(&file_path));
+
+		if metadata.is_dir() {
+			// Directories are addressed with the trailing slash so relative links work.
+			// The location is built from the normalized path so it never refers to another host.
+			if !req.path().ends_with('/') {
+				let location = match req.query() {
+					Some(query) => format!("{}/?{}", path::normalize(req.path()), query),
+					None => format!("{}/", path::normalize(req.path())),
+				};
+
+				return Err(Response::new(301).header("Location", location));
+			}
+
+			let found = self.index
+				.iter()
+				.map(|name| file_path.join(name))
+				.filter_map(|index_path| match self.metadata(&index_path) {
+					Ok(index_meta) => if index_meta.is_file() { Some((index_path, index_meta)) } else { None },
+					Err(_) => None,
+				})
+				.next();
+
+			match found {
+				Some(index) => Ok(index),
+				None => Err(Response::new(403)),
+			}
+		}
+		else if metadata.is_file() {
+			Ok((file_path, metadata))
+		}
+		else {
+			Err(Response::new(403))
+		}
+	}
+
+	/// Get metadata of the file at `file_path`. The file must not escape the document root
+	/// through symbolic links and files outside of it are reported as missing so responses
+	/// do not tell which of them exist.
+	fn metadata(&self, file_path: &Path) -> Result<Metadata, Response> {
+		match fs::canonicalize(file_path) {
+			Ok(ref real_path) if real_path.starts_with(&self.root) => (),
+			Ok(_) => return Err(Response::new(404)),
+			Err(ref err) if err.kind() == ErrorKind::PermissionDenied => return Err(Response::new(403)),
+			Err(_) => return Err(Response::new(404)),
+		};
+
+		match fs::metadata(file_path) {
+			Ok(metadata) => Ok(metadata),
+			Err(ref err) if err.kind() == ErrorKind::PermissionDenied => Err(Response::new(403)),
+			Err(_) => Err(Response::new(404)),
+		}
+	}
+}
+
+
+impl Handler for StaticHandler {
+	fn handle(self: &Self, req: &Request) -> Response {
+		if req.method() != "GET" && req.method() != "HEAD" {
+			return Response::new(405)
+				.header("Allow", "GET, HEAD");
+		}
+
+		let (file_path, metadata) = match self.lookup(req) {
+			Ok(found) => found,
+			Err(resp) => return resp,
+		};
+
+		let length = metadata.len();
+		let modified = match metadata.modified() {
+			Ok(time) => match time.duration_since(UNIX_EPOCH) {
+				Ok(duration) => duration.as_secs(),
+				Err(_) => 0,
+			},
+			Err(_) => 0,
+		};
+
+		let etag = format!("\"{:x}-{:x}\"", modified, length);
+		let last_modified = date::format(modified);
+
+		// Conditional requests
+		let not_modified = match req.header("If-None-Match") {
+			Some(value) => value
+				.split(',')
+				.map(|tag| tag.trim().trim_left_matches("W/"))
+				.any(|tag| tag == "*" || tag == &etag[..]),
+			None => match req.header("If-Modified-Since").and_then(date::parse) {
+				Some(since) => modified <= since,
+				None => false,
+			},
+		};
+
+		if not_modified {
+			return Response::new(304)
+				.header("ETag", etag)
+				.header("Last-Modified", last_modified);
+		}
+
+		// Range requests are honored only if the representation did not change
+		let range = match req.header("Range") {
+			Some(value) => {
+				let unchanged = match req.header("If-Range") {
+					Some(validator) => {
+						let validator = validator.trim();
+						validator == etag || date::parse(validator) == Some(modified)
+					},
+					None => true,
+				};
+
+				if unchanged {
+					range::parse(value, length)
+				}
+				else {
+					Range::Full
+				}
+			},
+			None => Range::Full,
+		};
+
+		let file = match File::open(&file_path) {
+			Ok(file) => file,
+			Err(ref err) if err.kind() == ErrorKind::PermissionDenied => return Response::new(403),
+			Err(_) => return Response::new(500),
+		};
+
+		let headers = |resp: Response| {
+			resp.header("Content-Type", mime::from_path(&file_path))
+				.header("ETag", etag.clone())
+				.header("Last-Modified", last_modified.clone())
+				.header("Accept-Ranges", "bytes")
+		};
+
+		match range {
+			Range::Full => headers(Response::new(200))
+				.stream(file, length),
+			Range::Partial(first, last) => {
+				let mut file = file;
+				if file.seek(SeekFrom::Start(first)).is_err() {
+					return Response::new(500);
+				}
+
+				let count = last - first + 1;
+
+				headers(Response::new(206))
+					.header("Content-Range", format!("bytes {}-{}/{}", first, last, length))
+					.stream(file.take(count), count)
+			},
+			Range::Unsatisfiable => Response::new(416)
+				.header("Content-Range", format!("bytes */{}", length)),
+		}
+	}
+}
+
@@ -0,0 +1,17 @@
+//! Wrustlr static file serving module.
+
+extern crate wrust_types;
+extern crate wrust_io;
+extern crate wrust_conf;
+extern crate wrust_module;
+extern crate wrust_mod_http;
+
+mod module;
+pub mod handler;
+pub mod mime;
+pub mod date;
+pub mod range;
+pub mod path;
+
+pub use self::module::Module;
+pub use self::handler::StaticHandler;
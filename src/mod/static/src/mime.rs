@@ -0,0 +1,53 @@
+//! MIME type detection.
+
+use std::path::Path;
+
+const DEFAULT_MIME_TYPE: &'static str = "application/octet-stream";
+
+
+/// Detect MIME type of the file by its extension.
+pub fn from_path(path: &Path) -> &'static str {
+	let extension = match path.extension().and_then(|ext| ext.to_str()) {
+		Some(ext) => ext.to_lowercase(),
+		None => return DEFAULT_MIME_TYPE,
+	};
+
+	match extension.as_ref() {
+		"html" | "htm" => "text/html; charset=utf-8",
+		"css" => "text/css; charset=utf-8",
+		"js" => "application/javascript; charset=utf-8",
+		"json" => "application/json",
+		"txt" => "text/plain; charset=utf-8",
+		"csv" => "text/csv; charset=utf-8",
+		"xml" => "application/xml",
+		"png" => "image/png",
+		"jpg" | "jpeg" => "image/jpeg",
+		"gif" => "image/gif",
+		"svg" => "image/svg+xml",
+		"ico" => "image/x-icon",
+		"webp" => "image/webp",
+		"pdf" => "application/pdf",
+		"zip" => "application/zip",
+		"gz" => "application/gzip",
+		"tar" => "application/x-tar",
+		"mp3" => "audio/mpeg",
+		"ogg" => "audio/ogg",
+		"mp4" => "video/mp4",
+		"webm" => "video/webm",
+		"woff" => "font/woff",
+		"woff2" => "font/woff2",
+		"ttf" => "font/ttf",
+		"otf" => "font/otf",
+		"wasm" => "application/wasm",
+		_ => DEFAULT_MIME_TYPE,
+	}
+}
+
+
+#[test]
+fn test_mime() {
+	assert_eq!(from_path(Path::new("index.html")), "text/html; charset=utf-8");
+	assert_eq!(from_path(Path::new("/img/LOGO.PNG")), "image/png");
+	assert_eq!(from_path(Path::new("archive.tar.gz")), "application/gzip");
+	assert_eq!(from_path(Path::new("README")), "application/octet-stream");
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+use wrust_types::net::connection::Descriptor;
+use wrust_conf::Conf;
+use wrust_module::{Facility, Category};
+use wrust_module::stream::{Behavior, Intention, Flush, Session};
+use wrust_mod_http::Module as HttpModule;
+use handler::StaticHandler;
+
+const MOD_NAME: &'static str = "static";
+
+const DEFAULT_ROOT: &'static str = "www";
+const DEFAULT_INDEX: &'static str = "index.html";
+
+/// The static file serving module is the HTTP module which
+/// dispatches all requests to `StaticHandler`.
+pub struct Module {
+	http: HttpModule,
+}
+
+#[inline(never)]
+impl Facility for Module {
+	fn new(config: &Conf, xpath: &String) -> Self {
+		// Read configuration
+		let root = config.lookup_str_or(&format!("{}.root", xpath), DEFAULT_ROOT);
+		let index = config.lookup_str_or(&format!("{}.index", xpath), DEFAULT_INDEX)
+			.split(',')
+			.map(|name| name.trim().to_string())
+			.filter(|name| !name.is_empty())
+			.collect();
+
+		let handler = StaticHandler::new(Path::new(root), index);
+
+		Module {
+			http: HttpModule::with_handler(config, xpath, Box::new(handler)),
+		}
+	}
+
+	fn name() -> String {
+		MOD_NAME.to_string()
+	}
+
+	fn version() -> String {
+		format!("{}.{}.{}", env!("CARGO_PKG_VERSION_MAJOR"), env!("CARGO_PKG_VERSION_MINOR"), env!("CARGO_PKG_VERSION_PATCH")).to_string()
+	}
+
+	fn category() -> Category {
+		Category::Stream
+	}
+}
+
+
+#[inline(never)]
+impl Behavior for Module {
+	fn open(self: &Self, desc: &Descriptor) -> (Intention, Session) {
+		self.http.open(desc)
+	}
+
+	fn read(self: &Self, desc: &Descriptor, session: &mut Session, buf: &Vec<u8>) -> Intention {
+		self.http.read(desc, session, buf)
+	}
+
+	fn write(self: &Self, desc: &Descriptor, session: &mut Session, buf: &mut Vec<u8>) -> (Intention, Flush) {
+		self.http.write(desc, session, buf)
+	}
+
+	fn close(self: &Self, desc: &Descriptor, session: Session) {
+		self.http.close(desc, session)
+	}
+}
@@ -0,0 +1,109 @@
+//! Request path resolution.
+
+use std::path::{Path, PathBuf};
+
+
+/// Resolve the request `path` against the document `root`. Returns `None` if
+/// the path is malformed or tries to escape the document root.
+pub fn resolve(root: &Path, path: &str) -> Option<PathBuf> {
+	let decoded = match decode(path) {
+		Some(decoded) => decoded,
+		None => return None,
+	};
+
+	let mut result = root.to_path_buf();
+	for segment in decoded.split('/') {
+		match segment {
+			"" | "." => (),
+			".." => return None,
+			_ => {
+				if segment.contains('\\') || segment.contains('\0') {
+					return None;
+				}
+
+				result.push(segment);
+			}
+		};
+	}
+
+	Some(result)
+}
+
+
+/// Normalize the request `path` leaving it encoded. Empty and `.` segments are dropped
+/// so the result starts with the single slash and has no empty segments.
+pub fn normalize(path: &str) -> String {
+	let segments: Vec<&str> = path
+		.split('/')
+		.filter(|segment| !segment.is_empty() && *segment != ".")
+		.collect();
+
+	format!("/{}", segments.join("/"))
+}
+
+
+/// Decode percent-encoded `value`. Returns `None` if the value is malformed
+/// or the result is not valid UTF-8.
+pub fn decode(value: &str) -> Option<String> {
+	let bytes = value.as_bytes();
+	let mut result: Vec<u8> = Vec::with_capacity(bytes.len());
+
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' {
+			if i + 2 >= bytes.len() {
+				return None;
+			}
+
+			let high = match hex_value(bytes[i + 1]) {
+				Some(value) => value,
+				None => return None,
+			};
+
+			let low = match hex_value(bytes[i + 2]) {
+				Some(value) => value,
+				None => return None,
+			};
+
+			result.push(high * 16 + low);
+			i += 3;
+		}
+		else {
+			result.push(bytes[i]);
+			i += 1;
+		}
+	}
+
+	String::from_utf8(result).ok()
+}
+
+
+fn hex_value(c: u8) -> Option<u8> {
+	match c {
+		b'0'...b'9' => Some(c - b'0'),
+		b'a'...b'f' => Some(c - b'a' + 10),
+		b'A'...b'F' => Some(c - b'A' + 10),
+		_ => None,
+	}
+}
+
+
+#[test]
+fn test_resolve() {
+	let root = Path::new("/srv/www");
+
+	assert_eq!(resolve(root, "/"), Some(PathBuf::from("/srv/www")));
+	assert_eq!(resolve(root, "/css/site.css"), Some(PathBuf::from("/srv/www/css/site.css")));
+	assert_eq!(resolve(root, "/a/./b//c"), Some(PathBuf::from("/srv/www/a/b/c")));
+	assert_eq!(resolve(root, "/my%20file.txt"), Some(PathBuf::from("/srv/www/my file.txt")));
+	assert_eq!(resolve(root, "/../etc/passwd"), None);
+	assert_eq!(resolve(root, "/a/%2e%2e/%2e%2e/etc/passwd"), None);
+	assert_eq!(resolve(root, "/a%2f..%2f..%2fetc"), None);
+	assert_eq!(resolve(root, "/a%5c..%5cb"), None);
+	assert_eq!(resolve(root, "/nul%00"), None);
+	assert_eq!(resolve(root, "/bad%2"), None);
+
+	assert_eq!(normalize("//evil.example"), "/evil.example");
+	assert_eq!(normalize("/a/./b//c%20d"), "/a/b/c%20d");
+	assert_eq!(normalize(""), "/");
+}
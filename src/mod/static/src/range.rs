@@ -0,0 +1,87 @@
+//! Byte range requests.
+
+/// The part of the resource requested with the Range header.
+#[derive(Debug, PartialEq)]
+pub enum Range {
+	/// The whole resource.
+	Full,
+	/// Bytes from the first to the last position inclusively.
+	Partial(u64, u64),
+	/// The range does not overlap the resource.
+	Unsatisfiable,
+}
+
+
+/// Parse the Range header `value` for the resource of `length` bytes. Unknown units,
+/// malformed and multiple ranges result in `Range::Full` so the whole resource is sent.
+pub fn parse(value: &str, length: u64) -> Range {
+	let value = value.trim();
+	if !value.starts_with("bytes=") {
+		return Range::Full;
+	}
+
+	let spec = value["bytes=".len()..].trim();
+	if spec.contains(',') {
+		return Range::Full;
+	}
+
+	let dash = match spec.find('-') {
+		Some(pos) => pos,
+		None => return Range::Full,
+	};
+
+	let (first, last) = (spec[..dash].trim(), spec[dash + 1..].trim());
+
+	if first.is_empty() {
+		// The suffix range selects the last bytes
+		let suffix = match last.parse::<u64>() {
+			Ok(suffix) => suffix,
+			Err(_) => return Range::Full,
+		};
+
+		if suffix == 0 || length == 0 {
+			return Range::Unsatisfiable;
+		}
+
+		return Range::Partial(length.saturating_sub(suffix), length - 1);
+	}
+
+	let first = match first.parse::<u64>() {
+		Ok(first) => first,
+		Err(_) => return Range::Full,
+	};
+
+	let last = if last.is_empty() {
+		None
+	}
+	else {
+		match last.parse::<u64>() {
+			Ok(last) if last >= first => Some(last),
+			_ => return Range::Full,
+		}
+	};
+
+	if first >= length {
+		return Range::Unsatisfiable;
+	}
+
+	match last {
+		Some(last) if last < length => Range::Partial(first, last),
+		_ => Range::Partial(first, length - 1),
+	}
+}
+
+
+#[test]
+fn test_range() {
+	assert_eq!(parse("bytes=0-499", 1000), Range::Partial(0, 499));
+	assert_eq!(parse("bytes=500-", 1000), Range::Partial(500, 999));
+	assert_eq!(parse("bytes=-200", 1000), Range::Partial(800, 999));
+	assert_eq!(parse("bytes=-2000", 1000), Range::Partial(0, 999));
+	assert_eq!(parse("bytes=900-1200", 1000), Range::Partial(900, 999));
+	assert_eq!(parse("bytes=1000-", 1000), Range::Unsatisfiable);
+	assert_eq!(parse("bytes=-0", 1000), Range::Unsatisfiable);
+	assert_eq!(parse("bytes=5-1", 1000), Range::Full);
+	assert_eq!(parse("bytes=0-1,5-6", 1000), Range::Full);
+	assert_eq!(parse("items=0-1", 1000), Range::Full);
+}